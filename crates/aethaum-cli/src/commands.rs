@@ -0,0 +1,7 @@
+pub mod init;
+pub mod add;
+pub mod new;
+pub mod check;
+pub mod build;
+pub mod run;
+pub mod watch;
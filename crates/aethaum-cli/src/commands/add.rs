@@ -0,0 +1,328 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use clap::Subcommand;
+use convert_case::{Case, Casing};
+use toml_edit::{DocumentMut, Item, Table, value};
+use aethaum_core::ecs::checker::context::ModuleCheckContext;
+use aethaum_core::ecs::checker::in_module::InModuleChecker;
+use aethaum_core::ecs::loader::ProjectLoader;
+use aethaum_core::ecs::module::{AethaumProject, EcsThingRef};
+use aethaum_core::toml_parser::parsed::{ComponentRef, EntityProtoRef, EventRef, SystemRef};
+use crate::error::CliError;
+
+#[derive(Debug, Subcommand)]
+pub enum AddTarget {
+    /// Create a module directory and register it in world.toml
+    Module {
+        name: String,
+        /// Module path relative to the project root, defaults to modules/<name>
+        #[arg(long)]
+        path: Option<String>,
+    },
+    /// Add a component definition to a module
+    Component {
+        module: String,
+        name: String,
+        /// File stem under components/, defaults to the snake_case name
+        #[arg(long)]
+        file: Option<String>,
+    },
+    /// Add an event definition to a module
+    Event {
+        module: String,
+        name: String,
+        /// File stem under events/, defaults to the snake_case name
+        #[arg(long)]
+        file: Option<String>,
+    },
+    /// Add a system definition to a module
+    System {
+        module: String,
+        name: String,
+    },
+    /// Add an entity prototype to a module
+    Proto {
+        module: String,
+        name: String,
+        /// Components of the prototype, e.g. --components Health,combat::Attack
+        #[arg(long, value_delimiter = ',')]
+        components: Vec<String>,
+        /// File stem under entity_protos/, defaults to the snake_case name
+        #[arg(long)]
+        file: Option<String>,
+    },
+}
+
+pub fn run(project_path: &Path, target: AddTarget) -> Result<(), CliError> {
+    match target {
+        AddTarget::Module { name, path } => add_module(project_path, &name, path),
+        AddTarget::Component { module, name, file } => {
+            let project = ProjectLoader::new(project_path.to_path_buf()).load()?;
+            let thing_ref = ComponentRef::from((module.as_str(), name.as_str())).into();
+            let file = file.unwrap_or_else(|| name.to_case(Case::Snake));
+            add_definition(&project, thing_ref, "components", &file, &component_stub(&name))
+        }
+        AddTarget::Event { module, name, file } => {
+            let project = ProjectLoader::new(project_path.to_path_buf()).load()?;
+            let thing_ref = EventRef::from((module.as_str(), name.as_str())).into();
+            let file = file.unwrap_or_else(|| name.to_case(Case::Snake));
+            add_definition(&project, thing_ref, "events", &file, &event_stub(&name))
+        }
+        AddTarget::Proto { module, name, components, file } => {
+            components.iter().try_for_each(|component| check_component_ref(component))?;
+            let project = ProjectLoader::new(project_path.to_path_buf()).load()?;
+            let thing_ref = EntityProtoRef::from((module.as_str(), name.as_str())).into();
+            let file = file.unwrap_or_else(|| name.to_case(Case::Snake));
+            add_definition(&project, thing_ref, "entity_protos", &file, &entity_proto_stub(&name, &components))
+        }
+        AddTarget::System { module, name } => {
+            let project = ProjectLoader::new(project_path.to_path_buf()).load()?;
+            let thing_ref = SystemRef::from((module.as_str(), name.as_str())).into();
+            let file = name.to_case(Case::Snake);
+            add_definition(&project, thing_ref, "systems", &file, &system_stub(&module, &name))
+        }
+    }
+}
+
+fn add_module(project_path: &Path, name: &str, path: Option<String>) -> Result<(), CliError> {
+    check_identifier(name)?;
+    let project = ProjectLoader::new(project_path.to_path_buf()).load()?;
+    if project.world.modules.modules.contains_key(name) {
+        return Err(CliError::ModuleExists(name.to_string()));
+    }
+    let module_path = path.unwrap_or_else(|| format!("modules/{name}"));
+    let module_dir = project.root.join(&module_path);
+    for dir in ["components", "systems", "events", "entity_protos"] {
+        fs::create_dir_all(module_dir.join(dir))?;
+    }
+
+    // 使用 toml_edit 修改 world.toml，以保留用户的注释与格式
+    let world_toml_path = project.root.join("world.toml");
+    let mut world_toml = fs::read_to_string(&world_toml_path)?.parse::<DocumentMut>()?;
+    let modules = world_toml.entry("modules").or_insert_with(|| Item::Table(Table::new()));
+    let modules = modules.as_table_like_mut().ok_or(CliError::MalformedWorld("[modules] is not a table"))?;
+    modules.insert(name, value(module_path.as_str()));
+    fs::write(&world_toml_path, world_toml.to_string())?;
+
+    println!("Added module '{}' at {}", name, module_dir.display());
+    Ok(())
+}
+///按照 InModuleChecker::try_register 的规则检查命名冲突后，写入定义的TOML桩
+fn add_definition(project: &AethaumProject, thing_ref: EcsThingRef, kind_dir: &str, file: &str, stub: &Stub) -> Result<(), CliError> {
+    check_identifier(thing_ref.name())?;
+    check_file_name(file)?;
+    let module_name = thing_ref.module_name();
+    let module_path = project.world.modules.modules.get(module_name)
+        .ok_or_else(|| CliError::ModuleNotFound(module_name.to_string()))?;
+    let module = project.module_tree.get_module(module_name)
+        .ok_or_else(|| CliError::ModuleNotFound(module_name.to_string()))?;
+
+    let mut module_context = ModuleCheckContext::new(module.name.clone(), project.root.clone());
+    // 已有定义之间的冲突由 aethaum check 报告，这里只关心新定义
+    let _ = InModuleChecker::register_module_definitions(module, &mut module_context);
+    InModuleChecker::try_register(thing_ref.clone(), &mut module_context)?;
+
+    let file_path: PathBuf = project.root.join(module_path).join(kind_dir).join(format!("{file}.toml"));
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if file_path.exists() {
+        match &stub.entry {
+            Some(entry) => {
+                let mut content = fs::read_to_string(&file_path)?;
+                if !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                content.push('\n');
+                content.push_str(entry);
+                fs::write(&file_path, content)?;
+            }
+            _ => return Err(CliError::AlreadyExists(file_path)),
+        }
+    } else {
+        fs::write(&file_path, &stub.full)?;
+    }
+    println!("Added {} in {}", thing_ref.as_error_str(), file_path.display());
+    Ok(())
+}
+
+fn check_identifier(name: &str) -> Result<(), CliError> {
+    let mut chars = name.chars();
+    let valid = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(CliError::InvalidIdentifier(name.to_string()))
+    }
+}
+
+///原型的组件直接写入TOML，须为 `Name` 或 `module::Name`
+fn check_component_ref(component: &str) -> Result<(), CliError> {
+    let component = component.trim();
+    let valid = match component.split_once("::") {
+        Some((module, name)) => check_identifier(module).and(check_identifier(name)).is_ok(),
+        None => check_identifier(component).is_ok(),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(CliError::InvalidIdentifier(component.to_string()))
+    }
+}
+///--file 给出的文件名只能是 kind_dir 下的文件，不能指向其他目录
+fn check_file_name(file: &str) -> Result<(), CliError> {
+    if file.is_empty() || file == "." || file == ".." || file.contains(['/', '\\']) {
+        Err(CliError::InvalidFileName(file.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+///TOML桩，full 用于新建文件，entry 用于追加到已有文件
+struct Stub {
+    full: String,
+    entry: Option<String>,
+}
+fn component_stub(name: &str) -> Stub {
+    let entry = format!(
+        r#"[[components]]
+name = "{name}"
+description = ""
+
+[[components.fields]]
+name = "value"
+type = "float"
+default = 0.0
+description = ""
+"#
+    );
+    Stub::multi_definition(entry)
+}
+fn event_stub(name: &str) -> Stub {
+    let entry = format!(
+        r#"[[events]]
+name = "{name}"
+description = ""
+
+# [[events.fields]]
+# name = "amount"
+# type = "float"
+# description = ""
+"#
+    );
+    Stub::multi_definition(entry)
+}
+fn entity_proto_stub(name: &str, components: &[String]) -> Stub {
+    let components = components.iter()
+        .map(|component| format!("\"{}\"", component.trim()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let entry = format!(
+        r#"[[entity_protos]]
+name = "{name}"
+components = [{components}]
+description = ""
+"#
+    );
+    Stub::multi_definition(entry)
+}
+fn system_stub(module: &str, name: &str) -> Stub {
+    Stub {
+        full: format!(
+            r#"#一个toml文件中，只能定义一个系统
+[normal]
+name = "{name}"
+description = ""
+category = "{module}"
+priority = 0
+
+[[queries]]
+name = "entities"
+components_include = []
+description = ""
+
+[update]
+interval = 1.0  # 更新间隔(秒)
+
+logic = '''
+'''
+
+# [[event_handlers]]
+# watch_for = "SomeEvent"
+# logic = '''
+# '''
+"#
+        ),
+        entry: None,
+    }
+}
+impl Stub {
+    ///组件，事件，实体原型可以在一个文件中定义多个
+    fn multi_definition(entry: String) -> Self {
+        Self {
+            full: format!("[normal]\ntags = []\ndescription = \"\"\n\n{entry}"),
+            entry: Some(entry),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use aethaum_core::ecs::checker::AethaumChecker;
+    use crate::templates::TEMPLATES;
+    use super::*;
+    #[test]
+    fn test_add_module_and_definitions() {
+        let target = std::env::temp_dir().join("aethaum_test_add");
+        let _ = fs::remove_dir_all(&target);
+        TEMPLATES[0].instantiate(&target, "AddWorld").unwrap();
+        fs::write(
+            target.join("world.toml"),
+            fs::read_to_string(target.join("world.toml")).unwrap() + "# trailing comment\n",
+        ).unwrap();
+
+        run(&target, AddTarget::Module { name: "magic".into(), path: None }).unwrap();
+        run(&target, AddTarget::Component { module: "magic".into(), name: "Mana".into(), file: None }).unwrap();
+        run(&target, AddTarget::Component { module: "magic".into(), name: "ManaRegen".into(), file: Some("mana".into()) }).unwrap();
+        run(&target, AddTarget::Event { module: "magic".into(), name: "SpellCast".into(), file: None }).unwrap();
+        run(&target, AddTarget::Proto { module: "magic".into(), name: "Mage".into(), components: vec!["Mana".into()], file: None }).unwrap();
+        run(&target, AddTarget::System { module: "magic".into(), name: "ManaSystem".into() }).unwrap();
+
+        let world_toml = fs::read_to_string(target.join("world.toml")).unwrap();
+        assert!(world_toml.contains("# trailing comment"));
+        assert!(world_toml.contains("magic = \"modules/magic\""));
+
+        assert!(matches!(
+            run(&target, AddTarget::Module { name: "magic".into(), path: None }),
+            Err(CliError::ModuleExists(_))
+        ));
+        assert!(matches!(
+            run(&target, AddTarget::Component { module: "magic".into(), name: "Mana".into(), file: Some("other".into()) }),
+            Err(CliError::Definition(_))
+        ));
+        assert!(matches!(
+            run(&target, AddTarget::Event { module: "magic".into(), name: "1Invalid".into(), file: None }),
+            Err(CliError::InvalidIdentifier(_))
+        ));
+
+        // 无效的参数在写入任何文件之前报错
+        for file in ["../escape", "sub/mana", "..", "", "a\\b"] {
+            assert!(matches!(
+                run(&target, AddTarget::Component { module: "magic".into(), name: "Focus".into(), file: Some(file.into()) }),
+                Err(CliError::InvalidFileName(_))
+            ), "{file}");
+        }
+        for component in ["Mana\"]", "combat::", "1Bad", "a::b::c"] {
+            assert!(matches!(
+                run(&target, AddTarget::Proto { module: "magic".into(), name: "Golem".into(), components: vec![component.into()], file: None }),
+                Err(CliError::InvalidIdentifier(_))
+            ), "{component}");
+        }
+        assert!(!target.join("modules/magic/entity_protos/golem.toml").exists());
+        assert!(!target.join("modules/magic/escape.toml").exists());
+
+        let project = ProjectLoader::new(target).load().unwrap();
+        assert_eq!(project.module_tree.get_module("magic").unwrap().components.as_ref().unwrap().len(), 2);
+        AethaumChecker::run_check(project).unwrap();
+    }
+}
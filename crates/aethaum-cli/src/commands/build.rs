@@ -0,0 +1,20 @@
+use std::path::{Path, PathBuf};
+use aethaum_core::code_generator::CodeGenerator;
+use crate::commands::check::load_and_check;
+use crate::error::CliError;
+
+pub fn run(path: &Path, output_dir: Option<PathBuf>) -> Result<(), CliError> {
+    let code_generator = generate(path, output_dir)?;
+    println!("Generated crate '{}' at {}", code_generator.project().world.crate_name(), code_generator.output_dir().display());
+    Ok(())
+}
+///检查并生成Rust项目，output_dir 覆盖 world.toml 中的 [build].output_dir
+pub fn generate(path: &Path, output_dir: Option<PathBuf>) -> Result<CodeGenerator, CliError> {
+    let project = load_and_check(path)?;
+    let mut code_generator = CodeGenerator::new(project);
+    if let Some(output_dir) = output_dir {
+        code_generator = code_generator.with_output_dir(output_dir);
+    }
+    code_generator.generate()?;
+    Ok(code_generator)
+}
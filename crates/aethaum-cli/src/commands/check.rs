@@ -0,0 +1,20 @@
+use std::path::Path;
+use aethaum_core::ecs::checker::AethaumChecker;
+use aethaum_core::ecs::loader::ProjectLoader;
+use aethaum_core::ecs::module::AethaumProject;
+use crate::error::CliError;
+
+pub fn run(path: &Path) -> Result<(), CliError> {
+    let project = load_and_check(path)?;
+    println!(
+        "Check passed: world '{}' with {} module(s)",
+        project.world.normal.name,
+        project.module_tree.get_modules().len()
+    );
+    Ok(())
+}
+///加载项目并执行全部检查，build/run 等命令都以此为起点
+pub fn load_and_check(path: &Path) -> Result<AethaumProject, CliError> {
+    let project = ProjectLoader::new(path.to_path_buf()).load()?;
+    Ok(AethaumChecker::run_check(project)?)
+}
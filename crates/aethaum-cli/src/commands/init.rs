@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::Path;
+use crate::error::CliError;
+
+///在已有目录中初始化一个空的Aethaum项目(world.toml, modules/, scripts/)
+pub fn run(path: &Path) -> Result<(), CliError> {
+    let world_toml_path = path.join("world.toml");
+    if world_toml_path.exists() {
+        return Err(CliError::AlreadyInitialized(path.to_path_buf()));
+    }
+    fs::create_dir_all(path.join("modules"))?;
+    fs::create_dir_all(path.join("scripts"))?;
+
+    let world_name = path.canonicalize()?
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "AethaumWorld".to_string());
+    fs::write(world_toml_path, world_toml_content(&world_name))?;
+
+    println!("Initialized Aethaum project '{}' in {}", world_name, path.display());
+    Ok(())
+}
+fn world_toml_content(world_name: &str) -> String {
+    // 目录名可能含有引号、反斜杠等字符，写成转义后的TOML字符串
+    let world_name = toml_edit::Value::from(world_name);
+    format!(
+        r#"[world]
+name = {world_name}
+version = "0.1.0"
+author = ""
+
+# 模块名 = 模块路径
+[modules]
+
+[build]
+output_dir = "generated"
+
+[cargo]
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_world_name_is_escaped() {
+        let name = r#"my "quoted" \ world"#;
+        let content = world_toml_content(name);
+        let world: toml_edit::DocumentMut = content.parse().unwrap();
+        assert_eq!(world["world"]["name"].as_str(), Some(name));
+    }
+}
@@ -0,0 +1,39 @@
+use std::path::Path;
+use crate::error::CliError;
+use crate::templates::Template;
+
+///以模板创建一个新的Aethaum项目目录
+pub fn run(path: &Path, template_name: &str) -> Result<(), CliError> {
+    let template = Template::find(template_name)
+        .ok_or_else(|| CliError::UnknownTemplate { name: template_name.to_string() })?;
+    // `..`、`.` 等路径没有目录名，无法作为世界名
+    let world_name = path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("'{}' has no directory name to use as the world name", path.display()),
+        ))?;
+    if path.exists() {
+        return Err(CliError::AlreadyExists(path.to_path_buf()));
+    }
+
+    template.instantiate(path, &world_name)?;
+    println!("Created Aethaum project '{}' from template '{}' ({})", world_name, template.name, template.description);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_without_directory_name() {
+        for path in ["..", "."] {
+            let Err(CliError::Io(error)) = run(Path::new(path), "default") else {
+                panic!("expected an invalid input error for '{path}'");
+            };
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+            assert!(error.to_string().contains("has no directory name"), "{error}");
+        }
+    }
+}
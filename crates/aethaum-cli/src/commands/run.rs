@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+use aethaum_core::toml_parser::parsed::BuildTarget;
+use std::process::{Command, Stdio};
+use std::io::BufRead;
+use crate::commands::build::generate;
+use crate::error::CliError;
+
+///生成项目后，通过 cargo 编译生成的 crate，并直接运行编译出的程序
+pub fn run(path: &Path, output_dir: Option<PathBuf>, release: bool, args: &[String]) -> Result<(), CliError> {
+    let code_generator = generate(path, output_dir)?;
+    if code_generator.project().world.build.target == BuildTarget::Lib {
+        return Err(CliError::NotRunnable(code_generator.project().world.crate_name().to_string()));
+    }
+    let manifest_path = code_generator.output_dir().join("Cargo.toml");
+
+    // 编译信息以JSON输出到标准输出，从中取得可执行文件的路径；诊断信息照常输出到标准错误
+    let mut build = Command::new("cargo");
+    build.arg("build")
+        .arg("--manifest-path").arg(&manifest_path)
+        .arg("--message-format=json-render-diagnostics")
+        .stdout(Stdio::piped());
+    if release {
+        build.arg("--release");
+    }
+    let mut child = build.spawn()?;
+    let mut executable = None;
+    if let Some(stdout) = child.stdout.take() {
+        for line in std::io::BufReader::new(stdout).lines() {
+            executable = bin_executable(&line?, code_generator.project().world.crate_name()).or(executable);
+        }
+    }
+    if !child.wait()?.success() {
+        return Err(CliError::CargoBuild);
+    }
+    let executable = executable.ok_or(CliError::CargoBuild)?;
+
+    let status = Command::new(executable)
+        .args(args)
+        // 生成的程序据此找到脚本文件与配置
+        .env("AETHAUM_PROJECT_ROOT", &code_generator.project().root)
+        .status()?;
+    if !status.success() {
+        return Err(CliError::ProgramFailed(status));
+    }
+    Ok(())
+}
+///cargo 的一条JSON消息为名为 bin_name 的可执行目标的编译产物时，返回可执行文件的路径
+fn bin_executable(message: &str, bin_name: &str) -> Option<PathBuf> {
+    let message: serde_json::Value = serde_json::from_str(message).ok()?;
+    if message["reason"] != "compiler-artifact" || message["target"]["name"] != bin_name {
+        return None;
+    }
+    message["executable"].as_str().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bin_executable() {
+        let artifact = r#"{"reason":"compiler-artifact","target":{"name":"MyWorld","kind":["bin"]},"executable":"/tmp/target/debug/MyWorld"}"#;
+        assert_eq!(bin_executable(artifact, "MyWorld"), Some(PathBuf::from("/tmp/target/debug/MyWorld")));
+        // 库与依赖没有可执行文件，其他目标不是要运行的程序
+        let library = r#"{"reason":"compiler-artifact","target":{"name":"my_world","kind":["lib"]},"executable":null}"#;
+        assert_eq!(bin_executable(library, "my_world"), None);
+        assert_eq!(bin_executable(artifact, "Other"), None);
+        assert_eq!(bin_executable(r#"{"reason":"build-finished","success":true}"#, "MyWorld"), None);
+        assert_eq!(bin_executable("not json", "MyWorld"), None);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use aethaum_core::code_generator::CodeGenerator;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use crate::commands::check::load_and_check;
+use crate::error::CliError;
+
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+pub fn run(path: &Path, output_dir: Option<PathBuf>) -> Result<(), CliError> {
+    let root = path.canonicalize()?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = RecommendedWatcher::new(tx, notify::Config::default())?;
+    let mut watched = HashSet::new();
+    // 编辑器常以改名的方式保存文件，单独监听 world.toml 会在第一次保存后失效，因此监听项目根目录本身
+    watcher.watch(&root, RecursiveMode::NonRecursive)?;
+
+    let (mut module_dirs, mut failed) = rebuild(&root, output_dir.as_deref(), &Changes::All);
+    update_watched_paths(&mut watcher, &mut watched, &root, module_dirs.as_deref())?;
+    println!("Watching {} for changes, press Ctrl+C to stop", root.display());
+
+    while let Ok(first) = rx.recv() {
+        // 编辑器保存时往往产生一连串事件，等待其平息后再统一处理
+        let mut paths = Vec::new();
+        let mut next = Some(first);
+        while let Some(result) = next {
+            match result {
+                Ok(event) => paths.extend(modified_paths(event)),
+                Err(err) => eprintln!("watch error: {err}"),
+            }
+            next = rx.recv_timeout(DEBOUNCE).ok();
+        }
+        paths.retain(|path| is_watched_file(&root, path));
+        if paths.is_empty() {
+            continue;
+        }
+        // 上次生成失败的模块即使没有变化也一并重试
+        let changes = Changes::classify(&root, module_dirs.as_deref(), paths.iter().map(PathBuf::as_path))
+            .merge(failed);
+        (module_dirs, failed) = rebuild(&root, output_dir.as_deref(), &changes);
+        update_watched_paths(&mut watcher, &mut watched, &root, module_dirs.as_deref())?;
+    }
+    Ok(())
+}
+
+///忽略仅读取文件产生的事件，否则每次重新加载都会再次触发
+fn modified_paths(event: Event) -> Vec<PathBuf> {
+    if event.kind.is_access() {
+        Vec::new()
+    } else {
+        event.paths
+    }
+}
+
+///项目根目录下只关心 world.toml，其他文件与目录自身的变化由各自的监听处理
+fn is_watched_file(root: &Path, path: &Path) -> bool {
+    path.parent() != Some(root) || path.file_name().is_some_and(|name| name == "world.toml")
+}
+
+///模块名与模块目录
+type ModuleDirs = Vec<(String, PathBuf)>;
+
+///一批文件变化所影响的范围
+#[derive(Debug, Clone, PartialEq)]
+enum Changes {
+    ///world.toml 变化，模块列表和路径都可能改变，需要全部重新生成
+    All,
+    ///只有这些模块目录下的文件变化，为空时(仅 scripts/ 变化)只需要重新检查
+    Modules(HashSet<String>),
+}
+impl Changes {
+    fn classify<'a>(root: &Path, module_dirs: Option<&[(String, PathBuf)]>, paths: impl Iterator<Item = &'a Path>) -> Self {
+        // 上次加载失败时不知道模块目录，只能全部重新生成
+        let Some(module_dirs) = module_dirs else {
+            return Changes::All;
+        };
+        let mut modules = HashSet::new();
+        for path in paths {
+            if path == root.join("world.toml") {
+                return Changes::All;
+            }
+            if let Some((name, _)) = module_dirs.iter().find(|(_, dir)| path.starts_with(dir)) {
+                modules.insert(name.clone());
+            }
+        }
+        Changes::Modules(modules)
+    }
+    fn merge(self, other: Changes) -> Self {
+        match (self, other) {
+            (Changes::Modules(mut modules), Changes::Modules(other)) => {
+                modules.extend(other);
+                Changes::Modules(modules)
+            }
+            _ => Changes::All,
+        }
+    }
+}
+
+///加载，检查并生成项目，打印诊断信息。返回模块目录(加载失败时为 None，等待下一次变化)与需要在下一次重试的变化
+fn rebuild(root: &Path, output_dir: Option<&Path>, changes: &Changes) -> (Option<ModuleDirs>, Changes) {
+    let project = match load_and_check(root) {
+        Ok(project) => project,
+        Err(err) => {
+            eprint!("{}", err.render());
+            // 模块目录未知时下一次会全部重新生成
+            return (None, Changes::Modules(HashSet::new()));
+        }
+    };
+    let module_dirs = project.world.modules.modules.iter()
+        .map(|(name, path)| (name.to_string(), root.join(path)))
+        .collect();
+    let module_names = match changes {
+        Changes::All => project.module_tree.get_modules().into_iter().map(|module| module.name.to_string()).collect(),
+        Changes::Modules(modules) => modules.iter().cloned().collect::<Vec<_>>(),
+    };
+    let module_names = module_names.iter().map(String::as_str).collect::<Vec<_>>();
+    let mut code_generator = CodeGenerator::new(project);
+    if let Some(output_dir) = output_dir {
+        code_generator = code_generator.with_output_dir(output_dir.to_path_buf());
+    }
+    let failed = match code_generator.generate_modules(&module_names) {
+        Ok(generated) => {
+            for path in &generated.written {
+                println!("Regenerated {}", path.display());
+            }
+            if generated.written.is_empty() && generated.failed.is_empty() {
+                println!("Check passed, generated code is up to date");
+            }
+            let mut failed = HashSet::new();
+            for (module, err) in generated.failed {
+                eprintln!("Failed to regenerate module `{module}`, it will be retried on the next change");
+                eprint!("{}", CliError::from(err).render());
+                failed.insert(module);
+            }
+            Changes::Modules(failed)
+        }
+        Err(err) => {
+            eprint!("{}", CliError::from(err).render());
+            changes.clone()
+        }
+    };
+    (Some(module_dirs), failed)
+}
+
+///监听各模块目录与 scripts/，模块列表变化后同步调整
+fn update_watched_paths(
+    watcher: &mut dyn Watcher,
+    watched: &mut HashSet<PathBuf>,
+    root: &Path,
+    module_dirs: Option<&[(String, PathBuf)]>,
+) -> Result<(), CliError> {
+    let mut wanted = HashSet::from([root.join("scripts")]);
+    match module_dirs {
+        Some(module_dirs) => wanted.extend(module_dirs.iter().map(|(_, dir)| dir.clone())),
+        // 加载失败时保持原有的模块目录
+        None => wanted.extend(watched.iter().cloned()),
+    }
+    wanted.retain(|path| path.exists());
+
+    for path in watched.difference(&wanted) {
+        let _ = watcher.unwatch(path);
+    }
+    for path in wanted.difference(watched) {
+        watcher.watch(path, RecursiveMode::Recursive)?;
+    }
+    *watched = wanted;
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_classify_changes() {
+        let root = PathBuf::from("/project");
+        let module_dirs = vec![
+            ("combat".to_string(), root.join("modules/combat")),
+            ("explore".to_string(), root.join("modules/explore")),
+        ];
+        let classify = |paths: &[&str]| {
+            let paths = paths.iter().map(|path| root.join(path)).collect::<Vec<_>>();
+            Changes::classify(&root, Some(&module_dirs), paths.iter().map(PathBuf::as_path))
+        };
+        assert_eq!(classify(&["world.toml", "modules/combat/components/health.toml"]), Changes::All);
+        assert_eq!(
+            classify(&["modules/combat/components/health.toml", "scripts/movement.lua"]),
+            Changes::Modules(HashSet::from(["combat".to_string()]))
+        );
+        assert_eq!(classify(&["scripts/movement.lua"]), Changes::Modules(HashSet::new()));
+        assert_eq!(Changes::classify(&root, None, std::iter::empty()), Changes::All);
+    }
+    #[test]
+    fn test_failed_modules_are_retried() {
+        let combat = Changes::Modules(HashSet::from(["combat".to_string()]));
+        let explore = Changes::Modules(HashSet::from(["explore".to_string()]));
+        assert_eq!(
+            explore.clone().merge(combat.clone()),
+            Changes::Modules(HashSet::from(["combat".to_string(), "explore".to_string()]))
+        );
+        assert_eq!(Changes::All.merge(combat.clone()), Changes::All);
+        assert_eq!(combat.merge(Changes::All), Changes::All);
+    }
+    #[test]
+    fn test_only_world_toml_is_watched_in_the_root() {
+        let root = PathBuf::from("/project");
+        assert!(is_watched_file(&root, &root.join("world.toml")));
+        assert!(!is_watched_file(&root, &root.join(".world.toml.swp")));
+        assert!(!is_watched_file(&root, &root.join("scripts")));
+        assert!(is_watched_file(&root, &root.join("scripts/movement.lua")));
+    }
+}
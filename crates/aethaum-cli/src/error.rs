@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+use std::process::{ExitCode, ExitStatus};
+use miette::{Diagnostic, GraphicalReportHandler};
+use thiserror::Error;
+use aethaum_core::code_generator::CodeGenerationError;
+use aethaum_core::ecs::checker::CheckerError;
+use aethaum_core::ecs::checker::in_module::InModuleCheckError;
+use aethaum_core::ecs::loader::ProjectLoaderError;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum CliError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("'{0}' already contains a world.toml")]
+    AlreadyInitialized(PathBuf),
+    #[error("'{0}' already exists")]
+    AlreadyExists(PathBuf),
+    #[error("unknown template '{name}', available templates: {}",
+        crate::templates::TEMPLATES.iter().map(|t| t.name).collect::<Vec<_>>().join(", "))]
+    UnknownTemplate {
+        name: String,
+    },
+    #[error("'{0}' is not a valid identifier")]
+    InvalidIdentifier(String),
+    #[error("'{0}' is not a valid file name, it must not be empty, `.`, `..` or contain path separators")]
+    InvalidFileName(String),
+    #[error("module '{0}' already exists")]
+    ModuleExists(String),
+    #[error("module '{0}' not found in world.toml")]
+    ModuleNotFound(String),
+    #[error("malformed world.toml: {0}")]
+    MalformedWorld(&'static str),
+    #[error("failed to edit world.toml: {0}")]
+    TomlEdit(#[from] toml_edit::TomlError),
+    #[error("{0}")]
+    #[diagnostic(transparent)]
+    Definition(#[from] InModuleCheckError),
+    #[error("failed to watch files: {0}")]
+    Watch(#[from] notify::Error),
+    #[error("failed to load project: {0}")]
+    #[diagnostic(transparent)]
+    Load(#[from] ProjectLoaderError),
+    #[error("check failed: {0}")]
+    #[diagnostic(transparent)]
+    Check(#[from] CheckerError),
+    #[error("code generation failed: {0}")]
+    Generation(#[from] CodeGenerationError),
+    #[error("crate '{0}' is built as a library ([build].target = \"lib\") and cannot be run")]
+    NotRunnable(String),
+    #[error("cargo failed to build the generated crate")]
+    CargoBuild,
+    #[error("generated program exited with {0}")]
+    ProgramFailed(ExitStatus),
+}
+impl CliError {
+    ///带有位置的错误渲染为标注的源码片段，错误信息已包含内层错误，不再列出错误链
+    pub fn render(&self) -> String {
+        let mut report = String::new();
+        match GraphicalReportHandler::new().without_cause_chain().render_report(&mut report, self) {
+            Ok(()) => report,
+            Err(_) => format!("error: {self}\n"),
+        }
+    }
+    ///退出码约定：
+    /// 1 - IO及其他错误，2 - 命令行参数错误(由clap处理)，3 - 项目加载失败，
+    /// 4 - 检查失败，5 - 代码生成失败，6 - cargo编译失败，其余为被运行程序自身的退出码
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            CliError::Io(_)
+            | CliError::AlreadyInitialized(_)
+            | CliError::AlreadyExists(_)
+            | CliError::UnknownTemplate { .. }
+            | CliError::InvalidIdentifier(_)
+            | CliError::InvalidFileName(_)
+            | CliError::ModuleExists(_)
+            | CliError::ModuleNotFound(_)
+            | CliError::MalformedWorld(_)
+            | CliError::TomlEdit(_)
+            | CliError::Definition(_)
+            | CliError::Watch(_)
+            | CliError::NotRunnable(_) => ExitCode::from(1),
+            CliError::Load(_) => ExitCode::from(3),
+            CliError::Check(_) => ExitCode::from(4),
+            CliError::Generation(_) => ExitCode::from(5),
+            CliError::CargoBuild => ExitCode::from(6),
+            CliError::ProgramFailed(status) => status.code()
+                .and_then(|code| u8::try_from(code).ok())
+                .filter(|code| *code != 0)
+                .map(ExitCode::from)
+                .unwrap_or(ExitCode::FAILURE),
+        }
+    }
+}
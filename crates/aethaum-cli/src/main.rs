@@ -1,3 +1,99 @@
-fn main() {
-    println!("Hello, world!");
+mod commands;
+mod error;
+mod templates;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[command(name = "aethaum", version, about = "Aethaum: a declarative framework for AI interactive worlds")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Initialize an Aethaum project in an existing directory
+    Init {
+        /// Project directory
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+    /// Create a new Aethaum project from a template
+    New {
+        /// Directory of the new project, its name is used as the world name
+        path: PathBuf,
+        /// Template to use
+        #[arg(long, default_value = templates::DEFAULT_TEMPLATE)]
+        template: String,
+    },
+    /// Add a module or a definition stub to the project
+    Add {
+        #[command(subcommand)]
+        target: commands::add::AddTarget,
+        /// Project directory (containing world.toml)
+        #[arg(long, default_value = ".")]
+        project: PathBuf,
+    },
+    /// Load the project and run all checks
+    Check {
+        /// Project directory (containing world.toml)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
+    /// Check the project and generate the Rust crate
+    Build {
+        /// Project directory (containing world.toml)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Output directory of the generated crate, overrides [build].output_dir
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Re-check and regenerate the project whenever its files change
+    Watch {
+        /// Project directory (containing world.toml)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Output directory of the generated crate, overrides [build].output_dir
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Build the project, then compile and launch the generated crate
+    Run {
+        /// Project directory (containing world.toml)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Output directory of the generated crate, overrides [build].output_dir
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+        /// Compile the generated crate in release mode
+        #[arg(long)]
+        release: bool,
+        /// Arguments passed to the generated program
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Init { path } => commands::init::run(&path),
+        Command::New { path, template } => commands::new::run(&path, &template),
+        Command::Add { target, project } => commands::add::run(&project, target),
+        Command::Check { path } => commands::check::run(&path),
+        Command::Build { path, output_dir } => commands::build::run(&path, output_dir),
+        Command::Watch { path, output_dir } => commands::watch::run(&path, output_dir),
+        Command::Run { path, output_dir, release, args } => commands::run::run(&path, output_dir, release, &args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprint!("{}", err.render());
+            err.exit_code()
+        }
+    }
 }
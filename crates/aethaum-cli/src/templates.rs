@@ -0,0 +1,88 @@
+use std::fs;
+use std::path::Path;
+
+///项目模板，模板文件位于仓库的 templates/ 目录下，编译时嵌入二进制
+pub struct Template {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub files: &'static [TemplateFile],
+}
+pub struct TemplateFile {
+    pub path: &'static str,
+    pub content: &'static str,
+}
+
+macro_rules! template_file {
+    ($template:literal, $path:literal) => {
+        TemplateFile {
+            path: $path,
+            content: include_str!(concat!("../../../templates/", $template, "/", $path)),
+        }
+    };
+}
+
+pub const DEFAULT_TEMPLATE: &str = "default";
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "default",
+        description: "A single 'basic' module with components, an event, a prototype and a Lua-driven system",
+        files: &[
+            template_file!("default", "world.toml"),
+            template_file!("default", "config/config.toml"),
+            template_file!("default", "modules/basic/components/basic.toml"),
+            template_file!("default", "modules/basic/events/basic.toml"),
+            template_file!("default", "modules/basic/entity_protos/basic.toml"),
+            template_file!("default", "modules/basic/systems/regeneration.toml"),
+            template_file!("default", "scripts/regeneration.lua"),
+        ],
+    },
+];
+
+impl Template {
+    pub fn find(name: &str) -> Option<&'static Template> {
+        TEMPLATES.iter().find(|template| template.name == name)
+    }
+    ///将模板写入目标目录，`{{name}}` 会被替换为世界名称的TOML字符串(带引号并转义)
+    pub fn instantiate(&self, target: &Path, world_name: &str) -> std::io::Result<()> {
+        let world_name = toml_edit::Value::from(world_name).to_string();
+        for file in self.files {
+            let path = target.join(file.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, file.content.replace("{{name}}", &world_name))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aethaum_core::code_generator::CodeGenerator;
+    use aethaum_core::ecs::checker::AethaumChecker;
+    use aethaum_core::ecs::loader::ProjectLoader;
+    use super::*;
+    #[test]
+    fn test_templates_pass_check_and_generate() {
+        for template in TEMPLATES {
+            let target = std::env::temp_dir().join(format!("aethaum_template_{}", template.name));
+            let _ = fs::remove_dir_all(&target);
+            template.instantiate(&target, "TemplateWorld").unwrap();
+
+            let project = ProjectLoader::new(target.clone()).load().unwrap();
+            assert_eq!(project.world.normal.name, "TemplateWorld");
+            let project = AethaumChecker::run_check(project).unwrap();
+            CodeGenerator::new(project).generate().unwrap();
+        }
+    }
+    #[test]
+    fn test_world_name_is_escaped() {
+        let target = std::env::temp_dir().join("aethaum_template_escaped_name");
+        let _ = fs::remove_dir_all(&target);
+        let name = r#"my "quoted" \ world"#;
+        TEMPLATES[0].instantiate(&target, name).unwrap();
+        let world: toml_edit::DocumentMut = fs::read_to_string(target.join("world.toml")).unwrap().parse().unwrap();
+        assert_eq!(world["world"]["name"].as_str(), Some(name));
+    }
+}
@@ -0,0 +1,37 @@
+//! 生成的 crate 的各个测试共用的辅助函数，由 aethaum-core 的 test_generated_runtime 复制到 tests 目录
+#![allow(dead_code)]
+use bevy_app::App;
+use bevy_ecs::prelude::*;
+use mlua::prelude::*;
+use my_ai_world::aethaum_predefined::*;
+use my_ai_world::lua_bindings::*;
+use my_ai_world::modules::AethaumModulesPlugin;
+
+///注册了Lua运行时与所有模块插件的 App。时钟不前进，[update] 不会执行，只有事件处理器会运行
+pub fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins(bevy_time::TimePlugin)
+        .insert_resource(bevy_time::TimeUpdateStrategy::ManualDuration(std::time::Duration::ZERO));
+    app.add_plugins((LuaPlugin, AethaumModulesPlugin));
+    app.finish();
+    app.cleanup();
+    app
+}
+
+///在 ecs 可用时执行 source，返回脚本的结果
+pub fn run<R: FromLuaMulti>(world: &mut World, source: &str) -> LuaResult<R> {
+    world.resource_scope(|world, runtime: Mut<LuaRuntime>| {
+        let lua = &runtime.lua;
+        let function = lua.load(source).into_function()?;
+        with_ecs(world, lua, |_| call_lua_script(lua, &function))
+    })
+}
+
+///以 bindings 为 entity 绑定 entity 表，在 ecs 可用时执行 source，与 [update] 执行 logic 的方式相同
+pub fn run_for_entity(world: &mut World, entity: Entity, bindings: &[ComponentBinding], source: &str) -> LuaResult<()> {
+    world.resource_scope(|world, runtime: Mut<LuaRuntime>| {
+        let lua = &runtime.lua;
+        let logic = lua.load(source).into_function()?;
+        with_ecs(world, lua, |world| run_lua_for_entity(world, lua, entity, bindings, None, &logic))
+    })
+}
@@ -0,0 +1,18 @@
+//! 读取 config/config.toml。项目根目录通过环境变量指定，因此单独作为一个测试程序，且只有一个测试
+use my_ai_world::aethaum_predefined::*;
+
+#[test]
+fn malformed_config_falls_back_to_the_default() {
+    let root = std::env::temp_dir().join("aethaum_test_malformed_config");
+    std::fs::create_dir_all(root.join("config")).unwrap();
+    std::fs::write(root.join(CONFIG_FILE), "[lua\ninstruction_limit = ").unwrap();
+    // SAFETY: 该测试程序中只有这一个测试，没有其他线程读取环境变量
+    unsafe { std::env::set_var("AETHAUM_PROJECT_ROOT", &root) };
+    assert!(load_config().is_empty());
+    let runtime = LuaRuntime::default();
+    let count: i64 = runtime.lua.load("return 1 + 1").eval().unwrap();
+    assert_eq!(count, 2);
+
+    std::fs::write(root.join(CONFIG_FILE), "[lua]\ninstruction_limit = 7").unwrap();
+    assert_eq!(LuaLimits::from_config(&load_config()).instruction_limit, 7);
+}
@@ -0,0 +1,161 @@
+//! 脚本中的 ecs 表，每个调用都同步执行
+mod common;
+
+use bevy_ecs::prelude::*;
+use mlua::prelude::*;
+use my_ai_world::aethaum_predefined::*;
+use my_ai_world::modules::combat::components::Health;
+use my_ai_world::modules::explore::components::{Damage, Position};
+
+fn entity(world: &mut World, source: &str) -> Entity {
+    let bits: i64 = common::run(world, source).unwrap();
+    Entity::from_lua_value(LuaValue::Integer(bits), &Lua::new()).unwrap()
+}
+
+#[test]
+fn spawn_with_components() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let spawned = entity(world, r#"return ecs.spawn(nil, { Health = { value = 7 }, Position = {} })"#);
+    assert_eq!(world.get::<Health>(spawned).unwrap().value, 7.0);
+    assert_eq!(world.get::<Position>(spawned).unwrap().x, 0.0);
+    assert!(world.get::<Damage>(spawned).is_none());
+}
+
+#[test]
+fn spawn_empty() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let spawned = entity(world, "return ecs.spawn()");
+    assert!(world.get_entity(spawned).is_ok());
+    assert!(world.get::<Health>(spawned).is_none());
+}
+
+#[test]
+fn spawn_is_visible_to_the_same_script() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let has: bool = common::run(world, r#"
+        local e = ecs.spawn(nil, { Health = { value = 1 } })
+        return ecs.has(e, "combat::Health")
+    "#).unwrap();
+    assert!(has);
+}
+
+#[test]
+fn get_components() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let target = world.spawn((Health { value: 42.0 }, Position { x: 1.0, y: 2.0 })).id();
+    let (health, y, damage): (f64, f64, LuaValue) = common::run(world, &format!(r#"
+        local health, position, damage = ecs.get({}, "Health", "explore::Position", "Damage")
+        return health.value, position.y, damage
+    "#, target.to_bits())).unwrap();
+    assert_eq!(health, 42.0);
+    assert_eq!(y, 2.0);
+    assert!(damage.is_nil());
+}
+
+#[test]
+fn set_replaces_or_inserts_components() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let target = world.spawn((Health { value: 42.0 }, Position { x: 1.0, y: 2.0 })).id();
+    common::run::<()>(world, &format!(r#"
+        ecs.set({0}, "Position", {{ x = 5 }})
+        ecs.set({0}, "Damage", {{ amount = 3 }})
+    "#, target.to_bits())).unwrap();
+    let position = world.get::<Position>(target).unwrap();
+    assert_eq!((position.x, position.y), (5.0, 0.0));
+    assert_eq!(world.get::<Damage>(target).unwrap().amount, 3.0);
+}
+
+#[test]
+fn has_and_remove() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let target = world.spawn((Health { value: 42.0 }, Damage { amount: 1.0 })).id();
+    let (before, after): (bool, bool) = common::run(world, &format!(r#"
+        local before = ecs.has({0}, "Damage")
+        ecs.remove({0}, "Damage")
+        return before, ecs.has({0}, "Damage")
+    "#, target.to_bits())).unwrap();
+    assert!(before);
+    assert!(!after);
+    assert!(world.get::<Damage>(target).is_none());
+    assert!(world.get::<Health>(target).is_some());
+}
+
+#[test]
+fn despawn() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let target = world.spawn(Health { value: 42.0 }).id();
+    common::run::<()>(world, &format!("ecs.despawn({})", target.to_bits())).unwrap();
+    assert!(world.get_entity(target).is_err());
+}
+
+#[test]
+fn unknown_names_are_errors() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let target = world.spawn(Health { value: 42.0 }).id();
+    let err = common::run::<()>(world, &format!(r#"ecs.set({}, "Mana", {{}})"#, target.to_bits())).unwrap_err();
+    assert!(err.to_string().contains("no such component: Mana"), "{err}");
+    let err = common::run::<()>(world, r#"ecs.spawn("Ghost")"#).unwrap_err();
+    assert!(err.to_string().contains("no such entity prototype: Ghost"), "{err}");
+}
+
+#[test]
+fn query_views_use_entity_table_keys() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let target = world.spawn((Health { value: 42.0 }, Position { x: 1.0, y: 2.0 })).id();
+    world.spawn(Damage { amount: 1.0 });
+    let (count, y, damage): (i64, f64, LuaValue) = common::run(world, r#"
+        local count, y, damage = 0, nil, nil
+        for e in ecs.query({ include = { "Health" } }) do
+            count = count + 1
+            e.health.value = e["combat::health"].value + 1
+            y, damage = e.position.y, e.damage
+        end
+        return count, y, damage
+    "#).unwrap();
+    assert_eq!((count, y), (1, 2.0));
+    assert!(damage.is_nil());
+    assert_eq!(world.get::<Health>(target).unwrap().value, 43.0);
+    // 视图的键与 entity 表相同，不接受组件的类型名
+    let err = common::run::<()>(world, r#"for e in ecs.query({ include = { "Health" } }) do local _ = e.Health end"#).unwrap_err();
+    assert!(err.to_string().contains("no such component: Health"), "{err}");
+}
+
+#[test]
+fn set_checks_component_fields() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let target = world.spawn(Health { value: 42.0 }).id();
+    common::run::<()>(world, &format!(r#"ecs.set({}, "Health", {{}})"#, target.to_bits())).unwrap();
+    assert_eq!(world.get::<Health>(target).unwrap().value, 100.0);
+    let err = common::run::<()>(world, &format!(r#"ecs.set({}, "Health", {{ hp = 1 }})"#, target.to_bits())).unwrap_err();
+    assert!(err.to_string().contains("unknown field `hp` for component Health"), "{err}");
+    let err = common::run::<()>(world, &format!(r#"ecs.set({}, "Health", {{ value = "full" }})"#, target.to_bits())).unwrap_err();
+    assert!(err.to_string().contains("invalid field `value` of component Health"), "{err}");
+}
+
+#[test]
+fn spawn_proto_with_overrides() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let spawned = entity(world, r#"return ecs.spawn("explore::Player", { Health = { value = 50 }, Position = { y = 3 } })"#);
+    assert_eq!(world.get::<Health>(spawned).unwrap().value, 50.0);
+    let position = world.get::<Position>(spawned).unwrap();
+    assert_eq!((position.x, position.y), (0.0, 3.0));
+
+    let spawned = entity(world, r#"return ecs.spawn("Enemy")"#);
+    assert_eq!(world.get::<Health>(spawned).unwrap().value, 100.0);
+    assert!(world.get::<Position>(spawned).is_some());
+
+    // 覆盖表的键只能是原型中的组件
+    let err = common::run::<()>(world, r#"ecs.spawn("Player", { Damage = { amount = 1 } })"#).unwrap_err();
+    assert!(err.to_string().contains("unknown field `Damage` for entity prototype Player"), "{err}");
+}
@@ -0,0 +1,121 @@
+//! entity 表的写回
+mod common;
+
+use my_ai_world::aethaum_predefined::*;
+use my_ai_world::lua_bindings::with_ecs;
+use my_ai_world::modules::combat::components::Health;
+use my_ai_world::modules::explore::components::{Armor, Damage};
+
+fn bindings() -> Vec<ComponentBinding> {
+    vec![ComponentBinding::of::<Health>("health"), ComponentBinding::of::<Damage>("damage")]
+}
+
+#[test]
+fn writes_back_changed_fields() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let entity = world.spawn((Health { value: 100.0 }, Damage { amount: 5.0 })).id();
+    common::run_for_entity(world, entity, &bindings(), r#"
+        entity.health.value = entity.health.value - entity.damage.amount
+        entity.damage.amount = 0
+    "#).unwrap();
+    assert_eq!(world.get::<Health>(entity).unwrap().value, 95.0);
+    assert_eq!(world.get::<Damage>(entity).unwrap().amount, 0.0);
+}
+
+#[test]
+fn writes_back_fields_that_were_nil() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let entity = world.spawn(Armor { shield: None }).id();
+    let bindings = [ComponentBinding::of::<Armor>("armor")];
+    common::run_for_entity(world, entity, &bindings, "entity.armor.shield = 5").unwrap();
+    assert_eq!(world.get::<Armor>(entity).unwrap().shield, Some(5.0));
+    common::run_for_entity(world, entity, &bindings, "entity.armor.shield = nil").unwrap();
+    assert_eq!(world.get::<Armor>(entity).unwrap().shield, None);
+}
+
+#[test]
+fn rejects_fields_added_by_the_script() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let entity = world.spawn(Health { value: 100.0 }).id();
+    let err = common::run_for_entity(world, entity, &bindings(), "entity.health.armor = 5").unwrap_err();
+    assert!(err.to_string().contains("unknown field `armor`"), "{err}");
+}
+
+#[test]
+fn keeps_view_writes_to_the_current_entity() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let entity = world.spawn((Health { value: 100.0 }, Damage { amount: 5.0 })).id();
+    common::run_for_entity(world, entity, &bindings(), r#"
+        for e in ecs.query({ include = { "Health" } }) do
+            e.health.value = 50
+        end
+        entity.damage.amount = 0
+    "#).unwrap();
+    assert_eq!(world.get::<Health>(entity).unwrap().value, 50.0);
+    assert_eq!(world.get::<Damage>(entity).unwrap().amount, 0.0);
+}
+
+#[test]
+fn keeps_ecs_set_on_the_current_entity() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let entity = world.spawn((Health { value: 100.0 }, Damage { amount: 5.0 })).id();
+    common::run_for_entity(world, entity, &bindings(), r#"
+        ecs.set(entity.id, "Health", { value = 20 })
+        entity.damage.amount = 1
+    "#).unwrap();
+    assert_eq!(world.get::<Health>(entity).unwrap().value, 20.0);
+    assert_eq!(world.get::<Damage>(entity).unwrap().amount, 1.0);
+}
+
+#[test]
+fn entity_writes_override_ecs_writes_to_the_same_field() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let entity = world.spawn((Health { value: 100.0 }, Damage { amount: 5.0 })).id();
+    common::run_for_entity(world, entity, &bindings(), r#"
+        ecs.set(entity.id, "Health", { value = 20 })
+        entity.health.value = 30
+    "#).unwrap();
+    assert_eq!(world.get::<Health>(entity).unwrap().value, 30.0);
+}
+
+#[test]
+fn does_not_restore_removed_components() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let entity = world.spawn((Health { value: 100.0 }, Damage { amount: 5.0 })).id();
+    common::run_for_entity(world, entity, &bindings(), r#"
+        ecs.remove(entity.id, "Damage")
+        entity.damage.amount = 1
+    "#).unwrap();
+    assert!(world.get::<Damage>(entity).is_none());
+}
+
+#[test]
+fn condition_errors_are_told_apart_from_logic_errors() {
+    let mut app = common::app();
+    let world = app.world_mut();
+    let entity = world.spawn((Health { value: 100.0 }, Damage { amount: 5.0 })).id();
+    let bindings = bindings();
+    world.resource_scope(|world, runtime: bevy_ecs::prelude::Mut<LuaRuntime>| {
+        let lua = &runtime.lua;
+        let condition = lua.load("return entity.mana.value > 0").into_function().unwrap();
+        let logic = lua.load("entity.health.value = 0").into_function().unwrap();
+        let err = with_ecs(world, lua, |world| run_lua_for_entity(world, lua, entity, &bindings, Some(&condition), &logic)).unwrap_err();
+        assert!(err.downcast_ref::<LuaConditionError>().is_some(), "{err}");
+
+        let condition = lua.load("return true").into_function().unwrap();
+        let logic = lua.load("entity.mana.value = 0").into_function().unwrap();
+        let err = with_ecs(world, lua, |world| run_lua_for_entity(world, lua, entity, &bindings, Some(&condition), &logic)).unwrap_err();
+        assert!(err.downcast_ref::<LuaConditionError>().is_none(), "{err}");
+    });
+    assert_eq!(world.get::<Health>(entity).unwrap().value, 100.0);
+    let key = LuaScriptKey { module: "explore", system: "HealthSystem", handler: "update", part: LuaScriptPart::Condition };
+    assert_eq!(key.to_string(), "explore::HealthSystem (update condition)");
+    assert_eq!(LuaScriptKey { part: LuaScriptPart::Logic, ..key }.to_string(), "explore::HealthSystem (update)");
+}
@@ -0,0 +1,96 @@
+//! events.emit 发送的事件在本tick或下一个tick送达
+mod common;
+
+use bevy_app::App;
+use bevy_ecs::prelude::*;
+use my_ai_world::aethaum_predefined::*;
+use my_ai_world::modules::combat::components::Health;
+use my_ai_world::modules::explore::components::Position;
+
+///HealthSystem 的优先级为100，它的 EntityDamaged 处理器使 entity.health 减去 event.damage
+const HEALTH_SYSTEM_PRIORITY: u32 = 100;
+
+///第一个tick中执行一次 source
+fn emit_once(app: &mut App, priority: AethaumPriority, source: &'static str) {
+    add_prioritized_systems(app, priority, move |world: &mut World, mut emitted: Local<bool>| {
+        if !std::mem::replace(&mut *emitted, true) {
+            common::run::<()>(world, source).unwrap();
+        }
+    });
+}
+
+fn health(app: &mut App) -> f64 {
+    let mut query = app.world_mut().query::<&Health>();
+    query.single(app.world()).unwrap().value
+}
+
+fn spawn_target(app: &mut App) {
+    app.world_mut().spawn((Health { value: 100.0 }, Position { x: 0.0, y: 0.0 }));
+}
+
+#[test]
+fn handlers_after_the_sender_receive_in_the_same_tick() {
+    let mut app = common::app();
+    spawn_target(&mut app);
+    emit_once(&mut app, AethaumPriority::update(HEALTH_SYSTEM_PRIORITY + 1), r#"
+        events.emit("explore::EntityDamaged", { damage = 10, attacker = ecs.spawn() })
+    "#);
+    app.update();
+    assert_eq!(health(&mut app), 90.0);
+    app.update();
+    assert_eq!(health(&mut app), 90.0);
+}
+
+#[test]
+fn handlers_before_the_sender_receive_in_the_next_tick() {
+    let mut app = common::app();
+    spawn_target(&mut app);
+    emit_once(&mut app, AethaumPriority::update(HEALTH_SYSTEM_PRIORITY - 1), r#"
+        events.emit("explore::EntityDamaged", { damage = 10, attacker = ecs.spawn() })
+    "#);
+    app.update();
+    assert_eq!(health(&mut app), 100.0);
+    app.update();
+    assert_eq!(health(&mut app), 90.0);
+    app.update();
+    assert_eq!(health(&mut app), 90.0);
+}
+
+#[test]
+fn every_emitted_event_is_delivered_once() {
+    let mut app = common::app();
+    spawn_target(&mut app);
+    emit_once(&mut app, AethaumPriority::update(HEALTH_SYSTEM_PRIORITY + 1), r#"
+        local attacker = ecs.spawn()
+        events.emit("EntityDamaged", { damage = 1, attacker = attacker })
+        events.emit("EntityDamaged", { damage = 2, attacker = attacker })
+        events.emit("EntityDamaged", { damage = 4, attacker = attacker })
+    "#);
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(health(&mut app), 93.0);
+}
+
+#[test]
+fn events_reach_a_writer_in_emit_order() {
+    let mut app = common::app();
+    common::run::<()>(app.world_mut(), r#"
+        local attacker = ecs.spawn()
+        for _, damage in ipairs({ 3, 1, 2 }) do
+            events.emit("EntityDamaged", { damage = damage, attacker = attacker })
+        end
+    "#).unwrap();
+    let events = app.world().resource::<Events<my_ai_world::modules::explore::events::EntityDamaged>>();
+    let damages = events.iter_current_update_events().map(|event| event.damage).collect::<Vec<_>>();
+    assert_eq!(damages, vec![3.0, 1.0, 2.0]);
+}
+
+#[test]
+fn payload_must_match_the_event_fields() {
+    let mut app = common::app();
+    let err = common::run::<()>(app.world_mut(), r#"events.emit("EntityDamaged", { damage = 1 })"#).unwrap_err();
+    assert!(err.to_string().contains("invalid field `attacker` of event EntityDamaged"), "{err}");
+    let err = common::run::<()>(app.world_mut(), r#"events.emit("EntityDamaged", { damage = 1, attacker = 0, crit = true })"#).unwrap_err();
+    assert!(err.to_string().contains("unknown field `crit` for event EntityDamaged"), "{err}");
+}
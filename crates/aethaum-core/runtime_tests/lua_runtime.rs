@@ -0,0 +1,96 @@
+//! Lua运行时的沙箱与资源限制
+use mlua::prelude::*;
+use my_ai_world::aethaum_predefined::*;
+
+fn call<R: FromLuaMulti>(runtime: &LuaRuntime, source: &str) -> LuaResult<R> {
+    let function = runtime.lua.load(source).set_environment(runtime.script_env()?).into_function()?;
+    call_lua_script(&runtime.lua, &function)
+}
+
+#[test]
+fn sandbox_hides_files_and_processes() {
+    let runtime = LuaRuntime::new(LuaLimits::default());
+    let hidden: bool = call(&runtime, r#"
+        return io == nil and require == nil and dofile == nil and loadfile == nil
+            and os.execute == nil and os.remove == nil and os.getenv == nil and package == nil
+    "#).unwrap();
+    assert!(hidden);
+    let kept: bool = call(&runtime, "return os.time() ~= nil and os.clock() ~= nil").unwrap();
+    assert!(kept);
+}
+
+#[test]
+fn sandbox_loads_only_text_chunks() {
+    let runtime = LuaRuntime::new(LuaLimits::default());
+    let (text, binary): (i64, Option<i64>) = call(&runtime, r#"
+        local text = load("return 1")()
+        local binary = load(string.dump(function() return 2 end))
+        return text, binary and binary()
+    "#).unwrap();
+    assert_eq!(text, 1);
+    assert_eq!(binary, None);
+}
+
+#[test]
+fn scripts_do_not_share_globals() {
+    let runtime = LuaRuntime::new(LuaLimits::default());
+    call::<()>(&runtime, "shared = 1").unwrap();
+    let shared: Option<i64> = call(&runtime, "return shared").unwrap();
+    assert_eq!(shared, None);
+    // 全局表与标准库只读，脚本无法借此影响其它脚本
+    for source in ["_G.shared = 1", "rawset(_G, 'shared', 1)", "math.floor = nil", "string.upper = nil"] {
+        let err = call::<()>(&runtime, source).unwrap_err();
+        assert!(err.to_string().contains("read-only global table"), "{}: {}", source, err);
+    }
+    let intact: bool = call(&runtime, "return shared == nil and math.floor(1.5) == 1 and ('a'):upper() == 'A'").unwrap();
+    assert!(intact);
+}
+
+#[test]
+fn math_random_is_deterministic() {
+    let first: Vec<i64> = call(&LuaRuntime::new(LuaLimits::default()), "return { math.random(1000), math.random(1000) }").unwrap();
+    let second: Vec<i64> = call(&LuaRuntime::new(LuaLimits::default()), "return { math.random(1000), math.random(1000) }").unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn instruction_limit_stops_runaway_scripts() {
+    let runtime = LuaRuntime::new(LuaLimits { instruction_limit: 100_000, memory_limit_mb: 0 });
+    let err = call::<()>(&runtime, "while true do end").unwrap_err();
+    assert!(err.to_string().contains("script exceeded the instruction limit of 100000 instructions"), "{err}");
+    // 每次调用都有独立的预算
+    let sum: i64 = call(&runtime, "local sum = 0 for i = 1, 1000 do sum = sum + i end return sum").unwrap();
+    assert_eq!(sum, 500500);
+}
+
+#[test]
+fn zero_instruction_limit_is_unlimited() {
+    let runtime = LuaRuntime::new(LuaLimits { instruction_limit: 0, memory_limit_mb: 0 });
+    let count: i64 = call(&runtime, "local n = 0 for i = 1, 2000000 do n = n + 1 end return n").unwrap();
+    assert_eq!(count, 2_000_000);
+}
+
+#[test]
+fn memory_limit_stops_large_allocations() {
+    let runtime = LuaRuntime::new(LuaLimits { instruction_limit: 0, memory_limit_mb: 1 });
+    let err = call::<()>(&runtime, r#"local s = string.rep("x", 4 * 1024 * 1024)"#).unwrap_err();
+    assert!(matches!(err, LuaError::MemoryError(_)), "{err}");
+}
+
+#[test]
+fn limits_from_config() {
+    let config: toml::Table = toml::from_str("[lua]\ninstruction_limit = 5000\nmemory_limit_mb = 0").unwrap();
+    let limits = LuaLimits::from_config(&config);
+    assert_eq!((limits.instruction_limit, limits.memory_limit_mb), (5000, 0));
+    let default = LuaLimits::default();
+    let limits = LuaLimits::from_config(&toml::Table::new());
+    assert_eq!((limits.instruction_limit, limits.memory_limit_mb), (default.instruction_limit, default.memory_limit_mb));
+}
+
+#[test]
+fn invalid_limits_fall_back_to_the_default() {
+    let config: toml::Table = toml::from_str("[lua]\ninstruction_limit = -1\nmemory_limit_mb = \"a lot\"").unwrap();
+    let limits = LuaLimits::from_config(&config);
+    let default = LuaLimits::default();
+    assert_eq!((limits.instruction_limit, limits.memory_limit_mb), (default.instruction_limit, default.memory_limit_mb));
+}
@@ -0,0 +1,70 @@
+//! 脚本文件的热重载
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use my_ai_world::aethaum_predefined::*;
+
+const KEY: LuaScriptKey = LuaScriptKey { module: "test", system: "ReloadSystem", handler: "update", part: LuaScriptPart::Logic };
+
+///写入脚本，并将修改时间推后 offset 秒，避免文件系统的时间精度使两次写入的修改时间相同
+fn write_script(path: &Path, source: &str, offset: u64) {
+    std::fs::write(path, source).unwrap();
+    let file = std::fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(offset)).unwrap();
+}
+
+///以绝对路径登记的脚本文件，不受项目根目录影响
+fn runtime_with_script(name: &str, source: &str) -> (LuaRuntime, PathBuf) {
+    let dir = std::env::temp_dir().join("aethaum_test_reload");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    write_script(&path, source, 0);
+    let mut runtime = LuaRuntime::new(LuaLimits::default());
+    let leaked: &'static str = Box::leak(path.to_string_lossy().to_string().into_boxed_str());
+    runtime.register_scripts(&[(KEY, LuaSource::File { path: leaked })]);
+    assert!(runtime.compile_scripts().is_empty());
+    (runtime, path)
+}
+
+fn call(runtime: &LuaRuntime) -> i64 {
+    call_lua_script(&runtime.lua, runtime.script(&KEY).unwrap()).unwrap()
+}
+
+#[test]
+fn changed_scripts_are_recompiled() {
+    let (mut runtime, path) = runtime_with_script("changed.lua", "return 1");
+    assert!(runtime.reload_changed_scripts().is_empty());
+    write_script(&path, "return 2", 10);
+    let reloads = runtime.reload_changed_scripts();
+    assert_eq!(reloads.len(), 1);
+    assert_eq!(reloads[0].key, KEY);
+    assert_eq!(reloads[0].path, path);
+    assert!(reloads[0].result.is_ok());
+    assert_eq!(call(&runtime), 2);
+    assert!(runtime.reload_changed_scripts().is_empty());
+}
+
+#[test]
+fn syntax_errors_keep_the_previous_version() {
+    let (mut runtime, path) = runtime_with_script("broken.lua", "return 1");
+    write_script(&path, "return = 2", 10);
+    let reloads = runtime.reload_changed_scripts();
+    assert_eq!(reloads.len(), 1);
+    assert_eq!(reloads[0].key, KEY);
+    assert_eq!(reloads[0].path, path);
+    let err = reloads[0].result.as_ref().unwrap_err();
+    assert!(err.to_string().contains("broken.lua:1:"), "{err}");
+    assert_eq!(call(&runtime), 1);
+    // 错误只报告一次，修复后再次重新编译
+    assert!(runtime.reload_changed_scripts().is_empty());
+    write_script(&path, "return 3", 20);
+    assert!(runtime.reload_changed_scripts()[0].result.is_ok());
+    assert_eq!(call(&runtime), 3);
+}
+
+#[test]
+fn deleted_scripts_keep_the_previous_version() {
+    let (mut runtime, path) = runtime_with_script("deleted.lua", "return 1");
+    std::fs::remove_file(&path).unwrap();
+    assert!(runtime.reload_changed_scripts().is_empty());
+    assert_eq!(call(&runtime), 1);
+}
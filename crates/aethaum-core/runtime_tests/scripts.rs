@@ -0,0 +1,57 @@
+//! 脚本在启动时编译一次，之后复用编译得到的函数
+mod common;
+
+use bevy_app::App;
+use mlua::prelude::*;
+use my_ai_world::aethaum_predefined::*;
+use my_ai_world::modules::explore::systems::HealthSystem;
+
+const KEY: LuaScriptKey = LuaScriptKey { module: "test", system: "TestSystem", handler: "update", part: LuaScriptPart::Logic };
+
+///HealthSystem 中各脚本编译得到的函数
+fn compiled(app: &App) -> Vec<Option<*const std::ffi::c_void>> {
+    let runtime = app.world().resource::<LuaRuntime>();
+    HealthSystem::SCRIPTS.iter()
+        .map(|(key, _)| runtime.script(key).map(LuaFunction::to_pointer))
+        .collect()
+}
+
+#[test]
+fn scripts_are_compiled_once_at_startup() {
+    let mut app = common::app();
+    assert!(compiled(&app).iter().all(Option::is_none));
+    app.update();
+    let functions = compiled(&app);
+    assert!(functions.iter().all(Option::is_some));
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(compiled(&app), functions);
+    // 已编译的脚本不会再次编译
+    assert!(app.world_mut().resource_mut::<LuaRuntime>().compile_scripts().is_empty());
+    assert_eq!(compiled(&app), functions);
+}
+
+#[test]
+fn compiled_function_is_reused_between_calls() {
+    let mut runtime = LuaRuntime::new(LuaLimits::default());
+    runtime.register_scripts(&[(KEY, LuaSource::Embed { name: "test.toml", source: "calls = (calls or 0) + 1 return calls", line: 1 })]);
+    assert!(runtime.compile_scripts().is_empty());
+    let function = runtime.script(&KEY).unwrap().clone();
+    for expected in 1..=3 {
+        let calls: i64 = call_lua_script(&runtime.lua, runtime.script(&KEY).unwrap()).unwrap();
+        assert_eq!(calls, expected);
+    }
+    assert_eq!(runtime.script(&KEY).unwrap(), &function);
+}
+
+#[test]
+fn compile_errors_point_at_the_toml_line() {
+    let mut runtime = LuaRuntime::new(LuaLimits::default());
+    runtime.register_scripts(&[(KEY, LuaSource::Embed { name: "modules/test/systems/broken.toml", source: "x = = 1", line: 12 })]);
+    let errors = runtime.compile_scripts();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, KEY);
+    assert!(errors[0].1.to_string().contains("modules/test/systems/broken.toml:12:"), "{}", errors[0].1);
+    assert!(runtime.script(&KEY).is_none());
+}
@@ -1,12 +1,10 @@
-mod transpile;
+pub mod transpile;
 mod lua_binding;
 mod aethaum_predefined;
 mod utils;
 mod project_template;
 
-use std::fs;
-use std::path::Path;
-use bevy_ecs::event::Events;
+use std::path::{Path, PathBuf};
 use proc_macro2::TokenStream;
 use thiserror::Error;
 use crate::code_generator::project_template::ProjectTemplateGenerator;
@@ -22,38 +20,131 @@ pub enum CodeGenerationError {
     #[error("Template Generation error when generating code: {0}")]
     TemplateGeneration(#[from] project_template::TemplateGenerationError),
 }
+///generate_modules 的结果
+#[derive(Debug, Default)]
+pub struct GeneratedModules {
+    ///内容确实发生变化的模块文件
+    pub written: Vec<PathBuf>,
+    ///生成失败的模块及其错误
+    pub failed: Vec<(String, CodeGenerationError)>,
+}
 pub struct CodeGenerator {
-    project: AethaumProject
+    project: AethaumProject,
+    output_dir: Option<PathBuf>,
 }
 impl CodeGenerator {
     pub fn new(project: AethaumProject) -> Self {
         Self {
-            project
+            project,
+            output_dir: None,
+        }
+    }
+    ///覆盖 world.toml 中 [build].output_dir 指定的目录
+    pub fn with_output_dir(mut self, output_dir: PathBuf) -> Self {
+        self.output_dir = Some(output_dir);
+        self
+    }
+    ///生成的Rust项目所在的目录
+    pub fn output_dir(&self) -> PathBuf {
+        match &self.output_dir {
+            Some(output_dir) => output_dir.clone(),
+            None => self.project.root.join(&self.project.world.build.output_dir),
         }
     }
+    pub fn project(&self) -> &AethaumProject {
+        &self.project
+    }
     pub fn generate(&self) -> Result<(), CodeGenerationError> {
-        let generated_root = self.project.root.join("generated");
+        let module_names = self.project.module_tree.get_modules().into_iter()
+            .map(|module| module.name.as_str())
+            .collect::<Vec<_>>();
+        match self.generate_modules(&module_names)?.failed.into_iter().next() {
+            Some((_, err)) => Err(err),
+            None => Ok(()),
+        }
+    }
+    ///生成项目模板，但只重新生成指定模块的代码(尚未生成过的模块除外)，供 watch 模式增量更新使用
+    ///一个模块生成失败时继续生成其余模块；只有项目模板生成失败时返回错误
+    pub fn generate_modules(&self, module_names: &[&str]) -> Result<GeneratedModules, CodeGenerationError> {
+        let generated_root = self.output_dir();
         ProjectTemplateGenerator::generate(&generated_root, &self.project)?;
+        let mut generated = GeneratedModules::default();
         for module in self.project.module_tree.get_modules() {
             let module_path = generated_root.join("src").join("modules").join(format!("{}.rs", module.name));
-            let module_code = module.transpile()?;
-            Self::write_code_to_file(&module_path, module_code)?;
+            if !module_names.contains(&module.name.as_str()) && module_path.exists() {
+                continue;
+            }
+            let result = module.transpile()
+                .map_err(CodeGenerationError::from)
+                .and_then(|module_code| Self::write_code_to_file(&module_path, module_code));
+            match result {
+                Ok(true) => generated.written.push(module_path),
+                Ok(false) => {}
+                Err(err) => generated.failed.push((module.name.to_string(), err)),
+            }
         }
-        Ok(())
+        Ok(generated)
     }
-    fn write_code_to_file(path: &Path, content: TokenStream) -> Result<(), CodeGenerationError> {
+    fn write_code_to_file(path: &Path, content: TokenStream) -> Result<bool, CodeGenerationError> {
         let formatted_code = utils::format_rust_code(content)?;
-        fs::write(path, formatted_code)?;
-        Ok(())
+        Ok(utils::write_if_changed(path, &formatted_code)?)
     }
 }
 #[cfg(test)]
 mod tests {
-    use crate::code_generator::CodeGenerator;
+    use std::fs;
+    use std::path::Path;
+    use crate::code_generator::{utils, CodeGenerator};
     #[test]
     fn test_generate_code() {
-        let project = crate::ecs::loader::ProjectLoader::new("D:\\Aethaum\\test_project".into()).load().unwrap();
+        let project = crate::ecs::loader::ProjectLoader::new(crate::test_utils::test_project_root()).load().unwrap();
         let code_generator = CodeGenerator::new(project);
         code_generator.generate().unwrap();
     }
+    #[test]
+    fn test_generate_modules_skips_unchanged() {
+        let project = crate::ecs::loader::ProjectLoader::new(crate::test_utils::test_project_root()).load().unwrap();
+        let code_generator = CodeGenerator::new(project);
+        code_generator.generate().unwrap();
+        let generated = code_generator.generate_modules(&["combat", "explore"]).unwrap();
+        assert!(generated.written.is_empty());
+        assert!(generated.failed.is_empty());
+    }
+    ///将 runtime_tests 复制为生成的 crate 的 tests 目录，子目录一并复制
+    fn copy_runtime_tests(from: &Path, to: &Path) {
+        fs::create_dir_all(to).unwrap();
+        for entry in fs::read_dir(from).unwrap() {
+            let path = entry.unwrap().path();
+            let target = to.join(path.file_name().unwrap());
+            if path.is_dir() {
+                copy_runtime_tests(&path, &target);
+            } else {
+                utils::write_if_changed(&target, &fs::read_to_string(&path).unwrap()).unwrap();
+            }
+        }
+    }
+    ///生成 test_project，并在生成的 crate 中运行 runtime_tests 下的测试，检查生成的代码在运行时的行为
+    #[test]
+    fn test_generated_runtime() {
+        let project = crate::ecs::loader::ProjectLoader::new(crate::test_utils::test_project_root()).load().unwrap();
+        let output_dir = std::env::temp_dir().join("aethaum_test_generated_runtime");
+        CodeGenerator::new(project).with_output_dir(output_dir.clone()).generate().unwrap();
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        copy_runtime_tests(&manifest_dir.join("runtime_tests"), &output_dir.join("tests"));
+        // 使用工作区 target 下的固定目录，生成的 crate 的依赖只需编译一次；生成的代码不应有警告
+        let output = std::process::Command::new(env!("CARGO"))
+            .arg("test")
+            .arg("--manifest-path").arg(output_dir.join("Cargo.toml"))
+            .env("CARGO_TARGET_DIR", manifest_dir.join("../../target/generated_runtime"))
+            .env("RUSTFLAGS", "-D warnings")
+            .env("AETHAUM_PROJECT_ROOT", crate::test_utils::test_project_root())
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "tests of the generated crate failed:\n{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
 }
\ No newline at end of file
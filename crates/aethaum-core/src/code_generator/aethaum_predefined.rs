@@ -3,7 +3,8 @@ use quote::quote;
 
 pub fn predefined() -> TokenStream {
     let mut predefined = trait_describe();
-    predefined.extend(event_aethaum_spawn_entity());
+    predefined.extend(lua_runtime());
+    predefined.extend(system_priority());
     predefined
  }
 pub fn trait_describe() -> TokenStream {
@@ -12,41 +13,755 @@ pub fn trait_describe() -> TokenStream {
             fn describe(&self) -> &'static str {
                  ""
             }
-            fn describe_field(&self, field_name: &str) -> &'static str {
+            fn describe_field(&self, _field_name: &str) -> &'static str {
                  ""
             }
         }
     }
 }
-//Reserved Events
-pub fn event_aethaum_spawn_entity() -> TokenStream {
+//Lua Runtime
+///生成的系统运行Lua脚本所需的运行时：Lua虚拟机资源，脚本来源，字段与组件的Lua转换
+pub fn lua_runtime() -> TokenStream {
     quote! {
         use bevy_ecs::prelude::*;
-        use tokio::sync::oneshot;
-        use std::sync::atomic::AtomicU64;
+        use mlua::prelude::*;
         use std::sync::atomic::Ordering;
+        pub use bevy_math::{Vec2, Vec3};
+        pub use bevy_color::Color;
 
-        // 全局请求ID生成器
-        static REQUEST_ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+        ///指定Aethaum项目根目录的环境变量
+        pub const PROJECT_ROOT_ENV: &str = "AETHAUM_PROJECT_ROOT";
+        ///Aethaum项目根目录，logic_file/condition_file 与配置文件相对于此目录。
+        ///依次取环境变量 AETHAUM_PROJECT_ROOT；当前目录或可执行文件所在目录向上第一个含有 world.toml 的目录；当前目录
+        pub fn project_root() -> std::path::PathBuf {
+            if let Some(root) = std::env::var_os(PROJECT_ROOT_ENV) {
+                return root.into();
+            }
+            static FOUND: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+            FOUND.get_or_init(|| {
+                let current_dir = std::env::current_dir().unwrap_or_default();
+                let exe_dir = std::env::current_exe().ok()
+                    .and_then(|exe| exe.parent().map(std::path::Path::to_path_buf));
+                std::iter::once(current_dir.clone())
+                    .chain(exe_dir)
+                    .find_map(|dir| dir.ancestors().find(|dir| dir.join("world.toml").is_file()).map(std::path::Path::to_path_buf))
+                    .unwrap_or(current_dir)
+            }).clone()
+        }
+
+        #[derive(Resource)]
+        pub struct LuaRuntime {
+            pub lua: Lua,
+            ///脚本共用的只读全局表，每个脚本的全局变量在各自的环境表中
+            sandbox: LuaTable,
+            scripts: std::collections::HashMap<LuaScriptKey, CompiledLuaScript>,
+        }
+        impl Default for LuaRuntime {
+            fn default() -> Self {
+                Self::new(LuaLimits::from_config(&load_config()))
+            }
+        }
+        impl LuaRuntime {
+            ///创建沙箱化的Lua环境：不能执行命令、访问文件或加载二进制代码，并施加 limits 中的限制
+            pub fn new(limits: LuaLimits) -> Self {
+                let libs = LuaStdLib::COROUTINE | LuaStdLib::TABLE | LuaStdLib::STRING
+                    | LuaStdLib::UTF8 | LuaStdLib::MATH | LuaStdLib::OS;
+                let lua = Lua::new_with(libs, LuaOptions::default())
+                    .expect("failed to create Lua state");
+                let sandbox: LuaTable = lua.load(LUA_SANDBOX)
+                    .set_name("=aethaum sandbox")
+                    .eval()
+                    .expect("failed to set up the Lua sandbox");
+                if limits.memory_limit_mb > 0 {
+                    lua.set_memory_limit(limits.memory_limit_mb as usize * 1024 * 1024)
+                        .expect("failed to set the Lua memory limit");
+                }
+                if limits.instruction_limit > 0 {
+                    lua.set_app_data(LuaInstructionBudget {
+                        limit: limits.instruction_limit,
+                        used: std::sync::atomic::AtomicU64::new(0),
+                    });
+                    let triggers = LuaHookTriggers::new().every_nth_instruction(LUA_HOOK_INSTRUCTIONS);
+                    lua.set_hook(triggers, |lua, _| {
+                        let Some(budget) = lua.app_data_ref::<LuaInstructionBudget>() else {
+                            return Ok(LuaVmState::Continue);
+                        };
+                        let used = budget.used.fetch_add(LUA_HOOK_INSTRUCTIONS as u64, Ordering::Relaxed) + LUA_HOOK_INSTRUCTIONS as u64;
+                        if used > budget.limit {
+                            return Err(LuaError::runtime(format!(
+                                "script exceeded the instruction limit of {} instructions", budget.limit
+                            )));
+                        }
+                        Ok(LuaVmState::Continue)
+                    }).expect("failed to set the Lua instruction hook");
+                }
+                Self { lua, sandbox, scripts: Default::default() }
+            }
+            ///为脚本创建独立的全局环境，读取时回退到只读的沙箱全局表
+            pub fn script_env(&self) -> LuaResult<LuaTable> {
+                new_script_env(&self.lua, &self.sandbox)
+            }
+            ///登记系统的脚本，在启动时由 compile_lua_scripts 统一编译
+            pub fn register_scripts(&mut self, scripts: &[(LuaScriptKey, LuaSource)]) {
+                for (key, source) in scripts {
+                    self.scripts.insert(*key, CompiledLuaScript { source: *source, function: None, modified: None });
+                }
+            }
+            ///编译所有尚未编译的脚本，返回编译失败的脚本及其错误
+            pub fn compile_scripts(&mut self) -> Vec<(LuaScriptKey, LuaError)> {
+                let mut errors = Vec::new();
+                for (key, script) in self.scripts.iter_mut().filter(|(_, script)| script.function.is_none()) {
+                    script.modified = script.source.path().and_then(|path| modified_time(&path));
+                    match new_script_env(&self.lua, &self.sandbox).and_then(|env| script.source.load(&self.lua, env)) {
+                        Ok(function) => script.function = Some(function),
+                        Err(err) => errors.push((*key, err)),
+                    }
+                }
+                errors
+            }
+            ///重新编译修改过的脚本文件。编译失败时保留之前的版本，文件被删除时不做处理
+            pub fn reload_changed_scripts(&mut self) -> Vec<LuaScriptReload> {
+                let mut reloads = Vec::new();
+                for (key, script) in self.scripts.iter_mut() {
+                    let Some(path) = script.source.path() else {
+                        continue;
+                    };
+                    let modified = modified_time(&path);
+                    if modified.is_none() || modified == script.modified {
+                        continue;
+                    }
+                    script.modified = modified;
+                    let result = new_script_env(&self.lua, &self.sandbox)
+                        .and_then(|env| script.source.load(&self.lua, env))
+                        .map(|function| script.function = Some(function));
+                    reloads.push(LuaScriptReload { key: *key, path, result });
+                }
+                reloads
+            }
+            ///已编译的脚本，编译失败时为 None
+            pub fn script(&self, key: &LuaScriptKey) -> Option<&LuaFunction> {
+                self.scripts.get(key).and_then(|script| script.function.as_ref())
+            }
+        }
+        fn new_script_env(lua: &Lua, sandbox: &LuaTable) -> LuaResult<LuaTable> {
+            let env = lua.create_table()?;
+            let metatable = lua.create_table()?;
+            metatable.set("__index", sandbox)?;
+            env.set_metatable(Some(metatable))?;
+            Ok(env)
+        }
+        ///保留 os 中只读取时间的函数，去掉读取文件的 dofile/loadfile，load 只接受文本代码；
+        ///Lua 5.4 默认以随机种子初始化 math.random，固定种子使每次运行结果一致。
+        ///返回只读的全局表，标准库表同样只读，脚本之间不能通过全局变量或修改标准库互相影响
+        const LUA_SANDBOX: &str = r#"
+            local clock, date, difftime, time = os.clock, os.date, os.difftime, os.time
+            os = { clock = clock, date = date, difftime = difftime, time = time }
+            dofile, loadfile = nil, nil
+            math.randomseed(0)
+            local readonly_tables = setmetatable({}, { __mode = "k" })
+            local function readonly(t)
+                local proxy = setmetatable({}, {
+                    __index = t,
+                    __newindex = function(_, key)
+                        error("attempt to modify read-only global table (key `" .. tostring(key) .. "`)", 2)
+                    end,
+                    __pairs = function() return next, t, nil end,
+                    __len = function() return #t end,
+                    __metatable = false,
+                })
+                readonly_tables[proxy] = true
+                return proxy
+            end
+            for _, name in ipairs({ "coroutine", "table", "string", "utf8", "math", "os" }) do
+                _G[name] = readonly(_G[name])
+            end
+            getmetatable("").__metatable = false
+            local raw_rawset = rawset
+            rawset = function(t, key, value)
+                if readonly_tables[t] then
+                    error("attempt to modify read-only global table (key `" .. tostring(key) .. "`)", 2)
+                end
+                return raw_rawset(t, key, value)
+            end
+            local sandbox = readonly(_G)
+            _G._G = sandbox
+            local raw_load = load
+            load = function(chunk, name, _, env)
+                return raw_load(chunk, name, "t", env or setmetatable({}, { __index = sandbox }))
+            end
+            return sandbox
+        "#;
+        ///config/config.toml 中 [lua] 的限制，为0时不限制
+        #[derive(Debug, Clone, Copy)]
+        pub struct LuaLimits {
+            ///每次调用脚本可以执行的指令数
+            pub instruction_limit: u64,
+            ///所有脚本共用的内存上限(MB)
+            pub memory_limit_mb: u64,
+        }
+        impl Default for LuaLimits {
+            fn default() -> Self {
+                Self { instruction_limit: 1_000_000, memory_limit_mb: 256 }
+            }
+        }
+        impl LuaLimits {
+            ///读取配置中的限制，缺少或无效的值使用默认值，无效的值会被报告
+            pub fn from_config(config: &toml::Table) -> Self {
+                let default = Self::default();
+                let lua = config.get("lua");
+                let limit = |key: &str, default: u64| match lua.and_then(|lua| lua.get(key)) {
+                    Some(toml::Value::Integer(limit)) if *limit >= 0 => *limit as u64,
+                    Some(value) => {
+                        eprintln!(
+                            "[aethaum] {} in [lua] of {} must be a non-negative integer, got {}; using the default {}",
+                            key, CONFIG_FILE, value, default
+                        );
+                        default
+                    }
+                    None => default,
+                };
+                Self {
+                    instruction_limit: limit("instruction_limit", default.instruction_limit),
+                    memory_limit_mb: limit("memory_limit_mb", default.memory_limit_mb),
+                }
+            }
+        }
+        ///指令计数的粒度，钩子每执行这么多条指令触发一次
+        const LUA_HOOK_INSTRUCTIONS: u32 = 1000;
+        struct LuaInstructionBudget {
+            limit: u64,
+            used: std::sync::atomic::AtomicU64,
+        }
+        ///调用脚本，每次调用都有独立的指令预算
+        pub fn call_lua_script<R: FromLuaMulti>(lua: &Lua, function: &LuaFunction) -> LuaResult<R> {
+            if let Some(budget) = lua.app_data_ref::<LuaInstructionBudget>() {
+                budget.used.store(0, Ordering::Relaxed);
+            }
+            function.call(())
+        }
+
+        ///项目配置文件的位置，相对于项目根目录
+        pub const CONFIG_FILE: &str = "config/config.toml";
+        ///读取项目根目录下的 config/config.toml，文件不存在时为空表；文件无效时报告错误，并同样使用空表，即全部取默认值
+        pub fn load_config() -> toml::Table {
+            let path = project_root().join(CONFIG_FILE);
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                return toml::Table::new();
+            };
+            toml::from_str(&content).unwrap_or_else(|err| {
+                eprintln!("[aethaum] Invalid {}, using the default configuration: {}", path.display(), err);
+                toml::Table::new()
+            })
+        }
+
+        ///脚本所属的系统与处理器，handler 为 "update" 或 "on 事件名"
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct LuaScriptKey {
+            pub module: &'static str,
+            pub system: &'static str,
+            pub handler: &'static str,
+            pub part: LuaScriptPart,
+        }
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum LuaScriptPart {
+            Condition,
+            Logic,
+        }
+        impl std::fmt::Display for LuaScriptKey {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self.part {
+                    LuaScriptPart::Condition => write!(f, "{}::{} ({} condition)", self.module, self.system, self.handler),
+                    LuaScriptPart::Logic => write!(f, "{}::{} ({})", self.module, self.system, self.handler),
+                }
+            }
+        }
+        struct CompiledLuaScript {
+            source: LuaSource,
+            function: Option<LuaFunction>,
+            ///脚本文件上次编译时的修改时间
+            modified: Option<std::time::SystemTime>,
+        }
+        fn modified_time(path: &std::path::Path) -> Option<std::time::SystemTime> {
+            std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+        }
+        ///一次热重载的结果
+        pub struct LuaScriptReload {
+            pub key: LuaScriptKey,
+            pub path: std::path::PathBuf,
+            pub result: LuaResult<()>,
+        }
+        ///启动时编译所有登记的脚本，之后每次调用都复用编译得到的函数
+        pub fn compile_lua_scripts(mut runtime: ResMut<LuaRuntime>) {
+            for (key, err) in runtime.compile_scripts() {
+                report_lua_error(&key, &err);
+            }
+        }
+        ///检查脚本文件的间隔
+        pub const LUA_RELOAD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+        ///在两个tick之间重新编译修改过的脚本文件，ECS 中的状态不受影响
+        pub fn reload_lua_scripts(mut runtime: ResMut<LuaRuntime>, mut last_poll: Local<Option<std::time::Instant>>) {
+            if last_poll.is_some_and(|last_poll| last_poll.elapsed() < LUA_RELOAD_POLL_INTERVAL) {
+                return;
+            }
+            *last_poll = Some(std::time::Instant::now());
+            for LuaScriptReload { key, path, result } in runtime.reload_changed_scripts() {
+                match result {
+                    Ok(()) => eprintln!("[aethaum] Reloaded {} for {}", path.display(), key),
+                    Err(err) => eprintln!(
+                        "[aethaum] Failed to reload {} for {}, keeping the previous version: {}",
+                        path.display(), key, err
+                    ),
+                }
+            }
+        }
+
+        ///TOML中的脚本：内嵌于TOML的代码，或相对于项目根目录的脚本文件
+        #[derive(Debug, Clone, Copy)]
+        pub enum LuaSource {
+            ///name 通常为定义脚本的TOML文件，line 为代码第一行在其中的行号
+            Embed {
+                name: &'static str,
+                source: &'static str,
+                line: u32,
+            },
+            File {
+                path: &'static str,
+            },
+        }
+        impl LuaSource {
+            ///脚本文件的完整路径，内嵌的脚本为 None
+            pub fn path(&self) -> Option<std::path::PathBuf> {
+                match self {
+                    LuaSource::Embed { .. } => None,
+                    LuaSource::File { path } => Some(project_root().join(path)),
+                }
+            }
+            ///以 env 作为全局环境编译脚本
+            pub fn load(&self, lua: &Lua, env: LuaTable) -> LuaResult<LuaFunction> {
+                match self {
+                    LuaSource::Embed { name, source, line } => {
+                        // 在代码前补齐空行，报错中的行号即为TOML文件中的行号
+                        let padding = "\n".repeat(line.saturating_sub(1) as usize);
+                        lua.load(format!("{}{}", padding, source)).set_name(format!("@{}", name)).set_environment(env).into_function()
+                    }
+                    LuaSource::File { path } => {
+                        let full_path = project_root().join(path);
+                        let source = std::fs::read_to_string(&full_path).map_err(|err| {
+                            LuaError::external(format!("failed to read {}: {}", full_path.display(), err))
+                        })?;
+                        lua.load(source).set_name(format!("@{}", full_path.display())).set_environment(env).into_function()
+                    }
+                }
+            }
+        }
 
-        #[derive(Event)]
-        pub struct AethaumSpawnEntity {
-            pub prototype_name: String,
-            pub request_id: u64,
+        ///组件与事件字段在Rust与Lua之间的转换
+        pub trait LuaField: Sized {
+            fn to_lua_value(&self, lua: &Lua) -> LuaResult<LuaValue>;
+            fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self>;
         }
-        #[derive(Event)]
-        pub struct AethaumSpawnEntityResponse {
-            pub request_id: u64,
-            pub entity: Entity,
+        macro_rules! impl_lua_field {
+            ($($ty:ty),*) => {
+                $(
+                    impl LuaField for $ty {
+                        fn to_lua_value(&self, lua: &Lua) -> LuaResult<LuaValue> {
+                            self.clone().into_lua(lua)
+                        }
+                        fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                            <$ty>::from_lua(value, lua)
+                        }
+                    }
+                )*
+            };
         }
-        impl AethaumSpawnEntity {
-            pub fn new(prototype_name: String) -> (Self, u64) {
-                let request_id = REQUEST_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
-                (Self {
-                    prototype_name,
-                    request_id,
-                }, request_id)
+        impl_lua_field!(f64, i64, bool, String, f32, i32, u32, u8, u64);
+        ///实体在Lua中表示为整数
+        impl LuaField for Entity {
+            fn to_lua_value(&self, _lua: &Lua) -> LuaResult<LuaValue> {
+                Ok(LuaValue::Integer(self.to_bits() as i64))
+            }
+            fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                let bits = i64::from_lua(value, lua)?;
+                Entity::try_from_bits(bits as u64).map_err(|_| LuaError::FromLuaConversionError {
+                    from: "integer",
+                    to: "Entity".to_string(),
+                    message: Some(format!("{} is not a valid entity", bits)),
+                })
+            }
+        }
+
+        ///向量在Lua中表示为 { x = .., y = .. } 表
+        impl LuaField for Vec2 {
+            fn to_lua_value(&self, lua: &Lua) -> LuaResult<LuaValue> {
+                let table = lua.create_table()?;
+                table.set("x", self.x)?;
+                table.set("y", self.y)?;
+                Ok(LuaValue::Table(table))
+            }
+            fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                let table = LuaTable::from_lua(value, lua)?;
+                Ok(Vec2::new(table.get("x")?, table.get("y")?))
+            }
+        }
+        ///向量在Lua中表示为 { x = .., y = .., z = .. } 表
+        impl LuaField for Vec3 {
+            fn to_lua_value(&self, lua: &Lua) -> LuaResult<LuaValue> {
+                let table = lua.create_table()?;
+                table.set("x", self.x)?;
+                table.set("y", self.y)?;
+                table.set("z", self.z)?;
+                Ok(LuaValue::Table(table))
+            }
+            fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                let table = LuaTable::from_lua(value, lua)?;
+                Ok(Vec3::new(table.get("x")?, table.get("y")?, table.get("z")?))
+            }
+        }
+        ///颜色在Lua中表示为 { r = .., g = .., b = .., a = .. } 表，分量为0到1的sRGB值，a 可省略
+        impl LuaField for Color {
+            fn to_lua_value(&self, lua: &Lua) -> LuaResult<LuaValue> {
+                let srgba = self.to_srgba();
+                let table = lua.create_table()?;
+                table.set("r", srgba.red)?;
+                table.set("g", srgba.green)?;
+                table.set("b", srgba.blue)?;
+                table.set("a", srgba.alpha)?;
+                Ok(LuaValue::Table(table))
+            }
+            fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                let table = LuaTable::from_lua(value, lua)?;
+                let alpha: Option<f32> = table.get("a")?;
+                Ok(Color::srgba(table.get("r")?, table.get("g")?, table.get("b")?, alpha.unwrap_or(1.0)))
+            }
+        }
+
+        ///容器中的元素转换失败时，在错误前加上元素位置，嵌套时位置依次展开
+        fn nested_lua_error(location: String, err: LuaError) -> LuaError {
+            let message = match err {
+                LuaError::RuntimeError(message) => message,
+                err => err.to_string(),
+            };
+            LuaError::RuntimeError(format!("{location}: {message}"))
+        }
+        ///列表在Lua中表示为序列表
+        impl<T: LuaField> LuaField for Vec<T> {
+            fn to_lua_value(&self, lua: &Lua) -> LuaResult<LuaValue> {
+                let table = lua.create_table()?;
+                for element in self {
+                    table.push(element.to_lua_value(lua)?)?;
+                }
+                Ok(LuaValue::Table(table))
+            }
+            fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                let table = LuaTable::from_lua(value, lua)?;
+                table.sequence_values::<LuaValue>()
+                    .enumerate()
+                    .map(|(i, element)| {
+                        T::from_lua_value(element?, lua)
+                            .map_err(|err| nested_lua_error(format!("at index {}", i + 1), err))
+                    })
+                    .collect()
+            }
+        }
+        ///定长数组在Lua中表示为下标1到N的表，option 元素可以为 nil
+        impl<T: LuaField, const N: usize> LuaField for [T; N] {
+            fn to_lua_value(&self, lua: &Lua) -> LuaResult<LuaValue> {
+                let table = lua.create_table()?;
+                for (i, element) in self.iter().enumerate() {
+                    table.raw_set(i + 1, element.to_lua_value(lua)?)?;
+                }
+                Ok(LuaValue::Table(table))
+            }
+            fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                let table = LuaTable::from_lua(value, lua)?;
+                if table.raw_len() > N {
+                    return Err(LuaError::runtime(format!("expected {N} elements, got {}", table.raw_len())));
+                }
+                let elements = (1..=N)
+                    .map(|i| {
+                        T::from_lua_value(table.raw_get(i)?, lua)
+                            .map_err(|err| nested_lua_error(format!("at index {i}"), err))
+                    })
+                    .collect::<LuaResult<Vec<T>>>()?;
+                match elements.try_into() {
+                    Ok(array) => Ok(array),
+                    Err(_) => unreachable!("exactly N elements are read"),
+                }
+            }
+        }
+        ///键为字符串的表
+        impl<T: LuaField> LuaField for std::collections::HashMap<String, T> {
+            fn to_lua_value(&self, lua: &Lua) -> LuaResult<LuaValue> {
+                let table = lua.create_table()?;
+                for (key, value) in self {
+                    table.set(key.as_str(), value.to_lua_value(lua)?)?;
+                }
+                Ok(LuaValue::Table(table))
+            }
+            fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                let table = LuaTable::from_lua(value, lua)?;
+                table.pairs::<String, LuaValue>()
+                    .map(|pair| {
+                        let (key, value) = pair?;
+                        let value = T::from_lua_value(value, lua)
+                            .map_err(|err| nested_lua_error(format!("at key `{key}`"), err))?;
+                        Ok((key, value))
+                    })
+                    .collect()
+            }
+        }
+        ///None 在Lua中表示为 nil
+        impl<T: LuaField> LuaField for Option<T> {
+            fn to_lua_value(&self, lua: &Lua) -> LuaResult<LuaValue> {
+                match self {
+                    Some(value) => value.to_lua_value(lua),
+                    None => Ok(LuaNil),
+                }
+            }
+            fn from_lua_value(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                match value {
+                    LuaNil => Ok(None),
+                    value => T::from_lua_value(value, lua).map(Some),
+                }
+            }
+        }
+
+        ///组件与Lua表之间的转换，字段名即表的键
+        pub trait LuaComponent: Component<Mutability = bevy_ecs::component::Mutable> {
+            fn to_lua_table(&self, lua: &Lua) -> LuaResult<LuaTable>;
+            ///以Lua表中的字段更新组件，表须给出组件的所有字段(option 字段为 nil 时为 None)
+            fn apply_lua_table(&mut self, table: &LuaTable, lua: &Lua) -> LuaResult<()>;
+            ///由Lua表构造组件，表中缺少的字段使用默认值，不能有组件之外的字段
+            fn from_lua_table(table: &LuaTable, lua: &Lua) -> LuaResult<Self>
+            where
+                Self: Sized;
+        }
+
+        ///事件转换为Lua表，供事件处理器中的 event 使用
+        pub trait LuaEvent: Event {
+            fn to_lua_table(&self, lua: &Lua) -> LuaResult<LuaTable>;
+            ///由Lua表构造事件，表必须恰好给出事件声明的所有字段
+            fn from_lua_table(table: &LuaTable, lua: &Lua) -> LuaResult<Self>
+            where
+                Self: Sized;
+        }
+
+        ///读取Lua表中的字段并转换，出错时指明字段与其所属的类型
+        pub fn lua_table_field<T: LuaField>(table: &LuaTable, key: &str, owner: &str, lua: &Lua) -> LuaResult<T> {
+            T::from_lua_value(table.get(key)?, lua)
+                .map_err(|err| LuaError::runtime(format!("invalid field `{key}` of {owner}: {err}")))
+        }
+        ///检查Lua表中没有声明之外的字段
+        pub fn check_lua_table_keys(table: &LuaTable, keys: &[&str], owner: &str) -> LuaResult<()> {
+            for pair in table.pairs::<LuaValue, LuaValue>() {
+                let (key, _) = pair?;
+                let known = match &key {
+                    LuaValue::String(key) => key.to_str().is_ok_and(|key| keys.contains(&&*key)),
+                    _ => false,
+                };
+                if !known {
+                    return Err(LuaError::runtime(format!("unknown field `{}` for {owner}", key.to_string()?)));
+                }
+            }
+            Ok(())
+        }
+
+        ///entity 表中的一个组件，key 为组件名的 snake_case 形式
+        pub struct ComponentBinding {
+            pub key: &'static str,
+            read: fn(&World, Entity, &Lua) -> LuaResult<Option<LuaTable>>,
+            write: fn(&mut World, Entity, &LuaTable, &Lua) -> LuaResult<()>,
+        }
+        impl ComponentBinding {
+            pub fn of<C: LuaComponent>(key: &'static str) -> Self {
+                Self {
+                    key,
+                    read: |world, entity, lua| {
+                        world.get::<C>(entity).map(|component| component.to_lua_table(lua)).transpose()
+                    },
+                    write: |world, entity, table, lua| match world.get_mut::<C>(entity) {
+                        Some(mut component) => component.apply_lua_table(table, lua),
+                        None => Ok(()),
+                    },
+                }
+            }
+        }
+
+        ///为实体绑定 entity 表：condition 不成立时跳过，否则执行 logic，并将表中被修改的字段写回组件
+        ///
+        ///entity.id 为实体本身，可传给 ecs 中的函数。脚本执行期间不借用 World，ecs 中的函数可以修改它；
+        ///写回时以组件的当前值为基础，只覆盖脚本通过 entity 修改过的字段，不会撤销 ecs 中的修改
+        pub fn run_lua_for_entity(
+            world: &WorldCell,
+            lua: &Lua,
+            entity: Entity,
+            bindings: &[ComponentBinding],
+            condition: Option<&LuaFunction>,
+            logic: &LuaFunction,
+        ) -> LuaResult<()> {
+            let entity_table = lua.create_table()?;
+            entity_table.set("id", entity.to_lua_value(lua)?)?;
+            // 绑定时各组件的另一份副本，用于找出脚本修改过的字段
+            let mut snapshots = Vec::with_capacity(bindings.len());
+            {
+                let world = world.borrow();
+                for binding in bindings {
+                    if let Some(component) = (binding.read)(&world, entity, lua)? {
+                        entity_table.set(binding.key, component)?;
+                        snapshots.push((binding, (binding.read)(&world, entity, lua)?));
+                    }
+                }
+            }
+            lua.globals().set("entity", &entity_table)?;
+            if let Some(condition) = condition {
+                let passed = call_lua_script::<bool>(lua, condition)
+                    .map_err(|err| LuaError::external(LuaConditionError(err)))?;
+                if !passed {
+                    return Ok(());
+                }
+            }
+            call_lua_script::<()>(lua, logic)?;
+            let mut world = world.borrow_mut();
+            for (binding, snapshot) in snapshots {
+                let (Some(snapshot), Some(component)) = (snapshot, entity_table.get::<Option<LuaTable>>(binding.key)?) else {
+                    continue;
+                };
+                // 组件已被移除时不再写回
+                let Some(current) = (binding.read)(&world, entity, lua)? else {
+                    continue;
+                };
+                // 值为 nil 的字段(如为 None 的 option 字段)不在快照中，脚本给它们赋的值同样要比较
+                let keys = snapshot.pairs::<LuaValue, LuaValue>()
+                    .chain(component.pairs::<LuaValue, LuaValue>())
+                    .map(|pair| pair.map(|(key, _)| key))
+                    .collect::<LuaResult<Vec<_>>>()?;
+                let mut changed = false;
+                for key in keys {
+                    let old_value = snapshot.get::<LuaValue>(&key)?;
+                    let new_value = component.get::<LuaValue>(&key)?;
+                    if !lua_value_eq(&old_value, &new_value)? {
+                        current.set(key, new_value)?;
+                        changed = true;
+                    }
+                }
+                if changed {
+                    (binding.write)(&mut world, entity, &current, lua)?;
+                }
+            }
+            Ok(())
+        }
+        ///condition 执行出错，与 logic 中的错误区分开，报告时归于 condition 脚本
+        #[derive(Debug)]
+        pub struct LuaConditionError(pub LuaError);
+        impl std::fmt::Display for LuaConditionError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+        impl std::error::Error for LuaConditionError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+        ///按值比较两个Lua值，表逐个字段递归比较
+        fn lua_value_eq(a: &LuaValue, b: &LuaValue) -> LuaResult<bool> {
+            let (LuaValue::Table(a), LuaValue::Table(b)) = (a, b) else {
+                return a.equals(b);
+            };
+            for pair in a.pairs::<LuaValue, LuaValue>() {
+                let (key, value) = pair?;
+                if !lua_value_eq(&value, &b.get::<LuaValue>(&key)?)? {
+                    return Ok(false);
+                }
+            }
+            // a 中的键 b 都有且相等，再检查 b 中没有多出的键
+            for pair in b.pairs::<LuaValue, LuaValue>() {
+                let (key, _) = pair?;
+                if a.get::<LuaValue>(&key)?.is_nil() {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+
+        ///Lua脚本执行期间共享的 World，脚本之外的代码只在两次调用之间短暂借用
+        pub type WorldCell<'w> = std::cell::RefCell<&'w mut World>;
+
+        ///查询匹配的实体，结果先收集起来，遍历期间不再借用 World
+        pub fn query_entities<F: bevy_ecs::query::QueryFilter>(
+            world: &WorldCell,
+            query: &mut QueryState<Entity, F>,
+        ) -> Vec<Entity> {
+            query.iter(&world.borrow()).collect()
+        }
+
+        ///固定步长累积：返回本帧应执行的次数，不足一个间隔的时间留到下一帧。间隔为0时每帧执行一次
+        pub fn fixed_timestep_runs(
+            accumulated: &mut std::time::Duration,
+            delta: std::time::Duration,
+            interval: std::time::Duration,
+        ) -> u32 {
+            if interval.is_zero() {
+                return 1;
+            }
+            *accumulated += delta;
+            let mut runs = 0;
+            while *accumulated >= interval {
+                *accumulated -= interval;
+                runs += 1;
+            }
+            runs
+        }
+
+        ///报告脚本出错的模块、系统与处理器，错误信息中的位置为 TOML文件:行号 或 脚本文件:行号
+        pub fn report_lua_error(key: &LuaScriptKey, error: &LuaError) {
+            eprintln!("[aethaum] Lua error in {}: {}", key, error);
+        }
+    }
+}
+//System Priority
+///系统按优先级排序：所有模块共用 Update 调度，排序需跨模块生效
+pub fn system_priority() -> TokenStream {
+    quote! {
+        ///系统的执行顺序键。先比较系统优先级，同一系统中 update 先于事件处理器，事件处理器再按自身优先级，均为越高越先执行
+        #[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct AethaumPriority {
+            pub system: u32,
+            pub handler: u64,
+        }
+        impl AethaumPriority {
+            pub const fn update(system: u32) -> Self {
+                Self { system, handler: u64::MAX }
+            }
+            pub const fn handler(system: u32, handler: u32) -> Self {
+                Self { system, handler: handler as u64 }
+            }
+        }
+
+        ///已注册的执行顺序键，在 App 中共享
+        #[derive(Resource, Default)]
+        pub struct AethaumPriorities(pub std::collections::BTreeSet<AethaumPriority>);
+
+        ///将系统加入其优先级对应的集合，并让该集合排在所有更低优先级的集合之前
+        pub fn add_prioritized_systems<M>(
+            app: &mut bevy_app::App,
+            priority: AethaumPriority,
+            systems: impl IntoScheduleConfigs<bevy_ecs::system::ScheduleSystem, M>,
+        ) {
+            app.init_resource::<AethaumPriorities>();
+            let mut priorities = app.world_mut().resource_mut::<AethaumPriorities>();
+            if priorities.0.insert(priority) {
+                // 只需与相邻的集合建立顺序，其余顺序由传递性保证
+                let higher = priorities.0.range(priority..).nth(1).copied();
+                let lower = priorities.0.range(..priority).next_back().copied();
+                if let Some(higher) = higher {
+                    app.configure_sets(bevy_app::Update, higher.before(priority));
+                }
+                if let Some(lower) = lower {
+                    app.configure_sets(bevy_app::Update, priority.before(lower));
+                }
             }
+            app.add_systems(bevy_app::Update, systems.in_set(priority));
         }
     }
 }
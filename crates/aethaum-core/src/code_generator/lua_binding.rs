@@ -1,4 +1,30 @@
-mod bevy_plugin;
-mod execution_context;
-mod reflection;
-mod ecs;
\ No newline at end of file
+pub mod bevy_plugin;
+pub mod execution_context;
+pub mod reflection;
+pub mod ecs;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+///生成的 lua_bindings.rs：按名称访问组件与实体原型，组件的Lua反射数据，以及Lua中的 ecs 表
+pub fn lua_bindings() -> TokenStream {
+    let reflection = reflection::reflection_token();
+    let reflect_init_registry = execution_context::lua_reflect_init_registry_token();
+    let ecs = ecs::ecs_token();
+    let plugin = bevy_plugin::lua_bevy_plugin();
+    quote! {
+        //! Lua bindings
+        //! Auto-generated by Aethaum
+        use std::any::TypeId;
+        use std::collections::HashMap;
+        use bevy_ecs::prelude::*;
+        use bevy_reflect::{FromType, Reflect, TypeRegistry};
+        use mlua::prelude::*;
+        use crate::aethaum_predefined::*;
+
+        #reflection
+        #reflect_init_registry
+        #ecs
+        #plugin
+    }
+}
@@ -1,74 +1,19 @@
-use mlua::prelude::*;
-use bevy_ecs::prelude::*;
 use proc_macro2::TokenStream;
 use quote::quote;
-pub fn test() {
-    let lua = Lua::new();
-}
+
+///生成代码的Lua入口插件：Lua运行时，启动时的脚本编译与脚本文件的热重载，以及按名称访问组件、原型与事件的注册表
 pub fn lua_bevy_plugin() -> TokenStream {
     quote! {
-        use bevy_ecs::prelude::*;
-        use bevy_app::prelude::*;
-        use std::sync::{Arc, Mutex};
-        use mlua::prelude::*;
-
+        ///Lua运行时与 ecs 使用的注册表，main.rs 与各模块插件共享同一个实例
         pub struct LuaPlugin;
-        impl Plugin for LuaPlugin {
-            fn build(&self, app: &mut App) {
+        impl bevy_app::Plugin for LuaPlugin {
+            fn build(&self, app: &mut bevy_app::App) {
                 app.init_resource::<LuaRuntime>()
-                    .add_systems(Update, execute_lua_systems);
-            }
-        }
-        #[derive(Resource)]
-        pub struct LuaRuntime {
-            pub lua: Arc<Mutex<Lua>>,
-        }
-        impl Default for LuaRuntime {
-            fn default() -> Self {
-                Self {
-                    lua: Arc::new(Mutex::new(Lua::new())),
-                }
+                    .init_resource::<LuaReflectInitRegistry>()
+                    .init_resource::<LuaEcsRegistry>()
+                    .add_systems(bevy_app::Startup, compile_lua_scripts)
+                    .add_systems(bevy_app::First, reload_lua_scripts);
             }
         }
-        fn execute_lua_systems(runtime: ResMut<LuaRuntime>) {
-
-        }
     }
 }
-pub fn lua_script() -> TokenStream {
-    quote! {
-        pub enum LuaScript {
-            Embed(Chunk),
-            File(ScriptFile)
-        }
-        pub struct ScriptFile {
-            path: PathBuf,
-            content: Chunk
-        }
-        impl LuaScript {
-            pub fn load_embed(script: &str, lua: &Lua) -> Self {
-                let chunk = lua.load(script);
-                LuaScript::Embed(chunk)
-            }
-            pub fn load_file(path: &Path, lua: &Lua) -> io::Result<Self> {
-                let content = std::fs::read_to_string(path)?;
-                let chunk = lua.load(file);
-                LuaScript::File(ScriptFile {
-                    path: path.into(),
-                    content: chunk
-                })
-            }
-        }
-    }
-}
-pub fn lua_script_server() -> TokenStream {
-    quote! {
-        pub struct LuaScriptServer {
-            pub registered: Vec<LuaScript>
-        }
-    }
-}
-#[derive(Resource)]
-pub struct LuaCommands {
-
-}
\ No newline at end of file
@@ -1,139 +1,323 @@
-mod entity;
-mod component;
+use proc_macro2::TokenStream;
+use quote::quote;
 
-///这些代码是在设计架构的时候，方便审查，测试用的，后续将会拆分至一系列输出TokenStream的函数用于代码生成
-use std::any::{Any, TypeId};
-use std::collections::HashMap;
-use bevy_ecs::bundle::DynamicBundle;
-use bevy_ecs::entity::EntityDoesNotExistError;
-use bevy_ecs::prelude::*;
-use bevy_ecs::reflect::{ReflectBundle, ReflectCommandExt};
-use bevy_ecs::world::reflect::GetComponentReflectError;
-use bevy_reflect::prelude::*;
-use bevy_reflect::{TypeData, TypeRegistry};
-use mlua::FromLua;
-use thiserror::Error;
-use crate::code_generator::lua_binding::ecs::component::{get_components_fn, handle_get_component_result};
-use crate::code_generator::lua_binding::ecs::entity::{despawn_fn, spawn_empty_fn, spawn_reflect_fn};
-use crate::code_generator::lua_binding::execution_context::{LuaExecutionContext, LuaReflectInitRegistry};
-use crate::code_generator::lua_binding::reflection::{get_type_id_by_str, ReflectToLua};
-pub enum AethaumEcsCommandType {
-    SpawnEntity {
-        proto_name: Option<String>,
-        init_value: Option<mlua::Value>,
-    },
-    DespawnEntity {
-        entity: Entity,
-    },
-    GetComponents {
-        entity: Entity,
-        components: Option<Vec<String>>
-    },
-}
-pub enum AethaumEcsCommandResponse {
-    SpawnEntity {
-        entity: Entity,
-    },
-    DespawnEntity,
-    GetComponents {
-        components: Vec<Option<mlua::Value>>
-    },
-    Error(String)
-}
-#[derive(Event)]
-pub struct AethaumEcsCommand {
-    pub command: AethaumEcsCommandType,
-    pub response_sender: std::sync::mpsc::Sender<AethaumEcsCommandResponse>,
+///生成代码中按名称操作 World 的函数，以及以它们为基础、在每个Lua脚本中可用的 ecs 表
+pub fn ecs_token() -> TokenStream {
+    let registry = lua_ecs_registry_token();
+    let lua_api = ecs_lua_api_token();
+    quote! {
+        #registry
+        #lua_api
+    }
 }
-
-
-pub fn process_aethaum_ecs_command(
-    world: &World,
-    mut commands: Commands,
-    mut events: EventReader<AethaumEcsCommand>,
-    lua_execution_context: Res<LuaExecutionContext>,
-    type_registration: Res<AppTypeRegistry>,
-    lua_bundle_registry: Res<LuaReflectInitRegistry>,
-) {
-    for event in events.read() {
-        let result = match &event.command {
-            AethaumEcsCommandType::SpawnEntity { proto_name, init_value } => {
-                if let Some(proto_name) = proto_name {
-                    let entity = spawn_reflect_fn(
-                        &mut commands,
-                        &proto_name,
-                        init_value.clone(), //TODO: 是否可以避免此处的clone
-                        lua_execution_context.as_ref(),
-                        type_registration.as_ref(),
-                        lua_bundle_registry.as_ref(),
-                    );
-                    match entity {
-                        Some(entity) => {
-                            AethaumEcsCommandResponse::SpawnEntity {
-                                entity
-                            }
-                        },
-                        None => {
-                            AethaumEcsCommandResponse::Error("Failed to spawn entity".to_string())
+fn lua_ecs_registry_token() -> TokenStream {
+    quote! {
+        ///组件在Lua中的读写方式
+        #[derive(Clone, Copy)]
+        pub struct LuaComponentFns {
+            pub get: fn(&World, Entity, &Lua) -> LuaResult<Option<LuaTable>>,
+            pub has: fn(&World, Entity) -> bool,
+            pub set: fn(&mut World, Entity, &LuaTable, &Lua) -> LuaResult<()>,
+            ///以表中的字段更新已有的组件
+            pub apply: fn(&mut World, Entity, &LuaTable, &Lua) -> LuaResult<()>,
+            pub remove: fn(&mut World, Entity),
+            pub component_id: fn(&mut World) -> bevy_ecs::component::ComponentId,
+        }
+        impl LuaComponentFns {
+            pub fn of<C: LuaComponent>() -> Self {
+                Self {
+                    get: |world, entity, lua| {
+                        world.get::<C>(entity).map(|component| component.to_lua_table(lua)).transpose()
+                    },
+                    has: |world, entity| world.get::<C>(entity).is_some(),
+                    set: |world, entity, table, lua| {
+                        let component = C::from_lua_table(table, lua)?;
+                        world.get_entity_mut(entity).map_err(LuaError::external)?.insert(component);
+                        Ok(())
+                    },
+                    apply: |world, entity, table, lua| match world.get_mut::<C>(entity) {
+                        Some(mut component) => component.apply_lua_table(table, lua),
+                        None => Err(LuaError::runtime(format!("entity {entity} has no component {}", std::any::type_name::<C>()))),
+                    },
+                    remove: |world, entity| {
+                        if let Ok(mut entity) = world.get_entity_mut(entity) {
+                            entity.remove::<C>();
                         }
+                    },
+                    component_id: |world| world.register_component::<C>(),
+                }
+            }
+        }
+        ///以原型生成实体，给出表时以组件名为键覆盖原型中的组件
+        pub type ProtoSpawnFn = fn(&mut World, Option<&LuaTable>, &Lua) -> LuaResult<Entity>;
+        ///Lua中可以按名称使用的组件与实体原型，由各模块插件注册，名称经 get_type_id_by_str 解析为 TypeId
+        #[derive(Resource, Default)]
+        pub struct LuaEcsRegistry {
+            components: HashMap<TypeId, LuaComponentFns>,
+            ///组件在实体视图中的键：与 entity 表相同的 snake_case 名称，以及 `module::name`
+            component_keys: HashMap<String, Vec<TypeId>>,
+            protos: HashMap<TypeId, ProtoSpawnFn>,
+            events: HashMap<TypeId, fn(&mut World, &LuaTable, &Lua) -> LuaResult<()>>,
+        }
+        impl LuaEcsRegistry {
+            pub fn register_component<C: LuaComponent>(&mut self, module: &str, key: &str) {
+                self.components.insert(TypeId::of::<C>(), LuaComponentFns::of::<C>());
+                for key in [key.to_string(), format!("{module}::{key}")] {
+                    let type_ids = self.component_keys.entry(key).or_default();
+                    if !type_ids.contains(&TypeId::of::<C>()) {
+                        type_ids.push(TypeId::of::<C>());
                     }
-                }else {
-                    AethaumEcsCommandResponse::SpawnEntity { entity: spawn_empty_fn(&mut commands)}
                 }
-
-            },
-            AethaumEcsCommandType::DespawnEntity { entity } => {
-                despawn_fn(&mut commands, *entity);
-                AethaumEcsCommandResponse::DespawnEntity
             }
-            AethaumEcsCommandType::GetComponents { entity, components } => {
-                let type_registry = type_registration.read();
-                let components = get_components_fn(
-                    world,
-                    *entity,
-                    components.as_ref(),
-                    &type_registry,
-                    &lua_execution_context.lua
-                );
-                handle_get_component_result(components)
+            pub fn register_proto<B: Bundle + Default + FromLua>(&mut self) {
+                self.protos.insert(TypeId::of::<B>(), |world, init_value, lua| {
+                    let bundle = match init_value {
+                        Some(init_value) => B::from_lua(LuaValue::Table(init_value.clone()), lua)?,
+                        None => B::default(),
+                    };
+                    Ok(world.spawn(bundle).id())
+                });
+            }
+            pub fn register_event<E: LuaEvent>(&mut self) {
+                self.events.insert(TypeId::of::<E>(), |world, payload, lua| {
+                    world.send_event(E::from_lua_table(payload, lua)?);
+                    Ok(())
+                });
+            }
+            pub fn component(&self, type_id: TypeId) -> Option<LuaComponentFns> {
+                self.components.get(&type_id).copied()
+            }
+            ///按实体视图中的键查找组件，多个模块有同名组件时须写作 `module::name`
+            pub fn component_by_key(&self, key: &str) -> LuaResult<LuaComponentFns> {
+                match self.component_keys.get(key).map(Vec::as_slice) {
+                    Some([type_id]) => Ok(self.components[type_id]),
+                    Some(_) => Err(LuaError::runtime(format!("component key `{key}` is ambiguous, use `module::{key}`"))),
+                    None => Err(LuaError::runtime(format!("no such component: {key}"))),
+                }
+            }
+            pub fn proto(&self, type_id: TypeId) -> Option<ProtoSpawnFn> {
+                self.protos.get(&type_id).copied()
+            }
+            pub fn event(&self, type_id: TypeId) -> Option<fn(&mut World, &LuaTable, &Lua) -> LuaResult<()>> {
+                self.events.get(&type_id).copied()
             }
-        };
-        if let Err(_) = event.response_sender.send(result) {
-            eprintln!("Failed to send ECS command response");
         }
-    }
-}
-
-
-
-#[derive(Error, Debug)]
-pub enum LuaBridgeError {
-    #[error("Error in Reflection: {0}")]
-    Reflection(#[from] GetComponentReflectError),
-    #[error("Error in ECS: {0}")]
-    EntityNotExist(#[from] EntityDoesNotExistError),
-    #[error("No such component, did you declare it?")]
-    NoSuchComponent,
-    #[error("Error when converting to Lua: {0}")]
-    ConversionToLua(#[from] mlua::Error)
-}
+        fn lua_component(world: &World, name: &str) -> LuaResult<LuaComponentFns> {
+            let registry = world.resource::<AppTypeRegistry>().read();
+            get_type_id_by_str(name, &registry)
+                .and_then(|type_id| world.resource::<LuaEcsRegistry>().component(type_id))
+                .ok_or_else(|| LuaError::runtime(format!("no such component: {name}")))
+        }
+        fn lua_component_by_key(world: &World, key: &str) -> LuaResult<LuaComponentFns> {
+            world.resource::<LuaEcsRegistry>().component_by_key(key)
+        }
+        fn lua_proto(world: &World, name: &str) -> LuaResult<ProtoSpawnFn> {
+            let registry = world.resource::<AppTypeRegistry>().read();
+            get_type_id_by_str(name, &registry)
+                .and_then(|type_id| world.resource::<LuaEcsRegistry>().proto(type_id))
+                .ok_or_else(|| LuaError::runtime(format!("no such entity prototype: {name}")))
+        }
 
+        fn lua_event(world: &World, name: &str) -> LuaResult<fn(&mut World, &LuaTable, &Lua) -> LuaResult<()>> {
+            let registry = world.resource::<AppTypeRegistry>().read();
+            get_type_id_by_str(name, &registry)
+                .and_then(|type_id| world.resource::<LuaEcsRegistry>().event(type_id))
+                .ok_or_else(|| LuaError::runtime(format!("no such event: {name}")))
+        }
 
-///仅用于说明
-#[derive(Bundle)]
-pub struct TestBundle {
-    pub test_component: TestComponent
-}
-#[derive(Component)]
-pub struct TestComponent {
-    pub a: i32,
+        ///生成实体，init_value 以组件名为键：指定原型时从原型的默认值开始，覆盖原型中的组件；
+        ///否则以给出的组件生成
+        pub fn spawn_fn(world: &mut World, proto_name: Option<&str>, init_value: Option<&LuaTable>, lua: &Lua) -> LuaResult<Entity> {
+            if let Some(proto_name) = proto_name {
+                return lua_proto(world, proto_name)?(world, init_value, lua);
+            }
+            let entity = world.spawn_empty().id();
+            if let Some(init_value) = init_value {
+                let result = init_value.pairs::<String, LuaTable>()
+                    .try_for_each(|pair| {
+                        let (component_name, value) = pair?;
+                        set_component_fn(world, entity, &component_name, &value, lua)
+                    });
+                if let Err(err) = result {
+                    world.despawn(entity);
+                    return Err(err);
+                }
+            }
+            Ok(entity)
+        }
+        ///实体没有该组件或实体不存在时，对应的结果为 None
+        pub fn get_components_fn(world: &World, entity: Entity, components: &[String], lua: &Lua) -> LuaResult<Vec<Option<LuaTable>>> {
+            components.iter()
+                .map(|component_name| (lua_component(world, component_name)?.get)(world, entity, lua))
+                .collect()
+        }
+        ///以表中的值替换组件，实体没有该组件时添加
+        pub fn set_component_fn(world: &mut World, entity: Entity, component_name: &str, value: &LuaTable, lua: &Lua) -> LuaResult<()> {
+            (lua_component(world, component_name)?.set)(world, entity, value, lua)
+        }
+        pub fn has_component_fn(world: &World, entity: Entity, component_name: &str) -> LuaResult<bool> {
+            Ok((lua_component(world, component_name)?.has)(world, entity))
+        }
+        ///按事件声明检查 payload 并发送事件，与 EventWriter 写入同一个 Events 队列
+        pub fn emit_event_fn(world: &mut World, event_name: &str, payload: &LuaTable, lua: &Lua) -> LuaResult<()> {
+            lua_event(world, event_name)?(world, payload, lua)
+        }
+        ///与 ComponentConstraint 相同的规则：包含 include 中的所有组件，且不包含 exclude 中的任何组件
+        pub fn query_fn(world: &mut World, include: &[String], exclude: &[String]) -> LuaResult<Vec<Entity>> {
+            let mut component_ids = |names: &[String]| {
+                names.iter()
+                    .map(|name| Ok((lua_component(world, name)?.component_id)(world)))
+                    .collect::<LuaResult<Vec<_>>>()
+            };
+            let include = component_ids(include)?;
+            let exclude = component_ids(exclude)?;
+            let mut builder = QueryBuilder::<Entity>::new(world);
+            for component_id in include {
+                builder.with_id(component_id);
+            }
+            for component_id in exclude {
+                builder.without_id(component_id);
+            }
+            let mut query = builder.build();
+            Ok(query.iter(world).collect())
+        }
+        ///读取组件的一个字段，组件以实体视图中的键给出，实体没有该组件时为 nil
+        pub fn get_component_field_fn(world: &World, entity: Entity, component_key: &str, field: &str, lua: &Lua) -> LuaResult<LuaValue> {
+            match (lua_component_by_key(world, component_key)?.get)(world, entity, lua)? {
+                Some(component) => component.get(field),
+                None => Ok(LuaNil),
+            }
+        }
+        pub fn set_component_field_fn(world: &mut World, entity: Entity, component_key: &str, field: &str, value: LuaValue, lua: &Lua) -> LuaResult<()> {
+            let component = lua_component_by_key(world, component_key)?;
+            let table = (component.get)(world, entity, lua)?
+                .ok_or_else(|| LuaError::runtime(format!("entity {entity} has no component {component_key}")))?;
+            if !table.contains_key(field)? {
+                return Err(LuaError::runtime(format!("unknown field `{field}` for {component_key}")));
+            }
+            table.set(field, value)?;
+            (component.apply)(world, entity, &table, lua)
+        }
+        pub fn remove_component_fn(world: &mut World, entity: Entity, component_name: &str) -> LuaResult<()> {
+            (lua_component(world, component_name)?.remove)(world, entity);
+            Ok(())
+        }
+    }
 }
-impl FromLua for TestBundle {
-    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
-        Ok(TestBundle {
-            test_component: TestComponent {
-                a: value.as_integer().unwrap() as i32,
-            },
-        })
+fn ecs_lua_api_token() -> TokenStream {
+    quote! {
+        fn with_world<R>(world: &WorldCell, f: impl FnOnce(&mut World) -> LuaResult<R>) -> LuaResult<R> {
+            let mut world = world.try_borrow_mut()
+                .map_err(|_| LuaError::runtime("ecs cannot be used while the world is borrowed"))?;
+            f(&mut world)
+        }
+        ///视图的目标实体与组件保存在视图各自的元表中，视图本身不含任何字段
+        fn view_target(view: &LuaTable, lua: &Lua) -> LuaResult<(Entity, Option<String>)> {
+            let metatable = view.metatable().ok_or_else(|| LuaError::runtime("not an entity view"))?;
+            Ok((Entity::from_lua_value(metatable.raw_get("entity")?, lua)?, metatable.raw_get("component")?))
+        }
+        fn new_view(lua: &Lua, entity: Entity, component: Option<&str>, index: &LuaFunction, newindex: Option<&LuaFunction>) -> LuaResult<LuaTable> {
+            let metatable = lua.create_table()?;
+            metatable.raw_set("__index", index)?;
+            metatable.raw_set("__newindex", newindex)?;
+            metatable.raw_set("entity", entity.to_lua_value(lua)?)?;
+            metatable.raw_set("component", component)?;
+            let view = lua.create_table()?;
+            view.set_metatable(Some(metatable))?;
+            Ok(view)
+        }
+        ///在 f 执行期间为Lua提供 ecs 与 events 表，每个调用都直接在 World 上执行，脚本立即得到结果：
+        ///ecs.spawn(proto?, init?)，ecs.despawn(e)，ecs.get(e, name, ...)，ecs.set(e, name, value)，
+        ///ecs.has(e, name)，ecs.remove(e, name)，ecs.query({ include, exclude }) 与 events.emit(name, payload)。
+        ///名称可以是 `module::Name` 或无歧义的 `Name`
+        ///
+        ///ecs.query 返回实体视图的迭代器：view.id 为实体，与 entity 表相同，view.health 等以 snake_case 组件名为键的是组件视图，
+        ///读写其字段即读写 World 中的组件
+        ///
+        ///发送的事件按发送顺序排队：优先级低于发送者、在本tick中随后运行的处理器在本tick收到，
+        ///已经运行过的处理器在下一个tick收到
+        pub fn with_ecs<R>(world: &mut World, lua: &Lua, f: impl FnOnce(&WorldCell) -> LuaResult<R>) -> LuaResult<R> {
+            let world = WorldCell::new(world);
+            let result = lua.scope(|scope| {
+                let ecs = lua.create_table()?;
+                ecs.set("spawn", scope.create_function(|lua, (proto_name, init_value): (Option<String>, Option<LuaTable>)| {
+                    let entity = with_world(&world, |world| spawn_fn(world, proto_name.as_deref(), init_value.as_ref(), lua))?;
+                    entity.to_lua_value(lua)
+                })?)?;
+                ecs.set("despawn", scope.create_function(|lua, entity: LuaValue| {
+                    let entity = Entity::from_lua_value(entity, lua)?;
+                    with_world(&world, |world| {
+                        world.despawn(entity);
+                        Ok(())
+                    })
+                })?)?;
+                ecs.set("get", scope.create_function(|lua, (entity, components): (LuaValue, LuaVariadic<String>)| {
+                    let entity = Entity::from_lua_value(entity, lua)?;
+                    let components = with_world(&world, |world| get_components_fn(world, entity, &components, lua))?;
+                    components.into_iter().map(|component| component.into_lua(lua)).collect::<LuaResult<LuaMultiValue>>()
+                })?)?;
+                ecs.set("set", scope.create_function(|lua, (entity, component, value): (LuaValue, String, LuaTable)| {
+                    let entity = Entity::from_lua_value(entity, lua)?;
+                    with_world(&world, |world| set_component_fn(world, entity, &component, &value, lua))
+                })?)?;
+                ecs.set("has", scope.create_function(|lua, (entity, component): (LuaValue, String)| {
+                    let entity = Entity::from_lua_value(entity, lua)?;
+                    with_world(&world, |world| has_component_fn(world, entity, &component))
+                })?)?;
+                ecs.set("remove", scope.create_function(|lua, (entity, component): (LuaValue, String)| {
+                    let entity = Entity::from_lua_value(entity, lua)?;
+                    with_world(&world, |world| remove_component_fn(world, entity, &component))
+                })?)?;
+                let component_index = scope.create_function(|lua, (view, field): (LuaTable, String)| {
+                    let (entity, component) = view_target(&view, lua)?;
+                    with_world(&world, |world| get_component_field_fn(world, entity, &component.unwrap_or_default(), &field, lua))
+                })?;
+                let component_newindex = scope.create_function(|lua, (view, field, value): (LuaTable, String, LuaValue)| {
+                    let (entity, component) = view_target(&view, lua)?;
+                    with_world(&world, |world| set_component_field_fn(world, entity, &component.unwrap_or_default(), &field, value, lua))
+                })?;
+                // 视图的元方法被其他作用域函数持有，这些函数以 move 捕获，只借用 world
+                let world = &world;
+                let entity_index = scope.create_function(move |lua, (view, name): (LuaTable, String)| {
+                    let (entity, _) = view_target(&view, lua)?;
+                    if name == "id" {
+                        return entity.to_lua_value(lua);
+                    }
+                    let component = with_world(world, |world| lua_component_by_key(world, &name))?;
+                    if !with_world(world, |world| Ok((component.has)(world, entity)))? {
+                        return Ok(LuaNil);
+                    }
+                    let component_view = new_view(lua, entity, Some(&name), &component_index, Some(&component_newindex))?;
+                    Ok(LuaValue::Table(component_view))
+                })?;
+                ecs.set("query", scope.create_function(move |lua, filter: LuaTable| {
+                    check_lua_table_keys(&filter, &["include", "exclude"], "ecs.query")?;
+                    let include = filter.get::<Option<Vec<String>>>("include")?.unwrap_or_default();
+                    let exclude = filter.get::<Option<Vec<String>>>("exclude")?.unwrap_or_default();
+                    let entities = with_world(world, |world| query_fn(world, &include, &exclude))?;
+                    // 匹配的实体在调用时确定，迭代期间按需创建视图
+                    let entity_index = entity_index.clone();
+                    let mut entities = entities.into_iter();
+                    let iterator = lua.create_function_mut(move |lua, _: LuaMultiValue| {
+                        match entities.next() {
+                            Some(entity) => Ok(LuaValue::Table(new_view(lua, entity, None, &entity_index, None)?)),
+                            None => Ok(LuaNil),
+                        }
+                    })?;
+                    Ok(LuaValue::Function(iterator))
+                })?)?;
+                let events = lua.create_table()?;
+                events.set("emit", scope.create_function(|lua, (event, payload): (String, LuaTable)| {
+                    with_world(world, |world| emit_event_fn(world, &event, &payload, lua))
+                })?)?;
+                lua.globals().set("ecs", ecs)?;
+                lua.globals().set("events", events)?;
+                f(world)
+            });
+            // 作用域结束后其中的函数已失效
+            lua.globals().set("ecs", LuaNil)?;
+            lua.globals().set("events", LuaNil)?;
+            result
+        }
     }
 }
@@ -1,47 +1,34 @@
-use std::any::TypeId;
-use std::collections::HashMap;
-///这些代码是在设计架构的时候，方便审查，测试用的，后续将会拆分至一系列输出TokenStream的函数用于代码生成
-use bevy_ecs::prelude::*;
-use mlua::prelude::*;
-use std::sync::{Arc, Mutex};
-use bevy_reflect::Reflect;
+use proc_macro2::TokenStream;
+use quote::quote;
 
-#[derive(Resource)]
-pub struct LuaExecutionContext {
-    pub lua: Arc<Lua>,
-}
-pub struct LuaReflectInit {
-    pub from_lua: fn(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Box<dyn Reflect>>,
-    pub default: fn() -> Box<dyn Reflect>,
-}
-#[derive(Resource)]
-pub struct LuaReflectInitRegistry {
-    constructors: HashMap<TypeId, LuaReflectInit>,
-}
-impl LuaReflectInitRegistry {
-    pub fn new() -> Self {
-        Self {
-            constructors: HashMap::new(),
+///生成代码中按类型构造组件的注册表
+pub fn lua_reflect_init_registry_token() -> TokenStream {
+    quote! {
+        pub struct LuaReflectInit {
+            pub from_lua: fn(value: LuaValue, lua: &Lua) -> LuaResult<Box<dyn Reflect>>,
+            pub default: fn() -> Box<dyn Reflect>,
+        }
+        ///由Lua值或默认值构造已注册的类型
+        #[derive(Resource, Default)]
+        pub struct LuaReflectInitRegistry {
+            constructors: HashMap<TypeId, LuaReflectInit>,
+        }
+        impl LuaReflectInitRegistry {
+            pub fn register_constructor<T>(&mut self)
+            where
+                T: Bundle + Reflect + Default + FromLua
+            {
+                self.constructors.insert(
+                    TypeId::of::<T>(),
+                    LuaReflectInit {
+                        from_lua: |value, lua| Ok(Box::new(T::from_lua(value, lua)?)),
+                        default: || Box::new(T::default()),
+                    }
+                );
+            }
+            pub fn get_constructor(&self, type_id: TypeId) -> Option<&LuaReflectInit> {
+                self.constructors.get(&type_id)
+            }
         }
     }
-    pub fn register_constructor<T>(&mut self)
-    where
-        T: Bundle + Reflect + Default + for<'lua> FromLua + 'static
-    {
-        let from_lua_fn = |value: mlua::Value, lua: &mlua::Lua| -> mlua::Result<Box<dyn Reflect>> {
-            let bundle = T::from_lua(value, lua)?;
-            Ok(Box::new(bundle))
-        };
-        let default_fn = || -> Box<dyn Reflect> {
-            Box::new(T::default())
-        };
-
-        self.constructors.insert(
-            TypeId::of::<T>(),
-            LuaReflectInit { from_lua: from_lua_fn, default: default_fn}
-        );
-    }
-    pub fn get_constructor(&self, type_id: TypeId) -> Option<&LuaReflectInit> {
-        self.constructors.get(&type_id)
-    }
-}
\ No newline at end of file
+}
@@ -1,11 +1,12 @@
 use thiserror::Error;
 use crate::ecs::module::AethaumProject;
+use crate::toml_parser::parsed::BuildTarget;
 use std::fs;
 use std::path::{Path, PathBuf};
 use quote::quote;
-use crate::code_generator::aethaum_predefined;
-use crate::code_generator::transpile::Transpile;
-use crate::code_generator::utils::format_rust_code;
+use convert_case::{Case, Casing};
+use crate::code_generator::{aethaum_predefined, lua_binding, transpile};
+use crate::code_generator::utils::{format_rust_code, write_if_changed};
 
 /// 项目生成模块
 /// 生成的项目架构示例：
@@ -29,15 +30,50 @@ use crate::code_generator::utils::format_rust_code;
 /// │   │   └── ui/
 /// │   │       └── scripts/
 /// │   └── scripts/
-/// └── config/
+///
+/// 运行时读取的是项目根目录下的 config/config.toml，生成的项目中没有配置文件
 #[derive(Debug, Error)]
 pub enum TemplateGenerationError {
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("Failed to serialize Cargo.toml: {0}")]
+    CargoManifest(toml::ser::Error),
     #[error("Failed to create directory: {0}")]
     DirectoryCreationError(PathBuf),
 }
 
+///生成项目的默认依赖
+const DEFAULT_DEPENDENCIES: &str = r#"
+tokio = { version = "1.47", features = ["full"] }
+serde = { version = "1.0", features = ["derive"] }
+serde_json = "1.0"
+mlua = { version = "0.11.2", features = ["lua54", "vendored", "send"] }
+bevy_ecs = "0.16.1"
+bevy_app = "0.16.1"
+bevy_time = "0.16.1"
+bevy_reflect = "0.16.1"
+bevy_math = { version = "0.16.1", default-features = false, features = ["std", "bevy_reflect"] }
+bevy_color = { version = "0.16.2", default-features = false, features = ["std", "bevy_reflect"] }
+toml = "0.9"
+smart-string = { version = "0.1.3", features = ["serde"]}
+itertools = "0.14.0"
+one-or-many = "0.4.0"
+miette = "7.6.0"
+thiserror = "2.0.16"
+"#;
+
+///把 overrides 深合并到 base：两边都是表时递归合并，否则以 overrides 为准，数组同样整体替换
+fn merge_toml_table(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => merge_toml_table(base, overrides),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
 pub struct ProjectTemplateGenerator;
 
 impl ProjectTemplateGenerator {
@@ -57,15 +93,12 @@ impl ProjectTemplateGenerator {
         // 生成 assets 目录结构
         Self::generate_assets_structure(project_path, project)?;
 
-        // 生成 config 目录
-        Self::generate_config_structure(project_path)?;
-
         Ok(())
     }
 
     fn build_folder_structure(project_path: &Path, project: &AethaumProject) -> Result<(), TemplateGenerationError> {
         // 创建根目录
-        let root_dirs: [PathBuf;3] = ["src".into(), "assets".into(), "config".into()];
+        let root_dirs: [PathBuf;2] = ["src".into(), "assets".into()];
         for dir in &root_dirs {
             let full_path = project_path.join(dir);
             fs::create_dir_all(&full_path)?;
@@ -90,68 +123,74 @@ impl ProjectTemplateGenerator {
     }
 
     fn generate_cargo_toml(project_path: &Path, project: &AethaumProject) -> Result<(), TemplateGenerationError> {
-        let cargo_toml_content = format!(
-            r#"[package]
-name = "{}"
-version = "0.1.0"
-edition = "2024"
-
-[dependencies]
-tokio = {{ version = "1.47", features = ["full"] }}
-serde = {{ version = "1.0", features = ["derive"] }}
-serde_json = "1.0"
-mlua = {{ version = "0.11.2", features = ["lua54", "vendored"] }}
-bevy_ecs = "0.16.1"
-bevy_app = "0.16.1"
-bevy_reflect = "0.16.1"
-smart-string = {{ version = "0.1.3", features = ["serde"]}}
-itertools = "0.14.0"
-one-or-many = "0.4.0"
-miette = "7.6.0"
-thiserror = "2.0.16"
-
-[workspace]
-            "#,
-            project.world.normal.name.as_str()
-        );
+        let cargo_toml_content = toml::to_string(&Self::cargo_manifest(project))
+            .map_err(TemplateGenerationError::CargoManifest)?;
 
         let cargo_toml_path = project_path.join("Cargo.toml");
-        fs::write(cargo_toml_path, cargo_toml_content)?;
+        write_if_changed(&cargo_toml_path, &cargo_toml_content)?;
 
         Ok(())
     }
 
-    fn generate_source_files(project_path: &Path, project: &AethaumProject) -> Result<(), TemplateGenerationError> {
-        // 生成 main.rs - 添加插件注册
-        let mut plugin_registrations = Vec::new();
-        for module in project.module_tree.get_modules() {
-            let module_name = proc_macro2::Ident::new(module.name.as_str(), proc_macro2::Span::call_site());
-            let plugin_name = proc_macro2::Ident::new(
-                &format!("{}Plugin", module.name.as_str()),
-                proc_macro2::Span::call_site()
-            );
-            plugin_registrations.push(quote! {
-            .add_plugins(modules::#module_name::#plugin_name)
-        });
+    ///默认清单合并 world.toml 中 [cargo] 的配置，表逐项合并，数组追加缺少的元素，其他同名项以 world.toml 为准
+    fn cargo_manifest(project: &AethaumProject) -> toml::Table {
+        let world = &project.world;
+        let mut manifest = toml::Table::new();
+
+        let mut package = toml::Table::new();
+        package.insert("name".into(), world.crate_name().into());
+        package.insert("version".into(), world.normal.version.as_str().into());
+        package.insert("edition".into(), "2024".into());
+        if !world.normal.author.is_empty() {
+            package.insert("authors".into(), toml::Value::Array(vec![world.normal.author.as_str().into()]));
         }
+        merge_toml_table(&mut package, world.cargo.package.clone());
+        manifest.insert("package".into(), package.into());
 
-        let main_rs_content = quote! {
-            use bevy_ecs::prelude::*;
-            use bevy_app::prelude::*;
+        let mut dependencies: toml::Table = toml::from_str(DEFAULT_DEPENDENCIES)
+            .expect("default dependencies should be valid toml");
+        merge_toml_table(&mut dependencies, world.cargo.dependencies.clone());
+        manifest.insert("dependencies".into(), dependencies.into());
 
-            mod modules;
-            mod lua_bindings;
-            mod aethaum_predefined;
+        if !world.cargo.features.is_empty() {
+            manifest.insert("features".into(), world.cargo.features.clone().into());
+        }
+        if !world.cargo.profile.is_empty() {
+            manifest.insert("profile".into(), world.cargo.profile.clone().into());
+        }
 
-            fn main() {
-                App::new()
-                    #(#plugin_registrations)*
-                    .run();
-            }
-        };
+        // 可执行目标的 main.rs 也通过库使用生成的模块
+        let mut lib = toml::Table::new();
+        lib.insert("name".into(), Self::lib_name(project).into());
+        lib.insert("path".into(), "src/lib.rs".into());
+        manifest.insert("lib".into(), lib.into());
+        if let BuildTarget::Bin = world.build.target {
+            let mut bin = toml::Table::new();
+            bin.insert("name".into(), world.crate_name().into());
+            bin.insert("path".into(), "src/main.rs".into());
+            manifest.insert("bin".into(), toml::Value::Array(vec![bin.into()]));
+        }
+        // 生成的项目不属于任何上层工作区
+        manifest.insert("workspace".into(), toml::Table::new().into());
+        manifest
+    }
 
-        let main_rs_path = project_path.join("src").join("main.rs");
-        fs::write(main_rs_path, format_rust_code(main_rs_content).unwrap())?;
+    ///库名，crate 名转为蛇形
+    fn lib_name(project: &AethaumProject) -> String {
+        project.world.crate_name().to_case(Case::Snake)
+    }
+
+    fn generate_source_files(project_path: &Path, project: &AethaumProject) -> Result<(), TemplateGenerationError> {
+        match project.world.build.target {
+            BuildTarget::Bin => Self::generate_main_rs(project_path, project)?,
+            BuildTarget::Lib => {
+                // 库目标不需要 main.rs，删除之前以可执行目标生成的文件
+                let main_rs_path = project_path.join("src").join("main.rs");
+                if main_rs_path.exists() {
+                    fs::remove_file(main_rs_path)?;
+                }
+            }
+        }
 
         // 生成 lib.rs
         let lib_rs_content = quote! {
@@ -161,38 +200,94 @@ thiserror = "2.0.16"
         };
 
         let lib_rs_path = project_path.join("src").join("lib.rs");
-        fs::write(lib_rs_path, format_rust_code(lib_rs_content).unwrap())?;
+        write_if_changed(&lib_rs_path, &format_rust_code(lib_rs_content).unwrap())?;
 
         let src_aethaum_predefined_path = project_path.join("src").join("aethaum_predefined.rs");
         let predefined = aethaum_predefined::predefined();
-        fs::write(src_aethaum_predefined_path, format_rust_code(predefined).unwrap())?;
+        write_if_changed(&src_aethaum_predefined_path, &format_rust_code(predefined).unwrap())?;
 
         // 生成 modules.rs
         let mut module_declarations = Vec::new();
+        let mut plugin_registrations = Vec::new();
         for module in project.module_tree.get_modules() {
             let module_name = proc_macro2::Ident::new(module.name.as_str(), proc_macro2::Span::call_site());
+            let plugin_name = transpile::module_plugin_ident(module.name.as_str());
             module_declarations.push(quote! {
                 pub mod #module_name;
             });
+            plugin_registrations.push(quote! {
+                app.add_plugins(#module_name::#plugin_name);
+            });
         }
 
         let modules_content = quote! {
             //! Aethaum modules
             #(#module_declarations)*
+
+            ///注册所有模块的插件
+            pub struct AethaumModulesPlugin;
+            impl bevy_app::Plugin for AethaumModulesPlugin {
+                fn build(&self, app: &mut bevy_app::App) {
+                    #(#plugin_registrations)*
+                }
+            }
         };
 
         let modules_path = project_path.join("src").join("modules.rs");
-        fs::write(modules_path, format_rust_code(modules_content).unwrap())?;
+        write_if_changed(&modules_path, &format_rust_code(modules_content).unwrap())?;
 
 
         // 生成 lua_bindings.rs
-        let lua_bindings_content = quote! {
-            //! Lua bindings
-            //! Auto-generated by Aethaum
-        };
+        let lua_bindings_content = lua_binding::lua_bindings();
 
         let lua_bindings_path = project_path.join("src").join("lua_bindings.rs");
-        fs::write(lua_bindings_path, format_rust_code(lua_bindings_content).unwrap())?;
+        write_if_changed(&lua_bindings_path, &format_rust_code(lua_bindings_content).unwrap())?;
+
+        Ok(())
+    }
+
+    fn generate_main_rs(project_path: &Path, project: &AethaumProject) -> Result<(), TemplateGenerationError> {
+        // 生成 main.rs - 添加插件注册，按 config/config.toml 中的 tick_rate 循环运行
+        let lib_name = proc_macro2::Ident::new(&Self::lib_name(project), proc_macro2::Span::call_site());
+        let main_rs_content = quote! {
+            use std::time::Duration;
+            use bevy_app::prelude::*;
+            use bevy_app::ScheduleRunnerPlugin;
+            use #lib_name::{aethaum_predefined, lua_bindings, modules};
+
+            ///config/config.toml 中 [engine] 的 tick_rate，文件或字段不存在、或不是正数时为60
+            fn tick_rate() -> f64 {
+                const DEFAULT_TICK_RATE: f64 = 60.0;
+                let config = aethaum_predefined::load_config();
+                let tick_rate = match config.get("engine").and_then(|engine| engine.get("tick_rate")) {
+                    Some(toml::Value::Float(tick_rate)) => Some(*tick_rate),
+                    Some(toml::Value::Integer(tick_rate)) => Some(*tick_rate as f64),
+                    Some(_) => None,
+                    None => return DEFAULT_TICK_RATE,
+                };
+                match tick_rate {
+                    Some(tick_rate) if tick_rate.is_finite() && tick_rate > 0.0 => tick_rate,
+                    _ => {
+                        eprintln!(
+                            "[aethaum] tick_rate in {} must be a positive number; using the default {}",
+                            aethaum_predefined::CONFIG_FILE, DEFAULT_TICK_RATE
+                        );
+                        DEFAULT_TICK_RATE
+                    }
+                }
+            }
+
+            fn main() {
+                App::new()
+                    .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / tick_rate())))
+                    .add_plugins(lua_bindings::LuaPlugin)
+                    .add_plugins(modules::AethaumModulesPlugin)
+                    .run();
+            }
+        };
+
+        let main_rs_path = project_path.join("src").join("main.rs");
+        write_if_changed(&main_rs_path, &format_rust_code(main_rs_content).unwrap())?;
 
         Ok(())
     }
@@ -207,23 +302,6 @@ thiserror = "2.0.16"
 
         Ok(())
     }
-
-    fn generate_config_structure(project_path: &Path) -> Result<(), TemplateGenerationError> {
-        // 创建一个示例配置文件
-        let example_config_content = r#"# Aethaum Configuration File
-
-[engine]
-tick_rate = 60
-
-[logging]
-level = "info"
-        "#;
-
-        let config_path = project_path.join("config").join("config.toml");
-        fs::write(config_path, example_config_content)?;
-
-        Ok(())
-    }
 }
 #[cfg(test)]
 mod tests {
@@ -231,8 +309,85 @@ mod tests {
     use super::*;
     #[test]
     fn test_generate_project_template() {
-        let project_path = Path::new(r#"D:\Aethaum\test_project_template/test"#);
-        let project = ProjectLoader::new(r#"D:\Aethaum\test_project"#.into()).load().unwrap();
-        ProjectTemplateGenerator::generate(project_path, &project).unwrap();
+        let project_path = std::env::temp_dir().join("aethaum_test_project_template");
+        let _ = fs::remove_dir_all(&project_path);
+        let project = ProjectLoader::new(crate::test_utils::test_project_root()).load().unwrap();
+        ProjectTemplateGenerator::generate(&project_path, &project).unwrap();
+        // 生成的项目中没有不会被读取的配置文件
+        assert!(!project_path.join("config").exists());
+        let main_rs = fs::read_to_string(project_path.join("src").join("main.rs")).unwrap();
+        assert!(main_rs.contains(".add_plugins(lua_bindings::LuaPlugin)"));
+        assert!(main_rs.contains("ScheduleRunnerPlugin::run_loop"));
+        // main.rs 通过库使用生成的模块，不重复声明
+        assert!(main_rs.contains("use my_ai_world::{aethaum_predefined, lua_bindings, modules};"));
+        assert!(!main_rs.contains("mod modules;"));
+        let modules_rs = fs::read_to_string(project_path.join("src").join("modules.rs")).unwrap();
+        assert!(modules_rs.contains("app.add_plugins(combat::CombatPlugin);"));
+        let lua_bindings = fs::read_to_string(project_path.join("src").join("lua_bindings.rs")).unwrap();
+        assert!(lua_bindings.contains("pub struct LuaPlugin"));
+    }
+    #[test]
+    fn test_generate_library_target() {
+        let project_path = std::env::temp_dir().join("aethaum_test_project_template_lib");
+        let mut project = ProjectLoader::new(crate::test_utils::test_project_root()).load().unwrap();
+        project.world.build.target = BuildTarget::Bin;
+        ProjectTemplateGenerator::generate(&project_path, &project).unwrap();
+        assert!(project_path.join("src").join("main.rs").exists());
+
+        project.world.build.target = BuildTarget::Lib;
+        project.world.build.crate_name = Some("renamed_world".into());
+        ProjectTemplateGenerator::generate(&project_path, &project).unwrap();
+        let cargo_toml = fs::read_to_string(project_path.join("Cargo.toml")).unwrap();
+        assert!(cargo_toml.contains("name = \"renamed_world\""));
+        assert!(cargo_toml.contains("[lib]"));
+        assert!(!project_path.join("src").join("main.rs").exists());
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_cargo_manifest_merges_world_cargo() {
+        let mut project = ProjectLoader::new(crate::test_utils::test_project_root()).load().unwrap();
+        project.world.cargo.dependencies = toml::from_str(r#"
+            rand = "0.9"
+            serde = { version = "1.0", features = ["derive", "rc"] }
+        "#).unwrap();
+        project.world.cargo.profile = toml::from_str("release = { lto = true }").unwrap();
+        project.world.cargo.package = toml::from_str(r#"license = "MIT""#).unwrap();
+
+        let manifest = ProjectTemplateGenerator::cargo_manifest(&project);
+        assert_eq!(manifest["package"]["version"].as_str(), Some(project.world.normal.version.as_str()));
+        assert_eq!(manifest["package"]["authors"][0].as_str(), Some(project.world.normal.author.as_str()));
+        assert_eq!(manifest["package"]["license"].as_str(), Some("MIT"));
+        assert_eq!(manifest["dependencies"]["rand"].as_str(), Some("0.9"));
+        assert_eq!(manifest["dependencies"]["serde"]["features"][1].as_str(), Some("rc"));
+        assert!(manifest["dependencies"].get("bevy_ecs").is_some());
+        assert_eq!(manifest["profile"]["release"]["lto"].as_bool(), Some(true));
+        assert!(manifest.get("features").is_none());
+        // 可执行目标也生成库，库名为蛇形
+        assert_eq!(manifest["lib"]["name"].as_str(), Some("my_ai_world"));
+        assert_eq!(manifest["bin"][0]["name"].as_str(), Some(project.world.crate_name()));
+    }
+    #[test]
+    fn test_cargo_manifest_deep_merges_world_cargo() {
+        let mut project = ProjectLoader::new(crate::test_utils::test_project_root()).load().unwrap();
+        project.world.cargo.dependencies = toml::from_str(r#"
+            serde = { features = ["derive", "rc"] }
+            bevy_math = { features = ["serialize"] }
+            toml = { version = "0.9.5" }
+        "#).unwrap();
+        project.world.cargo.package = toml::from_str(r#"authors = ["Someone Else"]"#).unwrap();
+
+        let manifest = ProjectTemplateGenerator::cargo_manifest(&project);
+        // 只给出 features 时保留默认的版本，features 以给出的为准
+        let serde = &manifest["dependencies"]["serde"];
+        assert_eq!(serde["version"].as_str(), Some("1.0"));
+        let features = serde["features"].as_array().unwrap();
+        assert_eq!(features, &vec![toml::Value::from("derive"), toml::Value::from("rc")]);
+        let bevy_math = &manifest["dependencies"]["bevy_math"];
+        assert_eq!(bevy_math["default-features"].as_bool(), Some(false));
+        assert_eq!(bevy_math["features"].as_array().unwrap(), &vec![toml::Value::from("serialize")]);
+        // 表中给出的值替换默认值
+        assert_eq!(manifest["dependencies"]["toml"]["version"].as_str(), Some("0.9.5"));
+        let authors = manifest["package"]["authors"].as_array().unwrap();
+        assert_eq!(authors, &vec![toml::Value::from("Someone Else")]);
+        assert_eq!(manifest["bin"][0]["name"].as_str(), Some(project.world.crate_name()));
+    }
+}
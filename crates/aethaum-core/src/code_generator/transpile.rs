@@ -1,9 +1,8 @@
 use std::collections::HashSet;
-use std::fmt::format;
-use convert_case::Casing;
-use quote::quote;
-use crate::toml_parser::parsed::{Component, Describable, EntityProto, Event, Field, System, SystemEventHandler, SystemQuery, SystemUpdate};
-use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use convert_case::{Case, Casing};
+use crate::toml_parser::parsed::{color_components, vector_components, AethaumType, Component, ComponentRef, Describable, EntityProto, Event, EventRef, Field, LuaScript, PrimitiveType, System, SystemEventHandler, SystemQuery};
+use proc_macro2::{Literal, Span, TokenStream};
 use syn::Ident;
 use thiserror::Error;
 use crate::ecs::module::EcsModule;
@@ -35,12 +34,90 @@ where
 {
     fields.into_iter().map(|field| {
         let field_name = field.name_as_rust_ident();
-        let field_type = field.type_as_rust_ident();
+        let field_type = field.type_as_rust_type();
         quote! {
             pub #field_name: #field_type,
         }
     })
 }
+///将 TOML 中的默认值转换为字段类型的 Rust 表达式，数字不带后缀以适配各种宽度
+fn default_value_literal(type_spec: &AethaumType, default_value: &toml::Value) -> TokenStream {
+    match (type_spec, default_value) {
+        (AethaumType::Primitive(PrimitiveType::Vec2), value) if let Some(xy) = vector_components(value, 2) => {
+            quote! { Vec2::new(#(#xy),*) }
+        }
+        (AethaumType::Primitive(PrimitiveType::Vec3), value) if let Some(xyz) = vector_components(value, 3) => {
+            quote! { Vec3::new(#(#xyz),*) }
+        }
+        (AethaumType::Primitive(PrimitiveType::Color), value) if let Some(rgba) = color_components(value) => {
+            quote! { Color::srgba(#(#rgba),*) }
+        }
+        (AethaumType::List(element), toml::Value::Array(array)) => {
+            let elements = array.iter().map(|value| default_value_literal(element, value));
+            quote! { vec![#(#elements),*] }
+        }
+        (AethaumType::Array(element, _), toml::Value::Array(array)) => {
+            let elements = array.iter().map(|value| default_value_literal(element, value));
+            quote! { [#(#elements),*] }
+        }
+        (AethaumType::Map(element), toml::Value::Table(table)) => {
+            let keys = table.keys();
+            let values = table.values().map(|value| default_value_literal(element, value));
+            quote! { std::collections::HashMap::from([#((#keys.to_string(), #values)),*]) }
+        }
+        (AethaumType::Option(inner), value) => {
+            let inner = default_value_literal(inner, value);
+            quote! { Some(#inner) }
+        }
+        (_, toml::Value::Boolean(b)) => quote! { #b },
+        (_, toml::Value::Integer(i)) => Literal::i64_unsuffixed(*i).into_token_stream(),
+        (_, toml::Value::Float(f)) => Literal::f64_unsuffixed(*f).into_token_stream(),
+        (_, toml::Value::String(s)) => quote! { #s.to_string() },
+        // 其他类型需要进一步处理
+        _ => type_default(type_spec),
+    }
+}
+///没有默认值的字段使用类型的默认值，实体没有 Default，使用占位实体
+fn type_default(type_spec: &AethaumType) -> TokenStream {
+    match type_spec {
+        AethaumType::Primitive(PrimitiveType::Entity) => quote! { Entity::PLACEHOLDER },
+        // 数组的 Default 只支持32个以内的元素
+        AethaumType::Array(element, _) => {
+            let element = type_default(element);
+            quote! { std::array::from_fn(|_| #element) }
+        }
+        _ => quote! { Default::default() },
+    }
+}
+///从Lua表读取字段时的 lua 参数，没有字段时不使用，以 _lua 命名避免生成的代码产生警告
+fn lua_param(field_keys: &[&str]) -> Ident {
+    let name = if field_keys.is_empty() { "_lua" } else { "lua" };
+    Ident::new(name, Span::call_site())
+}
+///模块插件名，模块名转为大驼峰
+pub(crate) fn module_plugin_ident(module_name: &str) -> Ident {
+    Ident::new(&format!("{}Plugin", module_name.to_case(Case::Pascal)), Span::call_site())
+}
+///同一组组件中有同名组件(如 `Health` 与 `combat::Health`)时，写明模块的组件需要以模块名区分
+fn is_ambiguous<'a>(component_ref: &ComponentRef, component_refs: impl IntoIterator<Item = &'a ComponentRef>) -> bool {
+    component_refs.into_iter().filter(|other| other.name == component_ref.name).count() > 1
+}
+///组件在Lua表中的键，ambiguous 时带上写明的模块名，如 `combat::Health`
+fn qualified_key(component_ref: &ComponentRef, name: &str, ambiguous: bool) -> String {
+    match &component_ref.module_name {
+        Some(module_name) if ambiguous => format!("{}::{}", module_name, name),
+        _ => name.to_string(),
+    }
+}
+///原型 Bundle 中组件的字段名，组件名转为蛇形，ambiguous 时带上写明的模块名，如 `combat_health`
+fn bundle_field_ident(component_ref: &ComponentRef, ambiguous: bool) -> Ident {
+    let name = component_ref.name.to_case(Case::Snake);
+    let field_name = match &component_ref.module_name {
+        Some(module_name) if ambiguous => format!("{}_{}", module_name.to_case(Case::Snake), name),
+        _ => name,
+    };
+    Ident::new(&field_name, Span::call_site())
+}
 fn transpile_descriptions<T: Describable>(to_transpile: &T, name: &str) -> TokenStream {
     let struct_desc = to_transpile.description()
         .map(|d| {
@@ -56,17 +133,22 @@ fn transpile_descriptions<T: Describable>(to_transpile: &T, name: &str) -> Token
         }).collect::<Vec<_>>();
 
         if !field_matches.is_empty() {
-            quote! {
+            Some(quote! {
                     match field_name {
                         #(#field_matches)*
                         _ => "",
                     }
-                }
+                })
         } else {
-            quote! { "" }
+            None
         }
     } else {
-        quote! { "" }
+        None
+    };
+    // 没有字段描述时不使用参数，以 _field_name 命名避免生成的代码产生警告
+    let (field_name_param, field_desc_impl) = match field_desc_impl {
+        Some(field_desc_impl) => (quote! { field_name }, field_desc_impl),
+        None => (quote! { _field_name }, quote! { "" }),
     };
 
     let name = Ident::new(name, Span::call_site());
@@ -77,7 +159,7 @@ fn transpile_descriptions<T: Describable>(to_transpile: &T, name: &str) -> Token
                 #struct_desc
             }
 
-            fn describe_field(&self, field_name: &str) -> &'static str {
+            fn describe_field(&self, #field_name_param: &str) -> &'static str {
                 #field_desc_impl
             }
         }
@@ -86,49 +168,59 @@ fn transpile_descriptions<T: Describable>(to_transpile: &T, name: &str) -> Token
 impl Transpile for Component {
     fn transpile(&self) -> Result<TokenStream, TranspileError> {
         let name = Ident::new(self.name.as_str(), Span::call_site());
+        let name_str = self.name.as_str();
         let fields = if let Some(fields) = &self.fields {
             transpile_fields(fields).collect()
         } else {
             vec![]
         };
-        // 生成 Default 实现（如果有默认值的话）
-        let default_impl = if let Some(fields) = &self.fields {
-            if fields.iter().any(|f| f.default_value.is_some()) {
-                let default_fields = fields.iter().map(|field| {
-                    let field_name = Ident::new(field.name.as_str(), Span::call_site());
-                    if let Some(default_value) = &field.default_value {
-                        // 将 TOML 值转换为 Rust 字面量
-                        let default_literal = match default_value {
-                            toml::Value::Boolean(b) => quote! { #b },
-                            toml::Value::Integer(i) => quote! { #i },
-                            toml::Value::Float(f) => quote! { #f },
-                            toml::Value::String(s) => quote! { #s },
-                            // 其他类型需要进一步处理
-                            _ => quote! { Default::default() },
-                        };
-                        quote! { #field_name: #default_literal }
-                    } else {
-                        quote! { #field_name: Default::default() }
-                    }
-                }).collect::<Vec<_>>();
-
-                quote! {
-                    impl Default for #name {
-                        fn default() -> Self {
-                            Self {
-                                #(#default_fields),*
-                            }
-                        }
+        // 生成 Default 实现，没有默认值的字段使用类型的默认值
+        let default_fields = self.fields.iter().flatten().map(|field| {
+            let field_name = Ident::new(field.name.as_str(), Span::call_site());
+            if let Some(default_value) = &field.default_value {
+                let default_literal = default_value_literal(&field.type_spec, default_value);
+                quote! { #field_name: #default_literal }
+            } else {
+                let default_literal = type_default(&field.type_spec);
+                quote! { #field_name: #default_literal }
+            }
+        }).collect::<Vec<_>>();
+        let default_impl = quote! {
+            impl Default for #name {
+                fn default() -> Self {
+                    Self {
+                        #(#default_fields),*
                     }
                 }
-            } else {
-                quote! {}
             }
-        } else {
-            quote! {}
         };
         //生成Describe trait
         let description_impl = transpile_descriptions(self,self.name.as_str());
+        //生成与Lua表的转换
+        let field_names = self.fields.iter().flatten()
+            .map(|field| field.name_as_rust_ident())
+            .collect::<Vec<_>>();
+        let field_keys = self.fields.iter().flatten()
+            .map(|field| field.name.as_str())
+            .collect::<Vec<_>>();
+        let owner = format!("component {}", self.name);
+        let lua_param = lua_param(&field_keys);
+        // 表中缺少的字段使用默认值，没有默认值的字段使用类型的默认值
+        let fields_from_lua = self.fields.iter().flatten()
+            .map(|field| {
+                let key = field.name.as_str();
+                let default_literal = match &field.default_value {
+                    Some(default_value) => default_value_literal(&field.type_spec, default_value),
+                    None => type_default(&field.type_spec),
+                };
+                quote! {
+                    match table.get::<LuaValue>(#key)? {
+                        LuaNil => #default_literal,
+                        _ => lua_table_field(table, #key, #owner, lua)?,
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
 
         Ok(quote! {
             #[derive(Component, Reflect)]
@@ -139,6 +231,48 @@ impl Transpile for Component {
             #default_impl
 
             #description_impl
+
+            impl LuaComponent for #name {
+                fn to_lua_table(&self, lua: &Lua) -> LuaResult<LuaTable> {
+                    let table = lua.create_table()?;
+                    #(
+                        table.set(#field_keys, self.#field_names.to_lua_value(lua)?)?;
+                    )*
+                    Ok(table)
+                }
+                fn apply_lua_table(&mut self, table: &LuaTable, #lua_param: &Lua) -> LuaResult<()> {
+                    check_lua_table_keys(table, &[#(#field_keys),*], #owner)?;
+                    #(
+                        self.#field_names = lua_table_field(table, #field_keys, #owner, lua)?;
+                    )*
+                    Ok(())
+                }
+                fn from_lua_table(table: &LuaTable, #lua_param: &Lua) -> LuaResult<Self> {
+                    check_lua_table_keys(table, &[#(#field_keys),*], #owner)?;
+                    Ok(Self {
+                        #(#field_names: #fields_from_lua,)*
+                    })
+                }
+            }
+
+            impl ToLua for #name {
+                fn to_lua(&self, lua: &Lua) -> LuaResult<LuaValue> {
+                    self.to_lua_table(lua).map(LuaValue::Table)
+                }
+            }
+
+            impl FromLua for #name {
+                fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                    match value {
+                        LuaValue::Table(table) => Self::from_lua_table(&table, lua),
+                        value => Err(LuaError::FromLuaConversionError {
+                            from: value.type_name(),
+                            to: #name_str.to_string(),
+                            message: Some("expected a table of component fields".to_string()),
+                        }),
+                    }
+                }
+            }
         })
     }
 }
@@ -151,15 +285,39 @@ impl Transpile for Event {
             vec![]
         };
         let description_impl = transpile_descriptions(self, self.name.as_str());
+        let field_names = self.fields.iter().flatten()
+            .map(|field| field.name_as_rust_ident())
+            .collect::<Vec<_>>();
+        let field_keys = self.fields.iter().flatten()
+            .map(|field| field.name.as_str())
+            .collect::<Vec<_>>();
+        let owner = format!("event {}", self.name);
+        let lua_param = lua_param(&field_keys);
 
         Ok(
             quote! {
-                #[derive(Event)]
+                #[derive(Event, Reflect)]
                 pub struct #name {
                     #(#fields)*
                 }
 
                 #description_impl
+
+                impl LuaEvent for #name {
+                    fn to_lua_table(&self, lua: &Lua) -> LuaResult<LuaTable> {
+                        let table = lua.create_table()?;
+                        #(
+                            table.set(#field_keys, self.#field_names.to_lua_value(lua)?)?;
+                        )*
+                        Ok(table)
+                    }
+                    fn from_lua_table(table: &LuaTable, #lua_param: &Lua) -> LuaResult<Self> {
+                        check_lua_table_keys(table, &[#(#field_keys),*], #owner)?;
+                        Ok(Self {
+                            #(#field_names: lua_table_field(table, #field_keys, #owner, lua)?,)*
+                        })
+                    }
+                }
             }
         )
     }
@@ -168,22 +326,22 @@ impl Transpile for EntityProto {
     fn transpile(&self) -> Result<TokenStream, TranspileError> {
         let name = Ident::new(self.name.as_str(), Span::call_site());
         let bundle_name = Ident::new(&format!("{}Bundle", self.name), Span::call_site());
-        let spawn_system_name = Ident::new(&format!("spawn_{}_system", self.name.to_lowercase()), Span::call_site());
 
         // 生成 Bundle 字段
         let mut errors = Vec::new();
 
+        let ambiguous = |component_ref: &ComponentRef| is_ambiguous(component_ref, self.components.iter().map(|other| &**other));
         let bundle_fields = self.components.iter().map(|component_ref| {
-            let component_name = Ident::new(component_ref.name.as_str(), Span::call_site());
+            let field_name = bundle_field_ident(component_ref, ambiguous(component_ref));
             let component_type_str = match &component_ref.module_name {
-                Some(module_name) => format!("{}::components::{}", module_name, component_name),
-                None => format!("components::{}", component_name),
+                Some(module_name) => format!("{}::components::{}", module_name, component_ref.name),
+                None => format!("components::{}", component_ref.name),
             };
             let component_type = syn::parse_str::<syn::Type>(&component_type_str);
             match component_type {
                 Ok(component_type) => {
                     quote! {
-                        #component_name: #component_type,
+                        #field_name: #component_type,
                     }
                 },
                 Err(err) => {
@@ -204,13 +362,44 @@ impl Transpile for EntityProto {
         
         // 生成描述实现
         let description_impl = transpile_descriptions(self, self.name.as_str());
+        //从Lua表生成：以组件名为键，给出的组件覆盖原型的默认值，缺少的字段使用组件的默认值
+        let component_fields = self.components.iter()
+            .map(|component_ref| bundle_field_ident(component_ref, ambiguous(component_ref)))
+            .collect::<Vec<_>>();
+        let component_keys = self.components.iter()
+            .map(|component_ref| qualified_key(component_ref, &component_ref.name, ambiguous(component_ref)))
+            .collect::<Vec<_>>();
+        let owner = format!("entity prototype {}", self.name);
+        let bundle_name_str = bundle_name.to_string();
 
         Ok(quote! {
-            #[derive(Bundle, Default)]
+            #[derive(Bundle, Default, Reflect)]
             pub struct #bundle_name {
                 #(#bundle_fields)*
             }
 
+            impl FromLua for #bundle_name {
+                fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+                    let table = match value {
+                        LuaValue::Table(table) => table,
+                        LuaNil => return Ok(Self::default()),
+                        value => return Err(LuaError::FromLuaConversionError {
+                            from: value.type_name(),
+                            to: #bundle_name_str.to_string(),
+                            message: Some("expected a table of components".to_string()),
+                        }),
+                    };
+                    check_lua_table_keys(&table, &[#(#component_keys),*], #owner)?;
+                    let mut bundle = Self::default();
+                    #(
+                        if let Some(component) = table.get::<Option<LuaTable>>(#component_keys)? {
+                            bundle.#component_fields = LuaComponent::from_lua_table(&component, lua)?;
+                        }
+                    )*
+                    Ok(bundle)
+                }
+            }
+
             pub struct #name;
 
             impl #name {
@@ -223,24 +412,6 @@ impl Transpile for EntityProto {
                 }
             }
 
-            // 为这个原型生成对应的处理系统
-            pub fn #spawn_system_name(
-                mut events: EventReader<AethaumSpawnEntity>,
-                mut spawn_responses: EventWriter<AethaumSpawnEntityResponse>,
-                mut commands: Commands,
-            ) {
-                for event in events.read() {
-                    if event.prototype_name == stringify!(#name) {
-                        let entity = #name::spawn(&mut commands);
-                        // 发送响应事件
-                        spawn_responses.send(AethaumSpawnEntityResponse {
-                            request_id: event.request_id,
-                            entity,
-                        });
-                    }
-                }
-            }
-
             #description_impl
         })
     }
@@ -289,80 +460,262 @@ impl Transpile for SystemQuery {
         }
     }
 }
+impl SystemQuery {
+    fn component_type(component_ref: &ComponentRef) -> Result<syn::Type, TranspileError> {
+        let component_module_path = match &component_ref.module_name {
+            Some(module_name) => format!("{}::components::{}", module_name, component_ref.name),
+            None => format!("components::{}", component_ref.name),
+        };
+        Ok(syn::parse_str::<syn::Type>(&component_module_path)?)
+    }
+    ///独占系统中使用的 QueryState，过滤条件与 Query 相同
+    fn transpile_query_state(&self) -> Result<TokenStream, TranspileError> {
+        let query = self.transpile()?;
+        let query: syn::Type = syn::parse2(query)?;
+        let syn::Type::Path(mut query) = query else {
+            unreachable!("SystemQuery always transpiles to Query<...>")
+        };
+        //ROBUST: Query<...> 必然只有一段路径
+        query.path.segments[0].ident = Ident::new("QueryState", Span::call_site());
+        Ok(quote! { #query })
+    }
+}
+///entity 表中可访问的组件：系统各查询包含的组件，实体拥有的才会出现在表中
+fn transpile_component_bindings(system: &System) -> Result<TokenStream, TranspileError> {
+    let mut recorded = HashSet::new();
+    let component_refs = system.queries.iter()
+        .flat_map(|query| query.component_constraint.get_include().into_iter().flatten())
+        .filter(|component_ref| recorded.insert(*component_ref))
+        .collect::<Vec<_>>();
+    // 与实体视图相同，同名组件的键带上模块名，如 `combat::health`
+    let bindings = component_refs.iter()
+        .map(|component_ref| {
+            let component_type = SystemQuery::component_type(component_ref)?;
+            let ambiguous = is_ambiguous(component_ref, component_refs.iter().map(|other| &***other));
+            let key = qualified_key(component_ref, &component_ref.name.to_case(Case::Snake), ambiguous);
+            Ok(quote! { ComponentBinding::of::<#component_type>(#key) })
+        })
+        .collect::<Result<Vec<_>, TranspileError>>()?;
+    Ok(quote! { #(#bindings),* })
+}
+fn event_type(event_ref: &EventRef) -> Result<syn::Type, TranspileError> {
+    let event_module_path = match &event_ref.module_name {
+        Some(module_name) => format!("{}::events::{}", module_name, event_ref.name),
+        None => format!("events::{}", event_ref.name),
+    };
+    Ok(syn::parse_str::<syn::Type>(&event_module_path)?)
+}
+///事件处理函数名包含事件所在模块与处理器序号，同一系统中同一事件的多个处理器、不同模块中的同名事件不会冲突
+fn event_handler_ident(system: &System, index: usize, event_handler: &SystemEventHandler) -> Ident {
+    let watch_for = &event_handler.watch_for;
+    let module = watch_for.module_name.as_ref()
+        .map(|module_name| format!("{}_", module_name))
+        .unwrap_or_default();
+    Ident::new(
+        &format!("{}_on_{}{}_{}",
+                 system.normal.name.to_lowercase(),
+                 module.to_lowercase(),
+                 watch_for.name.as_str().to_lowercase(),
+                 index),
+        Span::call_site()
+    )
+}
+///事件处理器在脚本键与报错中的名字，同一事件有多个处理器时附上从1开始的序号
+fn event_handler_section(system: &System, index: usize, event_handler: &SystemEventHandler) -> String {
+    let section = format!("on {}", *event_handler.watch_for);
+    let duplicated = system.event_handlers.iter()
+        .filter(|other| *other.watch_for == *event_handler.watch_for)
+        .count() > 1;
+    if duplicated { format!("{} #{}", section, index + 1) } else { section }
+}
+///有 [update] 且给出了 logic 的系统才会生成 update 函数
+fn update_logic(system: &System) -> Option<&LuaScript> {
+    system.update.as_ref().and_then(|update| update.logic.as_ref())
+}
+///内嵌脚本以定义它的TOML文件命名，并记录起始行，使Lua报错指向TOML中的位置；不知道来源时使用 chunk_name
+fn transpile_lua_source(system: &System, script: &LuaScript, chunk_name: &str) -> TokenStream {
+    match script {
+        LuaScript::Embed(embed) => {
+            let source = embed.source.as_str();
+            let name = system.source.as_ref()
+                .map_or_else(|| chunk_name.to_string(), |source| source.path.to_string_lossy().to_string());
+            let line = system.script_line(script).unwrap_or(1) as u32;
+            quote! { LuaSource::Embed { name: #name, source: #source, line: #line } }
+        },
+        LuaScript::File(path) => {
+            let path = path.to_string_lossy().to_string();
+            quote! { LuaSource::File { path: #path } }
+        }
+    }
+}
+///脚本在 LuaRuntime 中的键，以及引用它的常量
+fn lua_script_key(system: &System, handler: &str, part: &str) -> (Ident, TokenStream) {
+    // 处理器名中可能有 `::` 与 `#`，只保留字母与数字
+    let handler_words = handler.replace(|c: char| !c.is_alphanumeric(), " ");
+    let const_name = format!("{}_{}", handler_words.to_case(Case::UpperSnake), part.to_case(Case::UpperSnake));
+    let const_ident = Ident::new(&const_name, Span::call_site());
+    let system_name = system.normal.name.as_str();
+    let part = Ident::new(part, Span::call_site());
+    let key = quote! {
+        LuaScriptKey { module: MODULE_NAME, system: #system_name, handler: #handler, part: LuaScriptPart::#part }
+    };
+    (const_ident, key)
+}
 impl Transpile for System {
     fn transpile(&self) -> Result<TokenStream, TranspileError> {
         let system_name = Ident::new(self.normal.name.as_str(), Span::call_site());
+        // 系统中的所有脚本，在插件中登记并于启动时编译
+        let mut scripts = Vec::new();
+        let mut script_keys = Vec::new();
 
-        // 生成查询参数
-        let queries = self.queries.iter()
-            .map(|query| query.transpile().unwrap())
-            .collect::<Vec<_>>();
-
+        // 生成查询参数，Lua系统均为独占系统，查询以 QueryState 的形式传入
+        let query_states = self.queries.iter()
+            .map(SystemQuery::transpile_query_state)
+            .collect::<Result<Vec<_>, _>>()?;
         let query_params = self.queries.iter()
             .map(|s_query| Ident::new(s_query.name.as_str(), Span::call_site()))
             .collect::<Vec<_>>();
+        let bindings = transpile_component_bindings(self)?;
 
         // 生成 update 系统（如果存在）
-        let update_system = if let Some(update) = &self.update {
-            let update_system_name = Ident::new("update", Span::call_site());
-            quote! {
-                pub fn #update_system_name(
-                    mut commands: Commands,
-                    #(#query_params: #queries,)*
-                ) {
-                    // Update logic would go here
+        let update_system = match update_logic(self) {
+            Some(logic) => {
+                let system_name_str = self.normal.name.as_str();
+                let update = self.update.as_ref().unwrap(); //ROBUST: update_logic 非空时 update 必然存在
+                let source = transpile_lua_source(self, logic, &format!("{}.update.logic", system_name_str));
+                let (logic_key, key) = lua_script_key(self, "update", "Logic");
+                scripts.push(quote! { (Self::#logic_key, #source) });
+                script_keys.push(quote! { const #logic_key: LuaScriptKey = #key; });
+                let (condition, report_error) = match &update.condition {
+                    Some(condition) => {
+                        let source = transpile_lua_source(self, condition, &format!("{}.update.condition", system_name_str));
+                        let (key_const, key) = lua_script_key(self, "update", "Condition");
+                        scripts.push(quote! { (Self::#key_const, #source) });
+                        script_keys.push(quote! { const #key_const: LuaScriptKey = #key; });
+                        // 条件编译失败时不执行，避免无条件地运行 logic
+                        let condition = quote! {
+                            let Some(condition) = runtime.script(&Self::#key_const) else {
+                                return;
+                            };
+                            let condition = Some(condition);
+                        };
+                        // 条件中的错误报告在条件自己的键下
+                        let report_error = quote! {
+                            match err.downcast_ref::<LuaConditionError>() {
+                                Some(LuaConditionError(err)) => report_lua_error(&Self::#key_const, err),
+                                None => report_lua_error(&Self::#logic_key, &err),
+                            }
+                        };
+                        (condition, report_error)
+                    },
+                    None => (quote! { let condition = None; }, quote! { report_lua_error(&Self::#logic_key, &err); }),
+                };
+                let interval_nanos = update.interval.as_nanos() as u64;
+                quote! {
+                    ///[update] 的执行间隔
+                    pub const UPDATE_INTERVAL: std::time::Duration = std::time::Duration::from_nanos(#interval_nanos);
+                    ///每经过一个 UPDATE_INTERVAL，对每个查询匹配的实体执行一次 [update] 中的Lua逻辑
+                    pub fn update(
+                        world: &mut World,
+                        mut accumulated: Local<std::time::Duration>,
+                        #(#query_params: &mut #query_states,)*
+                    ) {
+                        let delta = world.resource::<bevy_time::Time>().delta();
+                        let runs = fixed_timestep_runs(&mut accumulated, delta, Self::UPDATE_INTERVAL);
+                        if runs == 0 {
+                            return;
+                        }
+                        world.resource_scope(|world, runtime: Mut<LuaRuntime>| {
+                            let lua = &runtime.lua;
+                            // 编译失败的脚本已在启动时报告
+                            let Some(logic) = runtime.script(&Self::#logic_key) else {
+                                return;
+                            };
+                            #condition
+                            let result = with_ecs(world, lua, |world| {
+                                let bindings: &[ComponentBinding] = &[#bindings];
+                                for _ in 0..runs {
+                                    #(
+                                        for entity in query_entities(world, #query_params) {
+                                            run_lua_for_entity(world, lua, entity, bindings, condition, logic)?;
+                                        }
+                                    )*
+                                }
+                                Ok(())
+                            });
+                            if let Err(err) = result {
+                                #report_error
+                            }
+                        });
+                    }
                 }
-            }
-        } else {
-            quote! {}
+            },
+            None => quote! {},
         };
-        let mut errors = Vec::new();
-
         // 生成事件处理系统
         let event_handler_systems = self.event_handlers.iter()
-            .map(|event_handler| {
-                let handler_system_name = Ident::new(
-                    &format!("{}_on_{}",
-                             self.normal.name.to_lowercase(),
-                             event_handler.watch_for.name.as_str().to_lowercase()),
-                    Span::call_site()
-                );
-                let event_module_path = match &event_handler.watch_for.module_name {
-                    Some(module_name) => format!("{}::events::{}", module_name, event_handler.watch_for),
-                    None => format!("events::{}", event_handler.watch_for),
+            .enumerate()
+            .filter_map(|(index, event_handler)| event_handler.logic.as_ref().map(|logic| (index, event_handler, logic)))
+            .map(|(index, event_handler, logic)| {
+                let handler_system_name = event_handler_ident(self, index, event_handler);
+                let event_type = event_type(&event_handler.watch_for)?;
+                let section = event_handler_section(self, index, event_handler);
+                let source = transpile_lua_source(self, logic, &format!("{}.{}", self.normal.name, section));
+                let (logic_key, key) = lua_script_key(self, &section, "Logic");
+                scripts.push(quote! { (Self::#logic_key, #source) });
+                script_keys.push(quote! { const #logic_key: LuaScriptKey = #key; });
+                // 没有查询的系统，每个事件只执行一次，entity 为 nil
+                let run_logic = if self.queries.is_empty() {
+                    quote! {
+                        lua.globals().set("entity", LuaNil)?;
+                        call_lua_script::<()>(lua, logic)?;
+                    }
+                } else {
+                    quote! {
+                        #(
+                            for entity in query_entities(world, #query_params) {
+                                run_lua_for_entity(world, lua, entity, bindings, None, logic)?;
+                            }
+                        )*
+                    }
                 };
-                let event_type = syn::parse_str::<syn::Type>(&event_module_path);
-                match event_type {
-                    Ok(event_type) => {
-                        quote! {
-                            pub fn #handler_system_name(
-                                mut commands: Commands,
-                                mut event_reader: EventReader<#event_type>,
-                                #(#query_params: #queries,)*
-                            ) {
-                                for event in event_reader.read() {
-                                    // Event handling logic would go here
+                Ok(quote! {
+                    ///对每个事件，以 event 表绑定事件字段，对每个查询匹配的实体执行处理器的Lua逻辑
+                    pub fn #handler_system_name(
+                        world: &mut World,
+                        event_reader: &mut bevy_ecs::system::SystemState<EventReader<#event_type>>,
+                        #(#query_params: &mut #query_states,)*
+                    ) {
+                        world.resource_scope(|world, runtime: Mut<LuaRuntime>| {
+                            let lua = &runtime.lua;
+                            let result = (|| -> LuaResult<()> {
+                                let events = event_reader.get_mut(world).read()
+                                    .map(|event| event.to_lua_table(lua))
+                                    .collect::<LuaResult<Vec<_>>>()?;
+                                if events.is_empty() {
+                                    return Ok(());
                                 }
+                                // 编译失败的脚本已在启动时报告，事件已读取，不会积压
+                                let Some(logic) = runtime.script(&Self::#logic_key) else {
+                                    return Ok(());
+                                };
+                                let bindings: &[ComponentBinding] = &[#bindings];
+                                with_ecs(world, lua, |world| {
+                                    for event in events {
+                                        lua.globals().set("event", event)?;
+                                        #run_logic
+                                    }
+                                    Ok(())
+                                })
+                            })();
+                            if let Err(err) = result {
+                                report_lua_error(&Self::#logic_key, &err);
                             }
-                        }
-                    },
-                    Err(err) => {
-                        errors.push(
-                            TranspileError::FormatError(err)
-                        );
-                        quote! {}
+                        });
                     }
-                }
+                })
             })
-            .collect::<Vec<_>>();
-
-        if !errors.is_empty() {
-            if errors.len() == 1 {
-                return Err(errors.pop().unwrap()); //ROBUST: must have one element
-            } else {
-                return Err(TranspileError::Multiple{errors});
-            }
-        }
+            .collect::<Result<Vec<_>, TranspileError>>()?;
 
         let description_impl = transpile_descriptions(self, self.normal.name.as_str());
 
@@ -370,6 +723,10 @@ impl Transpile for System {
             pub struct #system_name;
 
             impl #system_name {
+                #(#script_keys)*
+                ///系统中的脚本，由模块插件登记到 LuaRuntime
+                pub const SCRIPTS: &[(LuaScriptKey, LuaSource)] = &[#(#scripts),*];
+
                 #update_system
 
                 #(#event_handler_systems)*
@@ -388,13 +745,15 @@ impl Transpile for EcsModule {
             components.iter()
                 .map(|component| {
                     let component_name = Ident::new(component.name.as_str(), Span::call_site());
-                    (component.transpile().unwrap(),quote! {components::#component_name})
+                    let component_key = component.name.to_case(Case::Snake);
+                    (component.transpile().unwrap(),(quote! {components::#component_name}, component_key))
                 })
                 .collect::<(Vec<_>,Vec<_>)>()
             //ROBUST: this transpile could never fail
         }else {
             (vec![],vec![])
         };
+        let (components_to_register, component_keys): (Vec<_>, Vec<_>) = components_to_register.into_iter().unzip();
         //events
         let (events_token, events_to_register) = if let Some(events) = &self.events {
             events.iter()
@@ -407,27 +766,24 @@ impl Transpile for EcsModule {
             (vec![],vec![])
         };
         //entity_protos
-        let (entity_proto_token,entity_protos_to_register) = if let Some(entity_protos) = &self.entity_protos {
+        let entity_proto_token = if let Some(entity_protos) = &self.entity_protos {
             entity_protos.iter()
                 .map(|entity_prototype| {
                     entity_prototype.components.iter()
                         .for_each(|component_ref| {
-                            if let Some(module_name) = &component_ref.module_name {
-                                if module_name.as_str() != self.name.as_str() && recorded_external_modules.insert(module_name.as_str()){
-                                    let extern_module = Ident::new(module_name.as_str(), Span::call_site());
-                                    external_module.push(
-                                        quote! {use crate::modules::#extern_module;}
-                                    );
-                                }
+                            if let Some(module_name) = &component_ref.module_name
+                                && module_name.as_str() != self.name.as_str() && recorded_external_modules.insert(module_name.as_str()){
+                                let extern_module = Ident::new(module_name.as_str(), Span::call_site());
+                                external_module.push(
+                                    quote! {use crate::modules::#extern_module;}
+                                );
                             }
                         });
-                    let spawn_entity_system = format!("spawn_{}_system", entity_prototype.name.to_lowercase());
-                    let spawn_entity_system = Ident::new(spawn_entity_system.as_str(), Span::call_site());
-                    (entity_prototype.transpile().unwrap(),quote! {entity_protos::#spawn_entity_system})
+                    entity_prototype.transpile().unwrap()
                 })
-                .collect::<(Vec<_>,Vec<_>)>()
+                .collect::<Vec<_>>()
         }else {
-            (vec![],vec![])
+            vec![]
         };
 
         //systems
@@ -440,28 +796,46 @@ impl Transpile for EcsModule {
                     .for_each(|query| {
                         query.component_constraint.chained_iter()
                             .for_each(|component_ref| {
-                                if let Some(module_name) = &component_ref.module_name {
-                                    if module_name.as_str() != self.name.as_str() && recorded_external_modules.insert(module_name.as_str()){
-                                        let extern_module = Ident::new(module_name.as_str(), Span::call_site());
-                                        external_module.push(
-                                            quote! {use crate::modules::#extern_module;}
-                                        );
-                                    }
+                                if let Some(module_name) = &component_ref.module_name
+                                    && module_name.as_str() != self.name.as_str() && recorded_external_modules.insert(module_name.as_str()){
+                                    let extern_module = Ident::new(module_name.as_str(), Span::call_site());
+                                    external_module.push(
+                                        quote! {use crate::modules::#extern_module;}
+                                    );
                                 }
                             })
                     });
+                    // 处理器监听的其他模块事件同样以 `module::events::Name` 引用
+                    system.event_handlers.iter()
+                        .filter_map(|event_handler| event_handler.watch_for.module_name.as_ref())
+                        .for_each(|module_name| {
+                            if module_name.as_str() != self.name.as_str() && recorded_external_modules.insert(module_name.as_str()) {
+                                let extern_module = Ident::new(module_name.as_str(), Span::call_site());
+                                external_module.push(
+                                    quote! {use crate::modules::#extern_module;}
+                                );
+                            }
+                        });
                     //record system names for bevy registering
                     let system_ident = Ident::new(system.normal.name.as_str(), Span::call_site());
-                    let update_system_ident = {
-
-                        quote! {systems::#system_ident::update}
-                    };
-                    systems_to_register.push(update_system_ident);
+                    let system_priority = system.priority();
+                    systems_to_register.push(quote! {
+                        app.world_mut().resource_mut::<LuaRuntime>().register_scripts(systems::#system_ident::SCRIPTS);
+                    });
+                    if update_logic(system).is_some() {
+                        systems_to_register.push(quote! {
+                            add_prioritized_systems(app, AethaumPriority::update(#system_priority), systems::#system_ident::update);
+                        });
+                    }
                     let system_event_handlers_ident = system.event_handlers.iter()
-                        .map(|event_handler| {
-                            let event_handler_str = format!("{}_on_{}", system.normal.name.as_str().to_lowercase(), event_handler.watch_for.name.as_str().to_lowercase());
-                            let system_event_handler_ident = Ident::new(event_handler_str.as_str(), Span::call_site());
-                            quote! {systems::#system_ident::#system_event_handler_ident}
+                        .enumerate()
+                        .filter(|(_, event_handler)| event_handler.logic.is_some())
+                        .map(|(index, event_handler)| {
+                            let system_event_handler_ident = event_handler_ident(system, index, event_handler);
+                            let handler_priority = event_handler.priority;
+                            quote! {
+                                add_prioritized_systems(app, AethaumPriority::handler(#system_priority, #handler_priority), systems::#system_ident::#system_event_handler_ident);
+                            }
                         });
                     systems_to_register.extend(system_event_handlers_ident);
                     //do the transpile
@@ -484,14 +858,18 @@ impl Transpile for EcsModule {
                 return Err(TranspileError::Multiple { errors});
             }
         }
-        let module_name = Ident::new(&self.name, Span::call_site());
-        let plugin_name = Ident::new(&format!("{}Plugin", self.name.as_str()), Span::call_site());
+        let plugin_name = module_plugin_ident(self.name.as_str());
+        let module_name_str = self.name.as_str();
 
         //Plugin registration tokens
         let components_registration = if !components_to_register.is_empty() {
             quote! {
                 #(
-                    app.register_type::<#components_to_register>();
+                    app.register_type::<#components_to_register>()
+                        .register_type_data::<#components_to_register, ReflectToLua>()
+                        .register_type_data::<#components_to_register, ReflectFromLua>();
+                    app.world_mut().resource_mut::<LuaEcsRegistry>().register_component::<#components_to_register>(#module_name_str, #component_keys);
+                    app.world_mut().resource_mut::<LuaReflectInitRegistry>().register_constructor::<#components_to_register>();
                 )*
             }
         }else {
@@ -501,58 +879,90 @@ impl Transpile for EcsModule {
             quote! {
                 #(
                     app.add_event::<#events_to_register>();
+                    app.register_type::<#events_to_register>();
+                    app.world_mut().resource_mut::<LuaEcsRegistry>().register_event::<#events_to_register>();
                 )*
             }
         }else {
             quote! {}
         };
-        let systems_registration = if !systems_to_register.is_empty() {
-            quote! {
-                app.add_systems(Update, (#(#systems_to_register),*));
-            }
-        }else {
-            quote! {}
+        let systems_registration = quote! {
+            #(#systems_to_register)*
         };
-        let entity_protos_registration = if !entity_protos_to_register.is_empty() {
+        let proto_bundles = self.entity_protos.iter().flatten()
+            .map(|entity_proto| {
+                let bundle_name = Ident::new(&format!("{}Bundle", entity_proto.name), Span::call_site());
+                quote! {entity_protos::#bundle_name}
+            })
+            .collect::<Vec<_>>();
+        let entity_protos_registration = if !proto_bundles.is_empty() {
             quote! {
                 #(
-                    app.add_systems(Update, #entity_protos_to_register);
+                    app.register_type::<#proto_bundles>()
+                        .register_type_data::<#proto_bundles, ReflectFromLua>();
+                    app.world_mut().resource_mut::<LuaEcsRegistry>().register_proto::<#proto_bundles>();
+                    app.world_mut().resource_mut::<LuaReflectInitRegistry>().register_constructor::<#proto_bundles>();
                 )*
             }
         }else {
             quote! {}
         };
 
+        // 空的子模块不引入父模块，避免未使用的导入警告
+        let submodule_prelude = |tokens: &[TokenStream]| if tokens.is_empty() {
+            quote! {}
+        } else {
+            quote! { use super::*; }
+        };
+        let components_prelude = submodule_prelude(&components_token);
+        let events_prelude = submodule_prelude(&events_token);
+        let entity_protos_prelude = submodule_prelude(&entity_proto_token);
+        let systems_prelude = submodule_prelude(&systems_token);
+
         Ok(
             quote! {
                 //! Auto-generated by Aethaum
                 use bevy_ecs::prelude::*;
-                use bevy_app::{Plugin, App, Update};
+                use bevy_app::{Plugin, App};
                 use bevy_reflect::Reflect;
+                use mlua::prelude::*;
                 use crate::aethaum_predefined::*;
+                use crate::lua_bindings::*;
                 #(#external_module)*
 
+                ///模块名，用于定位脚本所属的系统
+                pub const MODULE_NAME: &str = #module_name_str;
+
                 pub mod components {
-                    use super::*;
+                    #components_prelude
                     #(#components_token)*
                 }
 
                 pub mod events {
-                    use super::*;
+                    #events_prelude
                     #(#events_token)*
                 }
                 pub mod entity_protos {
-                    use super::*;
+                    #entity_protos_prelude
                     #(#entity_proto_token)*
                 }
                 pub mod systems {
-                    use super::*;
+                    #systems_prelude
                     #(#systems_token)*
                 }
                 pub struct #plugin_name;
 
                 impl Plugin for #plugin_name {
                     fn build(&self, app: &mut App) {
+                        // Lua运行时与 ecs 表所需的注册表由各模块插件共享
+                        if !app.is_plugin_added::<LuaPlugin>() {
+                            app.add_plugins(LuaPlugin);
+                        }
+                        // [update].interval 依赖 Time，各模块插件共享同一个 TimePlugin
+                        if !app.is_plugin_added::<bevy_time::TimePlugin>() {
+                            app.add_plugins(bevy_time::TimePlugin);
+                        }
+
                         // 注册组件
                         #components_registration
 
@@ -577,7 +987,8 @@ mod tests {
     use smart_string::SmartString;
     use crate::code_generator::utils::format_rust_code;
     use crate::ecs::loader::ModuleFileLoader;
-    use crate::toml_parser::parsed::{AethaumType, ComponentConstraint, ComponentField, ComponentRef, EventField, EventRef, PrimitiveType, SystemNormal};
+    use crate::toml_parser::parsed::{AethaumType, ComponentConstraint, ComponentField, ComponentRef, EventField, EventRef, PrimitiveType, SourceFile, SystemEventHandler, SystemNormal, SystemUpdate};
+    use crate::toml_parser::raw::RawSystem;
     use super::*;
     #[test]
     fn test_transpile_component() {
@@ -587,13 +998,13 @@ mod tests {
             fields: Some(vec![
                 ComponentField {
                     name: SmartString::from("test_field".to_string()),
-                    type_spec: AethaumType::Primitive(PrimitiveType::Bool),
-                    default_value: Some(toml::Value::Boolean(true)),
+                    type_spec: AethaumType::Primitive(PrimitiveType::Bool).into(),
+                    default_value: Some(toml::Value::Boolean(true).into()),
                     description: Some(SmartString::from("This is a test field".to_string())),
                 },
                 ComponentField {
                     name: SmartString::from("test_field2".to_string()),
-                    type_spec: AethaumType::Primitive(PrimitiveType::Int),
+                    type_spec: AethaumType::Primitive(PrimitiveType::Int).into(),
                     default_value: None,
                     description: None,
                 },
@@ -604,6 +1015,72 @@ mod tests {
         println!("{}", transpiled);
         let parsed_result = syn::parse_str::<syn::File>(&transpiled);
         assert!(parsed_result.is_ok(), "Generated code has syntax errors: {:?}", parsed_result.err());
+        // 从Lua表构造时，缺少的字段回退到默认值，没有默认值的字段使用类型的默认值
+        assert!(transpiled.contains("LuaNil => true"));
+        assert!(transpiled.contains("LuaNil => Default::default()"));
+        assert!(transpiled.contains("lua_table_field(table, \"test_field\", \"component TestComponent\", lua)?"));
+        // 表中不能有组件之外的字段
+        assert!(transpiled.contains("&[\"test_field\", \"test_field2\"],"));
+        assert!(transpiled.contains("impl ToLua for TestComponent"));
+        assert!(transpiled.contains("impl FromLua for TestComponent"));
+        assert!(transpiled.contains("impl Default for TestComponent"));
+    }
+    #[test]
+    fn test_transpile_extended_primitive_defaults() {
+        let field = |name: &str, type_spec: PrimitiveType, default_value: Option<toml::Value>| ComponentField {
+            name: name.into(),
+            type_spec: AethaumType::Primitive(type_spec).into(),
+            default_value: default_value.map(Into::into),
+            description: None,
+        };
+        let component = Component {
+            name: "Kinds".into(),
+            description: None,
+            fields: Some(vec![
+                field("level", PrimitiveType::U8, Some(toml::Value::Integer(3))),
+                field("speed", PrimitiveType::F32, Some(toml::Value::Float(1.5))),
+                field("target", PrimitiveType::Entity, None),
+                field("heading", PrimitiveType::Vec2, Some(toml::Value::Array(vec![1.into(), 0.5.into()]))),
+                field("tint", PrimitiveType::Color, Some("#ff0000".into())),
+            ]),
+        };
+        let transpiled = format_rust_code(component.transpile().unwrap()).unwrap();
+        assert!(transpiled.contains("pub level: u8,"));
+        assert!(transpiled.contains("pub heading: Vec2,"));
+        assert!(transpiled.contains("level: 3,"));
+        assert!(transpiled.contains("speed: 1.5,"));
+        assert!(transpiled.contains("target: Entity::PLACEHOLDER,"));
+        assert!(transpiled.contains("heading: Vec2::new(1f32, 0.5f32),"));
+        assert!(transpiled.contains("tint: Color::srgba(1f32, 0f32, 0f32, 1f32),"));
+    }
+    #[test]
+    fn test_transpile_container_fields() {
+        let field = |name: &str, type_str: &str, default_value: Option<toml::Value>| ComponentField {
+            name: name.into(),
+            type_spec: AethaumType::from_type_str(type_str).unwrap().into(),
+            default_value: default_value.map(Into::into),
+            description: None,
+        };
+        let component = Component {
+            name: "Inventory".into(),
+            description: None,
+            fields: Some(vec![
+                field("items", "list<str>", Some(toml::Value::Array(vec!["sword".into()]))),
+                field("stock", "map<str, u32>", Some(toml::Value::Table(toml::Table::from_iter([("arrow".to_string(), 20.into())])))),
+                field("owner", "option<Entity>", None),
+                field("slots", "array<option<str>, 40>", None),
+                field("grid", "array<Entity, 2>", None),
+            ]),
+        };
+        let transpiled = format_rust_code(component.transpile().unwrap()).unwrap();
+        assert!(transpiled.contains("pub items: Vec<String>,"));
+        assert!(transpiled.contains("pub stock: std::collections::HashMap<String, u32>,"));
+        assert!(transpiled.contains("pub owner: Option<Entity>,"));
+        assert!(transpiled.contains("pub slots: [Option<String>; 40],"));
+        assert!(transpiled.contains("items: vec![\"sword\".to_string()],"));
+        assert!(transpiled.contains("stock: std::collections::HashMap::from([(\"arrow\".to_string(), 20)]),"));
+        assert!(transpiled.contains("slots: std::array::from_fn(|_| Default::default()),"));
+        assert!(transpiled.contains("grid: std::array::from_fn(|_| Entity::PLACEHOLDER),"));
     }
     #[test]
     fn test_transpile_event() {
@@ -614,12 +1091,12 @@ mod tests {
                 EventField {
                     name: SmartString::from("target"),
                     description: Some("The element that was clicked".into()),
-                    type_spec: AethaumType::Primitive(PrimitiveType::Str),
+                    type_spec: AethaumType::Primitive(PrimitiveType::Str).into(),
                 },
                 EventField {
                     name: SmartString::from("value"),
                     description: None,
-                    type_spec: AethaumType::Primitive(PrimitiveType::Int),
+                    type_spec: AethaumType::Primitive(PrimitiveType::Int).into(),
                 },
             ]),
         };
@@ -629,6 +1106,9 @@ mod tests {
         println!("{}", transpiled);
         let parsed_result = syn::parse_str::<syn::File>(&transpiled);
         assert!(parsed_result.is_ok(), "Generated code has syntax errors: {:?}", parsed_result.err());
+        // events.emit 的 payload 按事件字段检查
+        assert!(transpiled.contains("check_lua_table_keys("));
+        assert!(transpiled.contains("target: lua_table_field("));
     }
     #[test]
     fn test_transpile_entity_protos() {
@@ -636,8 +1116,8 @@ mod tests {
             name: "TestEntity".into(),
             description: Some("This is a test entity".into()),
             components: vec![
-                ComponentRef::new(None::<&str>, "position"),
-                ComponentRef::new(Some("TestComponent"), "test_component")
+                ComponentRef::new(None::<&str>, "position").into(),
+                ComponentRef::new(Some("TestComponent"), "test_component").into()
             ]
         };
         let transpiled = event.transpile().unwrap();
@@ -646,6 +1126,28 @@ mod tests {
         println!("{}", transpiled);
         let parsed_result = syn::parse_str::<syn::File>(&transpiled);
         assert!(parsed_result.is_ok(), "Generated code has syntax errors: {:?}", parsed_result.err());
+        // 覆盖表的键为原型中的组件名
+        assert!(transpiled.contains("impl FromLua for TestEntityBundle"));
+        assert!(transpiled.contains("&[\"position\", \"test_component\"]"));
+        assert!(transpiled.contains("bundle.position = LuaComponent::from_lua_table(&component, lua)?"));
+    }
+    #[test]
+    fn test_transpile_entity_protos_qualify_same_named_components() {
+        let entity_proto = EntityProto {
+            name: "Hero".into(),
+            description: None,
+            components: vec![
+                ComponentRef::new(None::<&str>, "Health").into(),
+                ComponentRef::new(Some("combat"), "Health").into(),
+                ComponentRef::new(None::<&str>, "Position").into(),
+            ]
+        };
+        let transpiled = format_rust_code(entity_proto.transpile().unwrap()).unwrap();
+        // 本模块的组件保留组件名，其他模块的同名组件以模块名区分
+        assert!(transpiled.contains("health: components::Health,"), "{}", transpiled);
+        assert!(transpiled.contains("combat_health: combat::components::Health,"));
+        assert!(transpiled.contains("&[\"Health\", \"combat::Health\", \"Position\"]"));
+        assert!(transpiled.contains("bundle.combat_health = LuaComponent::from_lua_table(&component, lua)?"));
     }
     #[test]
     fn test_transpile_system() {
@@ -674,11 +1176,12 @@ mod tests {
             }),
             event_handlers: vec![
                 SystemEventHandler {
-                    watch_for: EventRef::new(None::<&str>, "click"),
+                    watch_for: EventRef::new(None::<&str>, "click").into(),
                     priority: 0,
                     logic: None,
                 }
-            ]
+            ],
+            source: None,
         };
         let transpiled = system.transpile().unwrap();
         println!("{}", transpiled);
@@ -688,9 +1191,181 @@ mod tests {
         assert!(parsed_result.is_ok(), "Generated code has syntax errors: {:?}", parsed_result.err());
     }
     #[test]
+    fn test_transpile_system_update_runs_lua() {
+        let mut system = System {
+            normal: SystemNormal {
+                name: "RegenSystem".into(),
+                description: None,
+                category: None,
+                priority: None,
+            },
+            queries: vec![
+                SystemQuery {
+                    name: SmartString::from("living"),
+                    description: None,
+                    component_constraint: ComponentConstraint::new_empty().with_include(
+                        vec![ComponentRef::new(Some("combat"), "Health")]
+                    )
+                }
+            ],
+            update: Some(SystemUpdate {
+                interval: std::time::Duration::from_secs_f64(0.5),
+                condition: Some(LuaScript::Embed("return entity.health.value > 0".into())),
+                logic: Some(LuaScript::File("scripts/regen.lua".into())),
+            }),
+            event_handlers: vec![
+                SystemEventHandler {
+                    watch_for: EventRef::new(Some("combat"), "Damaged").into(),
+                    priority: 0,
+                    logic: Some(LuaScript::Embed("entity.health.value = entity.health.value - event.amount".into())),
+                },
+                SystemEventHandler {
+                    watch_for: EventRef::new(None::<&str>, "Ignored").into(),
+                    priority: 0,
+                    logic: None,
+                }
+            ],
+            source: None,
+        };
+        let transpiled = format_rust_code(system.transpile().unwrap()).unwrap();
+        assert!(transpiled.contains("pub fn update("));
+        assert!(transpiled.contains("ComponentBinding::of::<combat::components::Health>(\"health\")"));
+        assert!(transpiled.contains("path: \"scripts/regen.lua\""));
+        assert!(transpiled.contains("run_lua_for_entity("));
+        // 脚本在 with_ecs 中执行，可以使用 ecs 表
+        assert!(transpiled.contains("with_ecs("));
+        assert!(transpiled.contains("query_entities("));
+        assert!(transpiled.contains("500000000u64"));
+        assert!(transpiled.contains("fixed_timestep_runs("));
+        assert!(transpiled.contains("pub fn regensystem_on_combat_damaged_0("));
+        assert!(transpiled.contains("EventReader<combat::events::Damaged>"));
+        assert!(transpiled.contains("lua.globals().set(\"event\", event)"));
+        assert!(!transpiled.contains("regensystem_on_ignored"));
+        // 脚本在启动时编译，按系统与处理器取用
+        assert!(transpiled.contains("pub const SCRIPTS: &[(LuaScriptKey, LuaSource)]"));
+        assert!(transpiled.contains("const ON_COMBAT_DAMAGED_LOGIC: LuaScriptKey"));
+        // condition 中的错误报告在 condition 自己的键下
+        assert!(transpiled.contains("report_lua_error(&Self::UPDATE_CONDITION, err)"));
+        assert!(transpiled.contains("None => report_lua_error(&Self::UPDATE_LOGIC, &err)"));
+        assert!(transpiled.contains("module: MODULE_NAME"));
+        assert!(transpiled.contains("runtime.script(&Self::UPDATE_CONDITION)"));
+        assert!(transpiled.contains("runtime.script(&Self::UPDATE_LOGIC)"));
+        assert!(!transpiled.contains(".load(lua)"));
+        // 不知道TOML文件时，内嵌脚本以系统与处理器命名
+        assert!(transpiled.contains("name: \"RegenSystem.on combat::Damaged\""));
+        assert!(transpiled.contains("report_lua_error(&Self::ON_COMBAT_DAMAGED_LOGIC, &err)"));
+
+        //没有 logic 时不生成 update
+        system.update.as_mut().unwrap().logic = None;
+        let transpiled = format_rust_code(system.transpile().unwrap()).unwrap();
+        assert!(!transpiled.contains("pub fn update("));
+        // entity 表中的同名组件以模块名区分
+        system.update.as_mut().unwrap().logic = Some(LuaScript::File("scripts/regen.lua".into()));
+        system.queries[0].component_constraint = ComponentConstraint::new_empty().with_include(
+            vec![ComponentRef::new(None::<&str>, "Health"), ComponentRef::new(Some("combat"), "Health")]
+        );
+        let transpiled = format_rust_code(system.transpile().unwrap()).unwrap();
+        assert!(transpiled.contains("ComponentBinding::of::<components::Health>(\"health\")"));
+        assert!(transpiled.contains(">(\"combat::health\")"), "{}", transpiled);
+    }
+    #[test]
+    fn test_transpile_system_event_handlers_do_not_collide() {
+        let handler = |module_name: Option<&str>, name: &str| SystemEventHandler {
+            watch_for: EventRef::new(module_name, name).into(),
+            priority: 0,
+            logic: Some(LuaScript::Embed("return".into())),
+        };
+        let system = System {
+            normal: SystemNormal {
+                name: "RegenSystem".into(),
+                description: None,
+                category: None,
+                priority: None,
+            },
+            queries: vec![],
+            update: None,
+            event_handlers: vec![
+                handler(None, "Damaged"),
+                handler(None, "Damaged"),
+                handler(Some("combat"), "Damaged"),
+            ],
+            source: None,
+        };
+        let transpiled = format_rust_code(system.transpile().unwrap()).unwrap();
+        // 同一事件的多个处理器以序号区分，不同模块的同名事件以模块区分
+        assert!(transpiled.contains("pub fn regensystem_on_damaged_0("));
+        assert!(transpiled.contains("pub fn regensystem_on_damaged_1("));
+        assert!(transpiled.contains("pub fn regensystem_on_combat_damaged_2("));
+        assert!(transpiled.contains("const ON_DAMAGED_1_LOGIC: LuaScriptKey"));
+        assert!(transpiled.contains("const ON_DAMAGED_2_LOGIC: LuaScriptKey"));
+        assert!(transpiled.contains("const ON_COMBAT_DAMAGED_LOGIC: LuaScriptKey"));
+        assert!(transpiled.contains("handler: \"on Damaged #1\""));
+        assert!(transpiled.contains("handler: \"on Damaged #2\""));
+        assert!(transpiled.contains("handler: \"on combat::Damaged\""));
+    }
+    #[test]
+    fn test_transpile_module_imports_watched_event_modules() {
+        let handler = |module_name: Option<&str>, name: &str| SystemEventHandler {
+            watch_for: EventRef::new(module_name, name).into(),
+            priority: 0,
+            logic: Some(LuaScript::Embed("return".into())),
+        };
+        let system = System {
+            normal: SystemNormal {
+                name: "AlarmSystem".into(),
+                description: None,
+                category: None,
+                priority: None,
+            },
+            queries: vec![],
+            update: None,
+            event_handlers: vec![
+                handler(Some("combat"), "Damaged"),
+                handler(Some("combat"), "Healed"),
+                handler(Some("explore"), "Moved"),
+            ],
+            source: None,
+        };
+        let module = EcsModule::new_empty("explore".into()).with_systems(vec![system]);
+        let transpiled = format_rust_code(module.transpile().unwrap()).unwrap();
+        assert!(transpiled.contains("EventReader<combat::events::Damaged>"), "{}", transpiled);
+        // 每个外部模块只导入一次，本模块不需要导入
+        assert_eq!(transpiled.matches("use crate::modules::combat;").count(), 1);
+        assert!(!transpiled.contains("use crate::modules::explore;"));
+    }
+    #[test]
+    fn test_transpile_system_embed_source_location() {
+        let content = r#"
+[normal]
+name = "RegenSystem"
+
+[[queries]]
+name = "living"
+components_include = ["Health"]
+
+[update]
+interval = 1
+logic = '''
+entity.health.value = entity.health.value + 1
+'''
+
+[[event_handlers]]
+watch_for = "Damaged"
+logic = "entity.health.value = 0"
+"#;
+        let raw: RawSystem = toml::from_str(content).unwrap();
+        let system = System::try_from(raw).unwrap()
+            .with_source(SourceFile::new("modules/combat/systems/regen.toml", content));
+        let transpiled = format_rust_code(system.transpile().unwrap()).unwrap();
+        // 内嵌脚本以TOML文件命名，并从字符串在TOML中的起始行开始计数
+        assert!(transpiled.contains("name: \"modules/combat/systems/regen.toml\""));
+        assert!(transpiled.contains("line: 12u32"));
+        assert!(transpiled.contains("line: 17u32"));
+    }
+    #[test]
     fn test_transpile_module() {
         let module = ModuleFileLoader::new(
-            r#"D:\Aethaum\test_project\modules\explore"#.into(),
+            crate::test_utils::test_project_root().join("modules").join("explore"),
             "explore".into()
         ).load().unwrap();
         let transpiled = module.transpile().unwrap();
@@ -699,5 +1374,22 @@ mod tests {
         println!("{}", transpiled);
         let parsed_result = syn::parse_str::<syn::File>(&transpiled);
         assert!(parsed_result.is_ok(), "Generated code has syntax errors: {:?}", parsed_result.err());
+        // 每个系统按优先级单独注册，而不是放进同一个无序元组
+        assert!(!transpiled.contains("app.add_systems(Update, ("));
+        assert!(transpiled.contains("AethaumPriority::update(100u32)"));
+        assert!(transpiled.contains("AethaumPriority::handler(100u32, 10u32)"));
+        assert!(transpiled.contains("AethaumPriority::handler(100u32, 20u32)"));
+        assert!(transpiled.contains("register_component::<components::Position>(\"explore\", \"position\")"));
+        assert!(transpiled.contains("register_type_data::<components::Position, ReflectToLua>()"));
+        assert!(transpiled.contains("register_type_data::<components::Position, ReflectFromLua>()"));
+        assert!(transpiled.contains("register_constructor::<components::Position>()"));
+        assert!(transpiled.contains("register_proto::<entity_protos::PlayerBundle>()"));
+        // 原型只通过 ecs.spawn 生成，不再为每个原型生成处理生成请求的系统
+        assert!(!transpiled.contains("spawn_player_system"));
+        assert!(!transpiled.contains("AethaumSpawnEntity"));
+        // 插件名为大驼峰
+        assert!(transpiled.contains("pub struct ExplorePlugin;"));
+        assert!(transpiled.contains("register_scripts(systems::HealthSystem::SCRIPTS)"));
+        assert!(transpiled.contains("pub const MODULE_NAME: &str = \"explore\";"));
     }
 }
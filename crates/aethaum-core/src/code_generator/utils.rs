@@ -1,7 +1,17 @@
+use std::fs;
+use std::path::Path;
 use proc_macro2::TokenStream;
 use crate::code_generator::transpile::TranspileError;
 
 pub fn format_rust_code(stream : TokenStream) -> Result<String, TranspileError> {
     let syntax_tree = syn::parse2::<syn::File>(stream)?;
     Ok(prettyplease::unparse(&syntax_tree))
-}
\ No newline at end of file
+}
+///内容未变化时不写入，避免更新修改时间导致 cargo 重新编译
+pub fn write_if_changed(path: &Path, content: &str) -> std::io::Result<bool> {
+    if fs::read_to_string(path).is_ok_and(|old| old == content) {
+        return Ok(false);
+    }
+    fs::write(path, content)?;
+    Ok(true)
+}
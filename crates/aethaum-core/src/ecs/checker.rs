@@ -1,15 +1,17 @@
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
+use miette::Diagnostic;
 use thiserror::Error;
 use crate::ecs::checker::context::{ModuleCheckContext, ModuleCheckTree};
 use crate::ecs::checker::cross_module::{CrossModuleCheckError, CrossModuleCheckable};
 use crate::ecs::checker::in_module::{InModuleCheckError, InModuleCheckable};
 use crate::ecs::checker::type_checker::{TypeCheckError, TypeCheckable};
 use crate::ecs::module::{AethaumProject, EcsModule, EcsThingRef};
+use crate::toml_parser::parsed::Located;
 
-mod context;
-mod type_checker;
-mod in_module;
-mod cross_module;
+pub mod context;
+pub mod type_checker;
+pub mod in_module;
+pub mod cross_module;
 
 #[derive(Debug)]
 pub enum CheckStage {
@@ -18,19 +20,23 @@ pub enum CheckStage {
     CrossModule
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, Diagnostic)]
 pub enum CheckerError {
     #[error("Type Error: {0}")]
+    #[diagnostic(transparent)]
     Type(#[from] TypeCheckError),
     #[error("Unfiltered In Module Check Error: {0}")]
     InModule(InModuleCheckError, Box<ModuleCheckContext>),
     #[error("In Module Check Error: {0}")]
+    #[diagnostic(transparent)]
     FilteredInModule(InModuleCheckError),
     #[error("Cross Module Check Error: {0}")]
+    #[diagnostic(transparent)]
     CrossModule(#[from] CrossModuleCheckError),
     #[error("Multiple errors occurred during checking:\n{}",
         .errors.iter().map(|e| format!("  - {}", e)).collect::<Vec<_>>().join("\n"))]
     Multiple {
+        #[related]
         errors: Vec<CheckerError>,
     }
 }
@@ -42,7 +48,7 @@ impl CheckerError {
     }
 }
 pub struct CheckedEcs {
-    modules: Vec<EcsModule>,
+    pub modules: Vec<EcsModule>,
 }
 impl<I> From<I> for CheckedEcs
 where
@@ -74,7 +80,7 @@ impl SingleEcsModuleChecker {
 }
 pub struct CrossEcsModuleChecker;
 impl CrossEcsModuleChecker {
-    pub fn run_checks<'a>(ref_to_check: impl IntoIterator<Item = &'a EcsThingRef>, module_tree: &ModuleCheckTree) -> Result<(), CrossModuleCheckError> {
+    pub fn run_checks<'a>(ref_to_check: impl IntoIterator<Item = &'a Located<EcsThingRef>>, module_tree: &ModuleCheckTree) -> Result<(), CrossModuleCheckError> {
         //3.模块间检查
         let mut errors = Vec::new();
         for thing_ref in ref_to_check {
@@ -109,7 +115,7 @@ impl AethaumChecker {
         CrossEcsModuleChecker::run_checks(propagated_checks.iter(), &module_tree)?;
         Ok(project)
     }
-    fn extract_propagated_check(errors: Vec<CheckerError>) -> Result<(Vec<ModuleCheckContext>, Vec<EcsThingRef>), CheckerError> {
+    fn extract_propagated_check(errors: Vec<CheckerError>) -> Result<(Vec<ModuleCheckContext>, Vec<Located<EcsThingRef>>), CheckerError> {
         let mut true_errors = Vec::new();
         let mut module_contexts = Vec::new();
         let mut propagated_checks = Vec::new();
@@ -119,14 +125,14 @@ impl AethaumChecker {
                     module_contexts.push(*module_context);
                     match err {
                         InModuleCheckError::PropagateToCrossCheck {thing_ref} => {
-                            propagated_checks.push(thing_ref);
+                            propagated_checks.push(*thing_ref);
 
                         },
                         InModuleCheckError::Multiple { errors} => {
                             for err in errors {
                                 match err {
                                     InModuleCheckError::PropagateToCrossCheck {thing_ref} => {
-                                        propagated_checks.push(thing_ref);
+                                        propagated_checks.push(*thing_ref);
                                     },
                                     _ => true_errors.push(CheckerError::FilteredInModule(err)),
                                 }
@@ -150,10 +156,11 @@ impl AethaumChecker {
 #[cfg(test)]
 mod tests {
     use crate::ecs::loader::ProjectLoader;
+    use crate::test_utils::test_project_root;
     use super::*;
     #[test]
     fn test_project_checker() {
-        let project = ProjectLoader::new(r#"D:\Aethaum\test_project"#.into()).load().unwrap();
+        let project = ProjectLoader::new(test_project_root()).load().unwrap();
         AethaumChecker::run_check(project).unwrap();
     }
 }
\ No newline at end of file
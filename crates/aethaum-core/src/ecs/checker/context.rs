@@ -1,9 +1,7 @@
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use smart_string::SmartString;
-use crate::toml_parser::parsed::{AethaumType, ComponentRef, EntityProtoRef, EventRef, PrimitiveType, SystemRef};
-use anyhow::Result;
-use crate::ecs::module::EcsModule;
+use crate::toml_parser::parsed::{ComponentRef, EntityProtoRef, EventRef, SystemRef};
 
 pub struct ModuleCheckTree {
     modules: HashMap<String, ModuleCheckContext> // 模块名 -> 模块上下文
@@ -34,6 +32,11 @@ impl ModuleCheckTree {
         &mut self.modules
     }
 }
+impl Default for ModuleCheckTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 pub enum ECSThing {
     Component,
     Event,
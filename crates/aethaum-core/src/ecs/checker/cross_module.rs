@@ -1,22 +1,35 @@
+use std::sync::Arc;
+use miette::{Diagnostic, NamedSource, SourceSpan};
 use smart_string::SmartString;
 use thiserror::Error;
 use crate::ecs::checker::context::{ModuleCheckContext, ModuleCheckTree};
-use crate::ecs::module::{EcsThingRef, ModulePath};
-use crate::toml_parser::parsed::{ComponentRef, EntityProto, EntityProtoRef, EventRef, SystemEventHandler, SystemQuery, SystemRef};
+use crate::ecs::module::EcsThingRef;
+use crate::toml_parser::error::SourceLocation;
+use crate::toml_parser::parsed::Located;
 
-#[derive(Debug,Error)]
+#[derive(Debug,Error,Diagnostic)]
 pub enum CrossModuleCheckError {
     #[error("Module '{module_name} has multiple definition.'")]
     MultiDefinition {
         module_name: SmartString,
     },
     #[error("'{}' is not defined in module '{}'", thing_ref.as_error_str(), thing_ref.module_name())]
+    #[diagnostic(code(aethaum::check::ref_not_found))]
     RefNotFound {
         thing_ref: EcsThingRef,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("not defined in module '{}'", thing_ref.module_name())]
+        span: Option<SourceSpan>,
     },
     #[error("Module '{module_name}' is not found.")]
+    #[diagnostic(code(aethaum::check::module_not_found))]
     ModuleNotFound {
         module_name: SmartString,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("unknown module '{module_name}'")]
+        span: Option<SourceSpan>,
     },
     #[error("Unexpected Module name missing in {thing_ref}")]
     UnexpectedModuleNameMissing {
@@ -25,6 +38,7 @@ pub enum CrossModuleCheckError {
     #[error("Multiple errors occurred during checking:\n{}",
         .errors.iter().map(|e| format!("  - {}", e)).collect::<Vec<_>>().join("\n"))]
     Multiple {
+        #[related]
         errors: Vec<CrossModuleCheckError>,
     }
 
@@ -33,11 +47,11 @@ impl CrossModuleCheckError {
     pub fn raise_multi_definition(module_name: SmartString) -> Self {
         Self::MultiDefinition { module_name }
     }
-    pub fn raise_ref_not_found(thing_ref: EcsThingRef) -> Self {
-        Self::RefNotFound { thing_ref }
+    pub fn raise_ref_not_found(thing_ref: EcsThingRef, location: SourceLocation) -> Self {
+        Self::RefNotFound { thing_ref, src: location.src, span: location.span }
     }
-    pub fn raise_module_not_found(module_name: SmartString) -> Self {
-        Self::ModuleNotFound { module_name }
+    pub fn raise_module_not_found(module_name: SmartString, location: SourceLocation) -> Self {
+        Self::ModuleNotFound { module_name, src: location.src, span: location.span }
     }
     pub fn raise_unexpected_module_name_missing(thing_ref: EcsThingRef) -> Self {
         Self::UnexpectedModuleNameMissing { thing_ref }
@@ -61,7 +75,8 @@ impl CrossModuleChecker {
         Ok(())
     }
     ///检查跨模块引用，应当在所有module都被注册完后调用
-    pub fn check_cross_module_ref(thing_ref: &EcsThingRef, tree: &ModuleCheckTree) -> Result<(), CrossModuleCheckError> {
+    pub fn check_cross_module_ref(located_ref: &Located<EcsThingRef>, tree: &ModuleCheckTree) -> Result<(), CrossModuleCheckError> {
+        let thing_ref = &located_ref.value;
         if thing_ref.module_name().is_empty() {
             return Err(
                 CrossModuleCheckError::raise_unexpected_module_name_missing(thing_ref.clone())
@@ -69,34 +84,34 @@ impl CrossModuleChecker {
         }
 
         let module_context = tree.get_module_context(thing_ref.module_name())
-            .ok_or_else(|| CrossModuleCheckError::raise_module_not_found(SmartString::from(thing_ref.module_name())))?;
+            .ok_or_else(|| CrossModuleCheckError::raise_module_not_found(SmartString::from(thing_ref.module_name()), located_ref.location()))?;
 
         match thing_ref {
             EcsThingRef::Component(component_ref) => {
                 if !module_context.defined_components.contains(component_ref) {
                     return Err(
-                        CrossModuleCheckError::raise_ref_not_found(EcsThingRef::Component(component_ref.clone()))
+                        CrossModuleCheckError::raise_ref_not_found(EcsThingRef::Component(component_ref.clone()), located_ref.location())
                     )
                 }
             }
             EcsThingRef::Event(event_ref) => {
                 if !module_context.defined_events.contains(event_ref) {
                     return Err(
-                        CrossModuleCheckError::raise_ref_not_found(EcsThingRef::Event(event_ref.clone()))
+                        CrossModuleCheckError::raise_ref_not_found(EcsThingRef::Event(event_ref.clone()), located_ref.location())
                     )
                 }
             }
             EcsThingRef::EntityProto(entity_proto_ref) => {
                 if !module_context.defined_entity_protos.contains(entity_proto_ref) {
                     return Err(
-                        CrossModuleCheckError::raise_ref_not_found(EcsThingRef::EntityProto(entity_proto_ref.clone()))
+                        CrossModuleCheckError::raise_ref_not_found(EcsThingRef::EntityProto(entity_proto_ref.clone()), located_ref.location())
                     )
                 }
             }
             EcsThingRef::System(system_ref) => {
                 if !module_context.defined_systems.contains(system_ref) {
                     return Err(
-                        CrossModuleCheckError::raise_ref_not_found(EcsThingRef::System(system_ref.clone()))
+                        CrossModuleCheckError::raise_ref_not_found(EcsThingRef::System(system_ref.clone()), located_ref.location())
                     )
                 }
             }
@@ -106,7 +121,7 @@ impl CrossModuleChecker {
     }
 }
 pub trait CrossModuleCheckable {
-    fn check_cross_module(thing_ref: &EcsThingRef, module_context: &ModuleCheckTree) -> Result<(), CrossModuleCheckError> {
+    fn check_cross_module(thing_ref: &Located<EcsThingRef>, module_context: &ModuleCheckTree) -> Result<(), CrossModuleCheckError> {
        CrossModuleChecker::check_cross_module_ref(thing_ref, module_context)
     }
 }
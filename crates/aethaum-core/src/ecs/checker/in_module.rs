@@ -1,10 +1,13 @@
 use std::path::PathBuf;
+use std::sync::Arc;
+use miette::{Diagnostic, NamedSource, SourceSpan};
 use thiserror::Error;
 use crate::ecs::checker::context::{ModuleCheckContext};
 use crate::ecs::module::{EcsModule, EcsThingRef};
-use crate::toml_parser::parsed::{ComponentRef, EntityProto, EntityProtoRef, EventRef, LuaScript, System, SystemEventHandler, SystemQuery, SystemRef};
+use crate::toml_parser::error::SourceLocation;
+use crate::toml_parser::parsed::{ComponentRef, EntityProto, EntityProtoRef, EventRef, Located, LuaScript, System, SystemEventHandler, SystemQuery, SystemRef};
 
-#[derive(Debug,Error)]
+#[derive(Debug,Error,Diagnostic)]
 pub enum InModuleCheckError {
     #[error("'{}' is already defined.",thing_ref.as_error_str())]
     AlreadyDefined {
@@ -15,18 +18,24 @@ pub enum InModuleCheckError {
         thing_ref: EcsThingRef,
     },
     #[error("'{}' is not defined in current module.",thing_ref.as_error_str())]
+    #[diagnostic(code(aethaum::check::not_defined), help("define it in this module, or use `module::Name` to refer to another module"))]
     NotDefined {
         thing_ref: EcsThingRef,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("not defined in this module")]
+        span: Option<SourceSpan>,
     },
-    #[error("'{thing_ref}' should be checked in Cross Module Check stage.")]
+    #[error("'{}' should be checked in Cross Module Check stage.", thing_ref.value)]
     PropagateToCrossCheck {
-        thing_ref: EcsThingRef,
+        thing_ref: Box<Located<EcsThingRef>>,
     },
     #[error("Lua script '{0}' not found in system '{1}'.")]
     LuaScriptNotFound(PathBuf, SystemRef),
     #[error("Multiple errors occurred during checking:\n{}",
         .errors.iter().map(|e| format!("  - {}", e)).collect::<Vec<_>>().join("\n"))]
     Multiple {
+        #[related]
         errors: Vec<InModuleCheckError>,
     }
 }
@@ -37,11 +46,11 @@ impl InModuleCheckError {
     pub fn raise_define_external(thing_ref: EcsThingRef) -> Self {
         Self::DefineExternal { thing_ref }
     }
-    pub fn raise_not_defined(thing_ref: EcsThingRef) -> Self {
-        Self::NotDefined { thing_ref }
+    pub fn raise_not_defined(thing_ref: EcsThingRef, location: SourceLocation) -> Self {
+        Self::NotDefined { thing_ref, src: location.src, span: location.span }
     }
-    pub fn raise_propagate_to_cross_check(thing_ref: EcsThingRef) -> Self {
-        Self::PropagateToCrossCheck { thing_ref }
+    pub fn raise_propagate_to_cross_check(thing_ref: Located<EcsThingRef>) -> Self {
+        Self::PropagateToCrossCheck { thing_ref: Box::new(thing_ref) }
     }
     pub fn raise_lua_script_not_found(lua_script: PathBuf, system_ref: SystemRef) -> Self {
         Self::LuaScriptNotFound(lua_script, system_ref)
@@ -60,13 +69,12 @@ impl InModuleChecker {
     pub fn try_register(thing: EcsThingRef, module_context: &mut ModuleCheckContext) -> Result<(),InModuleCheckError> {
         match thing {
             EcsThingRef::Component(component_ref) => {
-                if let Some(module_name) = component_ref.module_name.as_ref() {
-                    if module_name.as_str() != module_context.name.as_str() {
-                        //TODO: remove the clone
-                        return Err(
-                            InModuleCheckError::raise_define_external(EcsThingRef::Component(component_ref.clone()))
-                        );
-                    }
+                if let Some(module_name) = component_ref.module_name.as_ref()
+                    && module_name.as_str() != module_context.name.as_str() {
+                    //TODO: remove the clone
+                    return Err(
+                        InModuleCheckError::raise_define_external(EcsThingRef::Component(component_ref.clone()))
+                    );
                 }
                 if module_context.defined_components.contains(&component_ref) {
                     return Err(
@@ -77,13 +85,12 @@ impl InModuleChecker {
                 Ok(())
             },
             EcsThingRef::Event(event_ref) => {
-                if let Some(module_name) = event_ref.module_name.as_ref() {
-                    if module_name.as_str() != module_context.name.as_str() {
-                        //TODO: remove the clone
-                        return Err(
-                            InModuleCheckError::raise_define_external(EcsThingRef::Event(event_ref.clone()))
-                        );
-                    }
+                if let Some(module_name) = event_ref.module_name.as_ref()
+                    && module_name.as_str() != module_context.name.as_str() {
+                    //TODO: remove the clone
+                    return Err(
+                        InModuleCheckError::raise_define_external(EcsThingRef::Event(event_ref.clone()))
+                    );
                 }
                 if module_context.defined_events.contains(&event_ref) {
                     return Err(
@@ -94,13 +101,12 @@ impl InModuleChecker {
                 Ok(())
             },
             EcsThingRef::EntityProto(entity_proto_ref) => {
-                if let Some(module_name) = entity_proto_ref.module_name.as_ref() {
-                    if module_name.as_str() != module_context.name.as_str() {
-                        //TODO: remove the clone
-                        return Err(
-                            InModuleCheckError::raise_define_external(EcsThingRef::EntityProto(entity_proto_ref.clone()))
-                        );
-                    }
+                if let Some(module_name) = entity_proto_ref.module_name.as_ref()
+                    && module_name.as_str() != module_context.name.as_str() {
+                    //TODO: remove the clone
+                    return Err(
+                        InModuleCheckError::raise_define_external(EcsThingRef::EntityProto(entity_proto_ref.clone()))
+                    );
                 }
                 if module_context.defined_entity_protos.contains(&entity_proto_ref) {
                     return Err(
@@ -111,13 +117,12 @@ impl InModuleChecker {
                 Ok(())
             },
             EcsThingRef::System(system_ref) => {
-                if let Some(module_name) = system_ref.module_name.as_ref() {
-                    if module_name.as_str() != module_context.name.as_str() {
-                        //TODO: remove the clone
-                        return Err(
-                            InModuleCheckError::raise_define_external(EcsThingRef::System(system_ref.clone()))
-                        );
-                    }
+                if let Some(module_name) = system_ref.module_name.as_ref()
+                    && module_name.as_str() != module_context.name.as_str() {
+                    //TODO: remove the clone
+                    return Err(
+                        InModuleCheckError::raise_define_external(EcsThingRef::System(system_ref.clone()))
+                    );
                 }
                 if module_context.defined_systems.contains(&system_ref) {
                     return Err(
@@ -129,71 +134,108 @@ impl InModuleChecker {
             }
         }
     }
+    ///将模块中定义的所有组件，事件，实体原型，系统注册到模块上下文中，返回注册时产生的错误
+    pub fn register_module_definitions(module: &EcsModule, module_context: &mut ModuleCheckContext) -> Vec<InModuleCheckError> {
+        let mut errors = Vec::new();
+        if let Some(components) = &module.components {
+            for component in components {
+                let comp_ref = ComponentRef::from((module.name.as_str(), component.name.as_str()));
+                if let Err(e) = InModuleChecker::try_register(comp_ref.into(), module_context) {
+                    errors.push(e);
+                }
+            }
+        }
+        if let Some(events) = &module.events {
+            for event in events {
+                let event_ref = EventRef::from((module.name.as_str(), event.name.as_str()));
+                if let Err(e) = InModuleChecker::try_register(event_ref.into(), module_context) {
+                    errors.push(e);
+                }
+            }
+        }
+        if let Some(entity_protos) = &module.entity_protos {
+            for entity_proto in entity_protos {
+                let entity_proto_ref = EntityProtoRef::from((module.name.as_str(), entity_proto.name.as_str()));
+                if let Err(e) = InModuleChecker::try_register(entity_proto_ref.into(), module_context) {
+                    errors.push(e);
+                }
+            }
+        }
+        if let Some(systems) = &module.systems {
+            for system in systems {
+                let system_ref = SystemRef::from((module.name.as_str(), system.normal.name.as_str()));
+                if let Err(e) = InModuleChecker::try_register(system_ref.into(), module_context) {
+                    errors.push(e);
+                }
+            }
+        }
+        errors
+    }
     ///检查模块内引用,应当等待所有组件，事件，实体原型，系统都被注册完后调用
     //TODO: Try reduce the clones
-    pub fn check_in_module_component_ref(component_ref: &ComponentRef, module_context: &ModuleCheckContext) -> Result<(), InModuleCheckError> {
+    pub fn check_in_module_component_ref(component_ref: &Located<ComponentRef>, module_context: &ModuleCheckContext) -> Result<(), InModuleCheckError> {
         if let Some(module_name) = component_ref.module_name.as_ref() {
             if module_name.as_str() != module_context.name.as_str() {
                 return Err(
-                    InModuleCheckError::raise_propagate_to_cross_check(EcsThingRef::Component(component_ref.clone()))
+                    InModuleCheckError::raise_propagate_to_cross_check(component_ref.map(|component_ref| EcsThingRef::Component(component_ref.clone())))
                 );
             }
         }else {
             let local_ref = ComponentRef::new(Some(module_context.name.clone()),component_ref.name.clone());
             if !module_context.defined_components.contains(&local_ref) {
                 return Err(
-                    InModuleCheckError::raise_not_defined(EcsThingRef::Component(component_ref.clone()))
+                    InModuleCheckError::raise_not_defined(EcsThingRef::Component(component_ref.value.clone()), component_ref.location())
                 )
             }
         }
         Ok(())
     }
-    pub fn check_in_module_event_ref(event_ref: &EventRef, module_context: &ModuleCheckContext) -> Result<(), InModuleCheckError> {
+    pub fn check_in_module_event_ref(event_ref: &Located<EventRef>, module_context: &ModuleCheckContext) -> Result<(), InModuleCheckError> {
         if let Some(module_name) = event_ref.module_name.as_ref() {
             if module_name.as_str() != module_context.name.as_str() {
                 return Err(
-                    InModuleCheckError::raise_propagate_to_cross_check(EcsThingRef::Event(event_ref.clone()))
+                    InModuleCheckError::raise_propagate_to_cross_check(event_ref.map(|event_ref| EcsThingRef::Event(event_ref.clone())))
                 );
             }
         }else {
             let local_ref = EventRef::new(Some(module_context.name.clone()),event_ref.name.clone());
             if !module_context.defined_events.contains(&local_ref) {
                 return Err(
-                    InModuleCheckError::raise_not_defined(EcsThingRef::Event(event_ref.clone()))
+                    InModuleCheckError::raise_not_defined(EcsThingRef::Event(event_ref.value.clone()), event_ref.location())
                 )
             }
         }
         Ok(())
     }
-    pub fn check_in_module_entity_proto_ref(entity_proto_ref: &EntityProtoRef, module_context: &ModuleCheckContext) -> Result<(), InModuleCheckError> {
+    pub fn check_in_module_entity_proto_ref(entity_proto_ref: &Located<EntityProtoRef>, module_context: &ModuleCheckContext) -> Result<(), InModuleCheckError> {
         if let Some(module_name) = entity_proto_ref.module_name.as_ref() {
             if module_name.as_str() != module_context.name.as_str() {
                 return Err(
-                    InModuleCheckError::raise_propagate_to_cross_check(EcsThingRef::EntityProto(entity_proto_ref.clone()))
+                    InModuleCheckError::raise_propagate_to_cross_check(entity_proto_ref.map(|entity_proto_ref| EcsThingRef::EntityProto(entity_proto_ref.clone())))
                 );
             }
         }else {
             let local_ref = EntityProtoRef::new(Some(module_context.name.clone()),entity_proto_ref.name.clone());
             if !module_context.defined_entity_protos.contains(&local_ref) {
                 return Err(
-                    InModuleCheckError::raise_not_defined(EcsThingRef::EntityProto(entity_proto_ref.clone()))
+                    InModuleCheckError::raise_not_defined(EcsThingRef::EntityProto(entity_proto_ref.value.clone()), entity_proto_ref.location())
                 )
             }
         }
         Ok(())
     }
-    pub fn check_in_module_system_ref(system_ref: &SystemRef, module_context: &ModuleCheckContext) -> Result<(), InModuleCheckError> {
+    pub fn check_in_module_system_ref(system_ref: &Located<SystemRef>, module_context: &ModuleCheckContext) -> Result<(), InModuleCheckError> {
         if let Some(module_name) = system_ref.module_name.as_ref() {
             if module_name.as_str() != module_context.name.as_str() {
                 return Err(
-                    InModuleCheckError::raise_propagate_to_cross_check(EcsThingRef::System(system_ref.clone()))
+                    InModuleCheckError::raise_propagate_to_cross_check(system_ref.map(|system_ref| EcsThingRef::System(system_ref.clone())))
                 );
             }
         }else {
             let local_ref = SystemRef::new(Some(module_context.name.clone()),system_ref.name.clone());
             if !module_context.defined_systems.contains(&local_ref) {
                 return Err(
-                    InModuleCheckError::raise_not_defined(EcsThingRef::System(system_ref.clone()))
+                    InModuleCheckError::raise_not_defined(EcsThingRef::System(system_ref.value.clone()), system_ref.location())
                 )
             }
         }
@@ -201,7 +243,7 @@ impl InModuleChecker {
     }
 }
 pub trait InModuleCheckable {
-    fn check_in_module(&self, module_context: &mut ModuleCheckContext) -> Result<(), InModuleCheckError> {
+    fn check_in_module(&self, _module_context: &mut ModuleCheckContext) -> Result<(), InModuleCheckError> {
         Ok(())
     }
 }
@@ -260,33 +302,30 @@ impl InModuleCheckable for System {
                 }
             }
         }
-        if let Some(updates) = &self.update {
-            if let Some(condition_path) = &updates.condition {
-                if let LuaScript::File(path) = condition_path {
-                    let path = module_context.project_root.join(path);
-                    if !path.exists() {
-                       errors.push(
-                            InModuleCheckError::raise_lua_script_not_found(
-                                path,
-                                SystemRef::new(Some(module_context.name.clone()),self.normal.name.clone())
-                            )
-                       )
-                    }
-                }
+        if let Some(updates) = &self.update
+            && let Some(condition_path) = &updates.condition
+            && let LuaScript::File(path) = condition_path {
+            let path = module_context.project_root.join(path);
+            if !path.exists() {
+               errors.push(
+                    InModuleCheckError::raise_lua_script_not_found(
+                        path,
+                        SystemRef::new(Some(module_context.name.clone()),self.normal.name.clone())
+                    )
+               )
             }
         } //TODO: reduce the clones
         for handler in self.event_handlers.iter() {
-            if let Some(script) = &handler.logic {
-                if let LuaScript::File(path) = &script {
-                    let path = module_context.project_root.join(path);
-                    if !path.exists() {
-                        errors.push(
-                            InModuleCheckError::raise_lua_script_not_found(
-                                path,
-                                SystemRef::new(Some(module_context.name.clone()),self.normal.name.clone())
-                            )
+            if let Some(script) = &handler.logic
+                && let LuaScript::File(path) = &script {
+                let path = module_context.project_root.join(path);
+                if !path.exists() {
+                    errors.push(
+                        InModuleCheckError::raise_lua_script_not_found(
+                            path,
+                            SystemRef::new(Some(module_context.name.clone()),self.normal.name.clone())
                         )
-                    }
+                    )
                 }
             }
         }
@@ -329,39 +368,7 @@ impl InModuleCheckable for EcsModule {
     fn check_in_module(&self, module_context: &mut ModuleCheckContext) -> Result<(), InModuleCheckError> {
         let mut errors = Vec::new();
         //registers
-        if let Some(components) = &self.components {
-            for component in components {
-                let comp_ref = ComponentRef::from((self.name.as_str(), component.name.as_str()));
-                if let Err(e) = InModuleChecker::try_register(comp_ref.into(), module_context) {
-                    errors.push(e);
-                }
-            }
-        }
-        if let Some(events) = &self.events {
-            for event in events {
-                let event_ref = EventRef::from((self.name.as_str(), event.name.as_str()));
-                if let Err(e) = InModuleChecker::try_register(event_ref.into(), module_context) {
-                    errors.push(e);
-                }
-            }
-        }
-        if let Some(entity_protos) = &self.entity_protos {
-            for entity_proto in entity_protos {
-                let entity_proto_ref = EntityProtoRef::from((self.name.as_str(), entity_proto.name.as_str()));
-                if let Err(e) = InModuleChecker::try_register(entity_proto_ref.into(), module_context) {
-                    errors.push(e);
-                }
-            }
-        }
-        if let Some(systems) = &self.systems {
-            for system in systems {
-                let system_ref = SystemRef::from((self.name.as_str(), system.normal.name.as_str()));
-                if let Err(e) = InModuleChecker::try_register(system_ref.into(), module_context) {
-                    errors.push(e);
-                }
-            }
-        }
-        println!(" module: {:?}", module_context);
+        errors.extend(InModuleChecker::register_module_definitions(self, module_context));
         //ref checking
         if let Some(systems) = &self.systems {
             for system in systems {
@@ -399,12 +406,15 @@ impl InModuleCheckable for EcsModule {
 #[cfg(test)]
 mod tests {
     use crate::ecs::loader::ModuleFileLoader;
+    use crate::test_utils::test_project_root;
+    use crate::toml_parser::parsed::SourceFile;
+    use crate::toml_parser::raw::{RawEntityProtoFile, RawTomlCodeFile};
     use super::*;
     #[test]
     fn test_in_module_check_pass() {
-        let module = ModuleFileLoader::new(r#"D:\Aethaum\test_project\modules\explore"#.into(), "explore".into())
+        let module = ModuleFileLoader::new(test_project_root().join("modules").join("explore"), "explore".into())
             .load().unwrap();
-        let mut module_context = ModuleCheckContext::new("explore".into(),r#"D:\Aethaum\test_project"#.into());
+        let mut module_context = ModuleCheckContext::new("explore".into(),test_project_root());
         let res = module.check_in_module(&mut module_context);
         assert!(res.is_err());
         if let Err(e) = res {
@@ -423,4 +433,25 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_not_defined_points_at_reference() {
+        let content = "[[entity_protos]]\nname = \"Player\"\ncomponents = [\"Health\", \"combat::Position\"]\n";
+        let source = SourceFile::new("modules/explore/entity_protos/player.toml", content);
+        let entity_proto = toml::from_str::<RawEntityProtoFile>(content).unwrap().into_pieces().pop().unwrap();
+        let entity_proto = EntityProto::try_from(entity_proto).unwrap().with_source(&source);
+        let mut module_context = ModuleCheckContext::new("explore".into(), test_project_root());
+        let Err(InModuleCheckError::Multiple { errors }) = entity_proto.check_in_module(&mut module_context) else {
+            panic!("expected both references to be reported");
+        };
+        let text_of = |span: SourceSpan| &content[span.offset()..span.offset() + span.len()];
+        let InModuleCheckError::NotDefined { src, span, .. } = &errors[0] else {
+            panic!("{}", errors[0]);
+        };
+        assert_eq!(src.as_ref().unwrap().name(), "modules/explore/entity_protos/player.toml");
+        assert_eq!(text_of(span.unwrap()), "\"Health\"");
+        let InModuleCheckError::PropagateToCrossCheck { thing_ref } = &errors[1] else {
+            panic!("{}", errors[1]);
+        };
+        assert_eq!(text_of(thing_ref.location().span.unwrap()), "\"combat::Position\"");
+    }
+}
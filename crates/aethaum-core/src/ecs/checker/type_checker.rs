@@ -1,20 +1,45 @@
+use std::sync::Arc;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use smart_string::SmartString;
 use thiserror::Error;
 use crate::ecs::module::EcsModule;
-use crate::toml_parser::parsed::{AethaumType, ComponentField, EventField, PrimitiveType};
+use crate::toml_parser::error::SourceLocation;
+use crate::toml_parser::parsed::{color_components, vector_components, AethaumType, ComponentField, EventField, Located, PrimitiveType};
 
-#[derive(Debug,Error)]
+#[derive(Debug,Error,Diagnostic)]
 pub enum TypeCheckError {
-    #[error("Type mismatch: expected {0}, got {1}")]
-    TypeMismatch(AethaumType, toml::Value),
+    #[error("Type mismatch: expected {expected}, got {got}")]
+    #[diagnostic(code(aethaum::check::type_mismatch))]
+    TypeMismatch {
+        expected: Box<AethaumType>,
+        got: toml::Value,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("expected {expected}")]
+        span: Option<SourceSpan>,
+    },
+    #[error("Unknown type `{type_name}`")]
+    #[diagnostic(code(aethaum::check::unknown_type), help("field types are the primitive types or `list<T>`, `map<str, T>`, `option<T>` and `array<T, N>` of them"))]
+    UnknownType {
+        type_name: SmartString,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("`{type_name}` is not a known type")]
+        span: Option<SourceSpan>,
+    },
     #[error("Multiple errors occurred during checking:\n{}",
         .errors.iter().map(|e| format!("  - {}", e)).collect::<Vec<_>>().join("\n"))]
     Multiple {
+        #[related]
         errors: Vec<TypeCheckError>,
     }
 }
 impl TypeCheckError {
-    pub fn raise_type_mismatch(expected: AethaumType, got: toml::Value) -> Self {
-        Self::TypeMismatch(expected, got)
+    pub fn raise_type_mismatch(expected: AethaumType, got: toml::Value, location: SourceLocation) -> Self {
+        Self::TypeMismatch { expected: Box::new(expected), got, src: location.src, span: location.span }
+    }
+    pub fn raise_unknown_type(type_name: SmartString, location: SourceLocation) -> Self {
+        Self::UnknownType { type_name, src: location.src, span: location.span }
     }
     pub fn raise_multiple(errors: Vec<Self>) -> Self {
         Self::Multiple { errors }
@@ -23,20 +48,57 @@ impl TypeCheckError {
 
 pub struct TypeChecker;
 impl TypeChecker {
-    pub fn check_type_value_match(type_spec: &AethaumType, default_value: &Option<toml::Value>) -> Result<(), TypeCheckError> {
+    pub fn check_type_value_match(type_spec: &AethaumType, default_value: &Option<Located<toml::Value>>) -> Result<(), TypeCheckError> {
+        match default_value {
+            Some(default_value) if !Self::value_matches(type_spec, default_value) => {
+                Err(TypeCheckError::raise_type_mismatch(type_spec.clone(), default_value.value.clone(), default_value.location()))
+            }
+            _ => Ok(()),
+        }
+    }
+    ///类型中的自定义类型，生成的代码中没有它们的定义，都无法解析
+    pub fn check_known_type(type_spec: &Located<AethaumType>) -> Result<(), TypeCheckError> {
+        match Self::custom_type(type_spec) {
+            Some(type_name) => Err(TypeCheckError::raise_unknown_type(type_name.clone(), type_spec.location())),
+            None => Ok(()),
+        }
+    }
+    fn custom_type(type_spec: &AethaumType) -> Option<&SmartString> {
         match type_spec {
-            AethaumType::Primitive(primitive_type) => {
-                match (primitive_type, default_value) {
-                    (PrimitiveType::Bool, Some(toml::Value::Boolean(_))) => Ok(()),
-                    (PrimitiveType::Int, Some(toml::Value::Integer(_))) => Ok(()),
-                    (PrimitiveType::Float, Some(toml::Value::Float(_))) => Ok(()),
-                    (PrimitiveType::Str, Some(toml::Value::String(_))) => Ok(()),
-                    (_, None) => Ok(()),
-                    _ => return Err(TypeCheckError::raise_type_mismatch(type_spec.clone(), default_value.as_ref().unwrap().clone())),
-                    //ROBUST: None is early returned
-                }
+            AethaumType::Primitive(_) => None,
+            AethaumType::List(inner)
+            | AethaumType::Map(inner)
+            | AethaumType::Option(inner)
+            | AethaumType::Array(inner, _) => Self::custom_type(inner),
+            AethaumType::Custom(type_name) => Some(type_name),
+        }
+    }
+    ///TOML值能否作为该类型的值，容器类型逐个检查其中的元素
+    fn value_matches(type_spec: &AethaumType, value: &toml::Value) -> bool {
+        match (type_spec, value) {
+            (AethaumType::Primitive(primitive_type), value) => match (primitive_type, value) {
+                (PrimitiveType::Bool, toml::Value::Boolean(_)) => true,
+                (PrimitiveType::Float | PrimitiveType::F32, toml::Value::Float(_)) => true,
+                (PrimitiveType::Str, toml::Value::String(_)) => true,
+                (integer, toml::Value::Integer(i)) => integer.fits_integer(*i),
+                (PrimitiveType::Vec2, value) => vector_components(value, 2).is_some(),
+                (PrimitiveType::Vec3, value) => vector_components(value, 3).is_some(),
+                (PrimitiveType::Color, value) => color_components(value).is_some(),
+                _ => false,
+            },
+            (AethaumType::List(element), toml::Value::Array(array)) => {
+                array.iter().all(|value| Self::value_matches(element, value))
             }
-            AethaumType::Custom(_) => unreachable!("Custom type not supported in this version")
+            (AethaumType::Array(element, len), toml::Value::Array(array)) => {
+                array.len() == *len && array.iter().all(|value| Self::value_matches(element, value))
+            }
+            (AethaumType::Map(element), toml::Value::Table(table)) => {
+                table.values().all(|value| Self::value_matches(element, value))
+            }
+            //TOML没有空值，给出的默认值即为 Some
+            (AethaumType::Option(inner), value) => Self::value_matches(inner, value),
+            //自定义类型无法检查默认值
+            _ => false,
         }
     }
 }
@@ -47,9 +109,15 @@ pub trait TypeCheckable {
 }
 impl TypeCheckable for ComponentField {
     fn check_type(&self) -> Result<(), TypeCheckError> {
+        TypeChecker::check_known_type(&self.type_spec)?;
         TypeChecker::check_type_value_match(&self.type_spec, &self.default_value)
     }
 }
+impl TypeCheckable for EventField {
+    fn check_type(&self) -> Result<(), TypeCheckError> {
+        TypeChecker::check_known_type(&self.type_spec)
+    }
+}
 impl TypeCheckable for EcsModule {
     fn check_type(&self) -> Result<(), TypeCheckError> {
         let mut errors = Vec::new();
@@ -64,6 +132,13 @@ impl TypeCheckable for EcsModule {
                 }
             }
         };
+        for event in self.events.iter().flatten() {
+            for field in event.fields.iter().flatten() {
+                if let Err(e) = field.check_type() {
+                    errors.push(e);
+                }
+            }
+        }
         if !errors.is_empty() {
             return Err(TypeCheckError::raise_multiple(errors));
         }
@@ -72,8 +147,8 @@ impl TypeCheckable for EcsModule {
 }
 #[cfg(test)]
 mod tests {
-    use crate::toml_parser::parsed::Component;
-    use crate::toml_parser::raw::{RawComponentFile, RawTomlCodeFile};
+    use crate::toml_parser::parsed::{Component, Event, SourceFile};
+    use crate::toml_parser::raw::{RawComponentFile, RawEventFile, RawTomlCodeFile};
     use super::*;
     #[test]
     fn test_type_check_passed() {
@@ -119,7 +194,7 @@ mod tests {
         "#;
         let components = toml::from_str::<RawComponentFile>(toml_file).unwrap().into_pieces();
         let components = components.into_iter()
-            .map(|c| c.into())
+            .map(|c| Component::try_from(c).unwrap())
             .collect::<Vec<Component>>();
         for comp in components {
             for fields in comp.fields.unwrap() {
@@ -171,7 +246,7 @@ mod tests {
         "#;
         let components = toml::from_str::<RawComponentFile>(toml_file).unwrap().into_pieces();
         let components = components.into_iter()
-            .map(|c| c.into())
+            .map(|c| Component::try_from(c).unwrap())
             .collect::<Vec<Component>>();
         let mut errors = Vec::new();
         for comp in components {
@@ -183,4 +258,81 @@ mod tests {
         }
         assert_eq!(errors.len(), 2);
     }
+    #[test]
+    fn test_type_mismatch_points_at_default() {
+        let toml_file = "[[components]]\nname = \"Health\"\n\n[[components.fields]]\nname = \"value\"\ntype = \"float\"\ndefault = \"a\"\n";
+        let source = SourceFile::new("modules/test/components/health.toml", toml_file);
+        let component = toml::from_str::<RawComponentFile>(toml_file).unwrap().into_pieces().pop().unwrap();
+        let component = Component::try_from(component).unwrap().with_source(&source);
+        let error = component.fields.unwrap()[0].check_type().unwrap_err();
+        let TypeCheckError::TypeMismatch { src, span, .. } = error else {
+            panic!("{}", error);
+        };
+        assert_eq!(src.unwrap().name(), "modules/test/components/health.toml");
+        let span = span.unwrap();
+        assert_eq!(&toml_file[span.offset()..span.offset() + span.len()], "\"a\"");
+    }
+    #[test]
+    fn test_unknown_types_point_at_the_type() {
+        let toml_file = "[[components]]\nname = \"Bag\"\n\n[[components.fields]]\nname = \"items\"\ntype = \"list<Item>\"\n";
+        let source = SourceFile::new("modules/test/components/bag.toml", toml_file);
+        let component = toml::from_str::<RawComponentFile>(toml_file).unwrap().into_pieces().pop().unwrap();
+        let component = Component::try_from(component).unwrap().with_source(&source);
+        let error = component.fields.unwrap()[0].check_type().unwrap_err();
+        let TypeCheckError::UnknownType { type_name, src, span } = error else {
+            panic!("{}", error);
+        };
+        assert_eq!(type_name, "Item");
+        assert_eq!(src.unwrap().name(), "modules/test/components/bag.toml");
+        let span = span.unwrap();
+        assert_eq!(&toml_file[span.offset()..span.offset() + span.len()], "\"list<Item>\"");
+
+        let toml_file = "[[events]]\nname = \"Picked\"\n\n[[events.fields]]\nname = \"item\"\ntype = \"option<Item>\"\n";
+        let event = toml::from_str::<RawEventFile>(toml_file).unwrap().into_pieces().pop().unwrap();
+        let event = Event::try_from(event).unwrap();
+        assert!(matches!(event.fields.unwrap()[0].check_type(), Err(TypeCheckError::UnknownType { .. })));
+    }
+    #[test]
+    fn test_type_check_extended_primitives() {
+        let field = |type_str: &str, default: &str| {
+            let toml_file = format!("[[components]]\nname = \"C\"\n\n[[components.fields]]\nname = \"f\"\ntype = \"{type_str}\"\ndefault = {default}\n");
+            let component = toml::from_str::<RawComponentFile>(&toml_file).unwrap().into_pieces().pop().unwrap();
+            Component::try_from(component).unwrap().fields.unwrap().pop().unwrap()
+        };
+        for (type_str, default) in [
+            ("f32", "1.5"), ("i32", "-7"), ("u32", "4000000000"), ("u8", "255"), ("u64", "0"),
+            ("vec2", "[1, 0.5]"), ("vec3", "[0.0, 2.0, -1.0]"),
+            ("color", "\"#ff8800\""), ("color", "\"#ff880080\""), ("color", "[0.0, 1.0, 0.5]"), ("color", "[0.0, 1.0, 0.5, 0.2]"),
+        ] {
+            field(type_str, default).check_type().unwrap();
+        }
+        for (type_str, default) in [
+            ("f32", "1"), ("u8", "256"), ("u32", "-1"), ("i32", "3000000000"), ("Entity", "0"),
+            ("vec2", "[1.0]"), ("vec3", "[1.0, \"a\", 2.0]"),
+            ("color", "\"red\""), ("color", "\"#ff88\""), ("color", "[2.0, 0.0, 0.0]"),
+        ] {
+            assert!(field(type_str, default).check_type().is_err(), "{type_str} = {default}");
+        }
+    }
+    #[test]
+    fn test_type_check_containers() {
+        let field = |type_str: &str, default: &str| {
+            let toml_file = format!("[[components]]\nname = \"C\"\n\n[[components.fields]]\nname = \"f\"\ntype = \"{type_str}\"\ndefault = {default}\n");
+            let component = toml::from_str::<RawComponentFile>(&toml_file).unwrap().into_pieces().pop().unwrap();
+            Component::try_from(component).unwrap().fields.unwrap().pop().unwrap()
+        };
+        for (type_str, default) in [
+            ("list<str>", "[\"a\", \"b\"]"), ("list<int>", "[]"), ("list<vec2>", "[[0, 1], [2.5, 3]]"),
+            ("map<str, u8>", "{ a = 1, b = 255 }"), ("option<float>", "1.0"), ("array<int, 3>", "[1, 2, 3]"),
+            ("map<str, list<option<str>>>", "{ a = [\"x\"], b = [] }"),
+        ] {
+            field(type_str, default).check_type().unwrap();
+        }
+        for (type_str, default) in [
+            ("list<str>", "[\"a\", 1]"), ("list<int>", "1"), ("map<str, u8>", "{ a = 256 }"),
+            ("option<float>", "\"a\""), ("array<int, 3>", "[1, 2]"), ("map<str, int>", "[1]"),
+        ] {
+            assert!(field(type_str, default).check_type().is_err(), "{type_str} = {default}");
+        }
+    }
 }
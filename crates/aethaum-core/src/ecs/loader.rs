@@ -3,23 +3,25 @@ use std::path::{Path, PathBuf};
 use serde::Deserialize;
 use smart_string::SmartString;
 use crate::ecs::module::{AethaumProject, EcsModule, EcsModuleTree};
-use crate::toml_parser::parsed::{Component, EntityProto, Event, System, World};
+use crate::toml_parser::parsed::{Component, EntityProto, Event, SourceFile, System, World};
 use crate::toml_parser::raw::{RawComponent, RawComponentFile, RawEntityProto, RawEntityProtoFile, RawEvent, RawEventFile, RawSystem, RawSystemFile, RawTomlCodeFile, RawWorld};
 use itertools::Itertools;
 use one_or_many::OneOrMany;
 use thiserror::Error;
+use miette::Diagnostic;
+use crate::toml_parser::error::ParseError;
 
-#[derive(Debug,Error)]
+#[derive(Debug, Error, Diagnostic)]
 pub enum ModuleFileLoaderError {
     #[error("Failed to load module file, {0}")]
     IoError(#[from] std::io::Error),
-    #[error("Illegal toml file, {0}")]
-    IllegalToml(#[from] toml::de::Error),
-    #[error("fail to convert: {0}")]
-    ConversionError(#[from] anyhow::Error),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Parse(#[from] ParseError),
     #[error("Multiple errors occurred during loading:\n{}",
         .errors.iter().map(|e| format!("  - {}", e)).collect::<Vec<_>>().join("\n"))]
     Multiple {
+        #[related]
         errors: Vec<ModuleFileLoaderError>
     }
 }
@@ -28,13 +30,14 @@ impl ModuleFileLoaderError {
         ModuleFileLoaderError::Multiple { errors }
     }
 }
-#[derive(Debug, Error)]
+#[derive(Debug, Error, Diagnostic)]
 pub enum ProjectLoaderError {
     #[error("world.toml not found in the project.")]
     MissingWorld,
     #[error("module '{0}' not found")]
     MissingModule(SmartString),
     #[error("{0}")]
+    #[diagnostic(transparent)]
     FileError(#[from] ModuleFileLoaderError)
 }
 impl From<std::io::Error> for ProjectLoaderError {
@@ -42,14 +45,9 @@ impl From<std::io::Error> for ProjectLoaderError {
         ProjectLoaderError::FileError(ModuleFileLoaderError::IoError(error))
     }
 }
-impl From<toml::de::Error> for ProjectLoaderError {
-    fn from(error: toml::de::Error) -> Self {
-        ProjectLoaderError::FileError(ModuleFileLoaderError::IllegalToml(error))
-    }
-}
-impl From<anyhow::Error> for ProjectLoaderError {
-    fn from(error: anyhow::Error) -> Self {
-        ProjectLoaderError::FileError(ModuleFileLoaderError::ConversionError(error))
+impl From<ParseError> for ProjectLoaderError {
+    fn from(error: ParseError) -> Self {
+        ProjectLoaderError::FileError(ModuleFileLoaderError::Parse(error))
     }
 }
 impl ProjectLoaderError {
@@ -68,17 +66,19 @@ trait Loadable: Sized {
     type RawFile: for<'de> Deserialize<'de>;
     type RawType: for<'de> Deserialize<'de>;
     type Error: Into<ProjectLoaderError>;
-    fn try_load(path: &Path) -> Result<OneOrMany<Self>, Self::Error>;
+    ///`source_path` 为报错时显示的文件路径
+    fn try_load(path: &Path, source_path: &Path) -> Result<OneOrMany<Self>, Self::Error>;
 }
 impl Loadable for System {
     type RawFile = RawSystemFile;
     type RawType = RawSystem;
     type Error = ModuleFileLoaderError;
-    fn try_load(path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
-        let file_content = load_file(path)?;
-        let raw_system_file: RawSystemFile = toml::from_str(&file_content)?;
+    fn try_load(path: &Path, source_path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
+        let (raw_system_file, source) = load_toml::<RawSystemFile>(path, source_path)?;
         let raw_system = raw_system_file.into_pieces();
-        let system = System::try_from(raw_system)?;
+        let system = System::try_from(raw_system)
+            .map_err(|error| error.with_source(&source))?
+            .with_source(source);
         Ok(OneOrMany::One(Box::new(system)))
     }
 }
@@ -86,12 +86,11 @@ impl Loadable for Component {
     type RawFile = RawComponentFile;
     type RawType = RawComponent;
     type Error = ModuleFileLoaderError;
-    fn try_load(path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
-        let file_content = load_file(path)?;
-        let raw_component_file: RawComponentFile = toml::from_str(&file_content)?;
-        let raw_components = raw_component_file.into_pieces();
+    fn try_load(path: &Path, source_path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
+        let (raw_component_file, source) = load_toml::<RawComponentFile>(path, source_path)?;
+        let components: Vec<Component> = convert_pieces(raw_component_file.into_pieces(), &source)?;
         Ok(OneOrMany::Many(
-            raw_components.into_iter().map(|raw_component| Component::from(raw_component)).collect()
+            components.into_iter().map(|component| component.with_source(&source)).collect()
         ))
     }
 }
@@ -99,12 +98,11 @@ impl Loadable for Event {
     type RawFile = RawEventFile;
     type RawType = RawEvent;
     type Error = ModuleFileLoaderError;
-    fn try_load(path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
-        let file_content = load_file(path)?;
-        let raw_event_file: RawEventFile = toml::from_str(&file_content)?;
-        let raw_events = raw_event_file.into_pieces();
+    fn try_load(path: &Path, source_path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
+        let (raw_event_file, source) = load_toml::<RawEventFile>(path, source_path)?;
+        let events: Vec<Event> = convert_pieces(raw_event_file.into_pieces(), &source)?;
         Ok(OneOrMany::Many(
-            raw_events.into_iter().map(|raw_event| Event::from(raw_event)).collect()
+            events.into_iter().map(|event| event.with_source(&source)).collect()
         ))
     }
 }
@@ -112,52 +110,43 @@ impl Loadable for EntityProto {
     type RawFile = RawEntityProtoFile;
     type RawType = RawEntityProto;
     type Error = ModuleFileLoaderError;
-    fn try_load(path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
-        let file_content = load_file(path)?;
-        let raw_entity_proto_file: RawEntityProtoFile = toml::from_str(&file_content)?;
-        let raw_entity_protos = raw_entity_proto_file.into_pieces();
-
-        let mut errors = Vec::new();
-        let mut entity_protos = Vec::new();
-        for raw_entity_proto in raw_entity_protos {
-            match EntityProto::try_from(raw_entity_proto) {
-                Ok(entity_proto) => entity_protos.push(entity_proto),
-                Err(error) => errors.push(error.into())
-            }
-        }
-        if !errors.is_empty() {
-            if errors.len() == 1 {
-                return Err(errors.pop().unwrap());
-            }else {
-                return Err(ModuleFileLoaderError::raise_multiple(errors));
-            }
-        }
-        Ok(OneOrMany::Many(entity_protos))
+    fn try_load(path: &Path, source_path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
+        let (raw_entity_proto_file, source) = load_toml::<RawEntityProtoFile>(path, source_path)?;
+        let entity_protos: Vec<EntityProto> = convert_pieces(raw_entity_proto_file.into_pieces(), &source)?;
+        Ok(OneOrMany::Many(
+            entity_protos.into_iter().map(|entity_proto| entity_proto.with_source(&source)).collect()
+        ))
     }
 }
 impl Loadable for World {
     type RawFile = RawWorld;
     type RawType = RawWorld;
     type Error = ProjectLoaderError;
-    fn try_load(path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
-        let file_content = load_file(path)?;
-        let raw_world: RawWorld = toml::from_str(&file_content)?;
-        let world = World::from(raw_world);
+    fn try_load(path: &Path, source_path: &Path) -> Result<OneOrMany<Self>, Self::Error> {
+        let (raw_world, source) = load_toml::<RawWorld>(path, source_path)?;
+        let world = World::try_from(raw_world).map_err(|error| error.with_source(&source))?;
         Ok(OneOrMany::One(Box::new(world)))
     }
 }
 
 pub struct ModuleFileLoader {
     base_path: PathBuf,
-    module_name: SmartString
+    module_name: SmartString,
+    project_root: Option<PathBuf>
 }
 impl ModuleFileLoader {
     pub fn new(base_path: PathBuf, module_name: SmartString) -> Self {
         ModuleFileLoader {
             base_path,
-            module_name
+            module_name,
+            project_root: None
         }
     }
+    ///记录的源文件路径改为相对于项目根目录
+    pub fn with_project_root(mut self, project_root: PathBuf) -> Self {
+        self.project_root = Some(project_root);
+        self
+    }
     pub fn load(self) -> Result<EcsModule, ModuleFileLoaderError> {
         let systems = self.load_parts(self.base_path.join("systems"))?;
         let components = self.load_parts(self.base_path.join("components"))?;
@@ -184,7 +173,10 @@ impl ModuleFileLoader {
         let mut errors = Vec::new();
 
         for path in paths {
-            match T::try_load(path.as_path()) {
+            let source_path = self.project_root.as_ref()
+                .and_then(|project_root| path.strip_prefix(project_root).ok())
+                .unwrap_or(&path);
+            match T::try_load(path.as_path(), source_path) {
                 Ok(part) => parts.push(part),
                 Err(error) => errors.push(error),
             }
@@ -201,6 +193,35 @@ impl ModuleFileLoader {
         Ok(Some(parts.into_iter().flatten().collect())) //TODO: try reduce the collect call
     }
 }
+///读取并反序列化TOML文件，TOML错误附带文件内容以便标注位置
+fn load_toml<R: for<'de> Deserialize<'de>>(path: &Path, source_path: &Path) -> Result<(R, SourceFile), ModuleFileLoaderError> {
+    let source = SourceFile::new(source_path, load_file(path)?);
+    let raw = toml::from_str(&source.content)
+        .map_err(|error| ParseError::from(error).with_source(&source))?;
+    Ok((raw, source))
+}
+///逐个转换文件中的定义，收集所有转换错误
+fn convert_pieces<R, T>(raw_pieces: Vec<R>, source: &SourceFile) -> Result<Vec<T>, ModuleFileLoaderError>
+where
+    T: TryFrom<R, Error = ParseError>,
+{
+    let mut errors = Vec::new();
+    let mut pieces = Vec::new();
+    for raw_piece in raw_pieces {
+        match T::try_from(raw_piece) {
+            Ok(piece) => pieces.push(piece),
+            Err(error) => errors.push(error.with_source(source).into())
+        }
+    }
+    if !errors.is_empty() {
+        if errors.len() == 1 {
+            return Err(errors.pop().unwrap());
+        }else {
+            return Err(ModuleFileLoaderError::raise_multiple(errors));
+        }
+    }
+    Ok(pieces)
+}
 fn load_file(path: impl AsRef<Path>) -> Result<String,std::io::Error> {
     if path.as_ref().exists() {
         std::fs::read_to_string(path)
@@ -233,7 +254,7 @@ impl ProjectLoader {
         }
     }
     pub fn load(self) -> Result<AethaumProject, ProjectLoaderError> {
-        let world_toml = World::try_load(self.base_path.join("world.toml").as_path())?;
+        let world_toml = World::try_load(self.base_path.join("world.toml").as_path(), Path::new("world.toml"))?;
         let world_toml = match world_toml {
             OneOrMany::One(world_toml) => world_toml,
             _ => unreachable!("World::try_load always yield OneOrMany::One"),
@@ -242,7 +263,9 @@ impl ProjectLoader {
         let mut errors = Vec::new();
         let mut modules = Vec::new();
         for (module_name, module_base_path) in world_toml.modules.modules.iter() {
-            match ModuleFileLoader::new(self.base_path.join(module_base_path), module_name.clone()).load() {
+            let loader = ModuleFileLoader::new(self.base_path.join(module_base_path), module_name.clone())
+                .with_project_root(self.base_path.clone());
+            match loader.load() {
                 Ok(module) => modules.push(module),
                 Err(error) => errors.push(error)
             }
@@ -260,16 +283,15 @@ impl ProjectLoader {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::Path;
-    use std::fs;
+    use crate::test_utils::test_project_root;
     #[test]
     fn test_load_file() {
-        let string = load_file(Path::new(r#"D:\Aethaum\test_project\world.toml"#)).unwrap();
+        let string = load_file(test_project_root().join("world.toml")).unwrap();
         println!("{}", string)
     }
     #[test]
     fn test_list_dir() {
-        let base_path = Path::new(r#"D:\Aethaum\test_project\modules\explore"#);
+        let base_path = test_project_root().join("modules").join("explore");
         let paths = list_dir(base_path.join("components")).unwrap();
         for path in paths {
             println!("{}", path.display());
@@ -277,14 +299,45 @@ mod tests {
     }
     #[test]
     fn test_load_single_module() {
-        let base_path = Path::new(r#"D:\Aethaum\test_project\modules\explore"#);
+        let base_path = test_project_root().join("modules").join("explore");
         let module = ModuleFileLoader::new(base_path.to_path_buf(), "explore".into()).load().unwrap();
         assert_eq!(module.name, "explore")
     }
     #[test]
+    fn test_load_system_records_source_lines() {
+        let module = ModuleFileLoader::new(test_project_root().join("modules").join("explore"), "explore".into())
+            .with_project_root(test_project_root())
+            .load()
+            .unwrap();
+        let system = &module.systems.unwrap()[0];
+        let source = system.source.as_ref().unwrap();
+        assert_eq!(source.path, Path::new("modules/explore/systems/health.toml"));
+        let update = system.update.as_ref().unwrap();
+        // 多行字符串从 ''' 的下一行开始
+        assert_eq!(system.script_line(update.condition.as_ref().unwrap()), Some(25));
+        assert_eq!(system.script_line(update.logic.as_ref().unwrap()), Some(31));
+        assert_eq!(system.script_line(system.event_handlers[0].logic.as_ref().unwrap()), Some(41));
+        assert_eq!(system.script_line(system.event_handlers[1].logic.as_ref().unwrap()), None);
+    }
+    #[test]
     fn test_load_project() {
-        let base_path = Path::new(r#"D:\Aethaum\test_project"#);
+        let base_path = test_project_root();
         let project = ProjectLoader::new(base_path.to_path_buf()).load().unwrap();
         assert_eq!(project.world.normal.name, "MyAIWorld");
     }
+    #[test]
+    fn test_invalid_crate_name_points_at_the_name() {
+        let dir = std::env::temp_dir().join("aethaum_test_invalid_crate_name");
+        fs::create_dir_all(&dir).unwrap();
+        let content = "[world]\nname = \"MyAIWorld\"\nversion = \"0.1.0\"\nauthor = \"Someone\"\n\n[modules]\n\n[build]\ncrate_name = \"my world\"\n";
+        fs::write(dir.join("world.toml"), content).unwrap();
+        let error = ProjectLoader::new(dir).load().err().unwrap();
+        let ProjectLoaderError::FileError(ModuleFileLoaderError::Parse(error)) = error else {
+            panic!("expected a parse error, got {error:?}");
+        };
+        assert!(matches!(error, ParseError::InvalidCrateName { .. }), "{error:?}");
+        assert_eq!(error.path(), Some("world.toml"));
+        let span = error.span().unwrap();
+        assert_eq!(&content[span.offset()..span.offset() + span.len()], "\"my world\"");
+    }
 }
\ No newline at end of file
@@ -1,11 +1,7 @@
 use std::collections::HashMap;
-use std::fs;
-use std::path::{Path, PathBuf};
-use serde::Deserialize;
+use std::path::PathBuf;
 use smart_string::SmartString;
-use thiserror::Error;
 use crate::toml_parser::parsed::{Component, ComponentRef, EntityProto, EntityProtoRef, Event, EventRef, System, SystemRef, World};
-use crate::toml_parser::raw::{RawComponent, RawEntityProto, RawEvent, RawSystem};
 
 #[derive(Debug)]
 #[derive(Clone)]
@@ -134,25 +130,25 @@ impl EcsModule {
         self.systems = Some(systems);
         self
     }
-    pub fn with_option_components(mut self, components: Option<Vec<Component>>) -> Self {
+    pub fn with_option_components(self, components: Option<Vec<Component>>) -> Self {
         match components {
             Some(components) => self.with_components(components),
             None => self
         }
     }
-    pub fn with_option_events(mut self, events: Option<Vec<Event>>) -> Self {
+    pub fn with_option_events(self, events: Option<Vec<Event>>) -> Self {
         match events {
             Some(events) => self.with_events(events),
             None => self
         }
     }
-    pub fn with_option_entity_protos(mut self, entity_protos: Option<Vec<EntityProto>>) -> Self {
+    pub fn with_option_entity_protos(self, entity_protos: Option<Vec<EntityProto>>) -> Self {
         match entity_protos {
             Some(entity_protos) => self.with_entity_protos(entity_protos),
             None => self
         }
     }
-    pub fn with_option_systems(mut self, systems: Option<Vec<System>>) -> Self {
+    pub fn with_option_systems(self, systems: Option<Vec<System>>) -> Self {
         match systems {
             Some(systems) => self.with_systems(systems),
             None => self
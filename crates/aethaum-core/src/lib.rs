@@ -1,18 +1,13 @@
-mod toml_parser;
-mod ecs;
-mod code_generator;
-
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+pub mod toml_parser;
+pub mod ecs;
+pub mod code_generator;
 
 #[cfg(test)]
-mod tests {
-    use super::*;
+pub(crate) mod test_utils {
+    use std::path::PathBuf;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    ///仓库中的示例项目，供测试使用
+    pub fn test_project_root() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../test_project")
     }
 }
@@ -1,8 +1,7 @@
 use std::marker::PhantomData;
 use one_or_many::OneOrMany;
 use crate::toml_parser::parsed::TomlCode;
-use anyhow::Result;
-use crate::toml_parser;
+use crate::toml_parser::error::ParseError;
 use crate::toml_parser::raw::RawTomlCodeFile;
 
 pub mod raw;
@@ -22,7 +21,7 @@ impl<T: TomlCode> Parser<T> {
             _type_marker: PhantomData
         }
     }
-    pub fn parse(&mut self) -> Result<OneOrMany<T>>
+    pub fn parse(&mut self) -> Result<OneOrMany<T>, ParseError>
     {
         // 解析 TOML 文件为 RawFile 类型
         let raw_file: T::RawFile = toml::from_str(&self.raw_content)?;
@@ -337,5 +336,46 @@ mod tests {
 
         assert!(result.is_ok()); // 即使没有fields也应该解析成功
     }
-}
 
+    /// 测试容器类型的解析，容器可以嵌套，类型参数间可以有空白
+    #[test]
+    fn test_parse_container_types() {
+        use crate::toml_parser::parsed::{AethaumType, PrimitiveType};
+        let parsed = AethaumType::from_type_str("map<str, list< option<vec2> >>").unwrap();
+        assert_eq!(parsed, AethaumType::Map(Box::new(AethaumType::List(Box::new(
+            AethaumType::Option(Box::new(AethaumType::Primitive(PrimitiveType::Vec2)))
+        )))));
+        assert_eq!(parsed.to_string(), "map<str, list<option<vec2>>>");
+        for type_str in ["list<u8>", "option<Entity>", "array<array<f32, 3>, 16>", "map<str, color>"] {
+            assert_eq!(AethaumType::from_type_str(type_str).unwrap().to_string(), type_str);
+        }
+        assert_eq!(AethaumType::from_type_str("Item").unwrap(), AethaumType::Custom("Item".into()));
+    }
+
+    /// 测试非法的类型表达式，错误指向类型字符串
+    #[test]
+    fn test_parse_invalid_container_types() {
+        use crate::toml_parser::parsed::AethaumType;
+        for (type_str, reason) in [
+            ("list<int", "expected `>` after the type arguments"),
+            ("list", "container types need type arguments, e.g. `list<int>`"),
+            ("map<int, str>", "map keys must be `str`"),
+            ("array<int>", "expected `,` before the length of `array`"),
+            ("array<int, -1>", "array length must be a non-negative integer"),
+            ("list<>", "expected a type name"),
+            ("option<int> x", "unexpected characters after the type"),
+            ("5", "type names must be identifiers"),
+            ("list<5>", "type names must be identifiers"),
+            ("array<fn, 2>", "type names must be identifiers"),
+        ] {
+            match AethaumType::from_type_str(type_str) {
+                Err(ParseError::InvalidType { reason: actual, .. }) => assert_eq!(actual, reason, "{type_str}"),
+                other => panic!("{type_str}: {other:?}"),
+            }
+        }
+        let toml_content = "[[components]]\nname = \"Bag\"\n\n[[components.fields]]\nname = \"items\"\ntype = \"list<str\"\n";
+        let error = Parser::<Component>::new(toml_content.to_string()).parse().unwrap_err();
+        let span = error.span().unwrap();
+        assert_eq!(&toml_content[span.offset()..span.offset() + span.len()], "\"list<str\"");
+    }
+}
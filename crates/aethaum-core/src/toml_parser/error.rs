@@ -0,0 +1,192 @@
+use std::ops::Range;
+use std::sync::Arc;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use smart_string::SmartString;
+use thiserror::Error;
+use crate::toml_parser::parsed::SourceFile;
+
+///出错的TOML文件，由加载器在读取文件后填入
+pub type ParseSource = Option<Arc<NamedSource<Arc<str>>>>;
+
+///报错时标注的位置：所在的TOML文件与字节范围
+#[derive(Debug, Clone, Default)]
+pub struct SourceLocation {
+    pub src: ParseSource,
+    pub span: Option<SourceSpan>,
+}
+impl SourceLocation {
+    pub fn new(source: Option<&SourceFile>, span: Option<Range<usize>>) -> Self {
+        SourceLocation {
+            src: source.map(SourceFile::named_source),
+            span: span.map(Into::into),
+        }
+    }
+}
+
+///解析TOML定义时的错误，带有文件与出错位置的字节范围，可由 miette 渲染为标注的源码片段
+#[derive(Debug, Error, Diagnostic)]
+pub enum ParseError {
+    #[error("invalid TOML: {message}")]
+    #[diagnostic(code(aethaum::parse::toml))]
+    Toml {
+        message: String,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("{message}")]
+        span: Option<SourceSpan>,
+    },
+    #[error("{field} must be {expected}")]
+    #[diagnostic(code(aethaum::parse::invalid_value))]
+    InvalidValue {
+        field: &'static str,
+        expected: &'static str,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("expected {expected}")]
+        span: Option<SourceSpan>,
+    },
+    #[error("invalid {kind} reference `{reference}`: {reason}")]
+    #[diagnostic(code(aethaum::parse::invalid_ref), help("use `Name` or `module::Name`"))]
+    InvalidRef {
+        kind: &'static str,
+        reference: SmartString,
+        reason: &'static str,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("{reason}")]
+        span: Option<SourceSpan>,
+    },
+    #[error("invalid type `{type_str}`: {reason}")]
+    #[diagnostic(code(aethaum::parse::invalid_type), help("containers are `list<T>`, `map<str, T>`, `option<T>` and `array<T, N>`"))]
+    InvalidType {
+        type_str: SmartString,
+        reason: &'static str,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("{reason}")]
+        span: Option<SourceSpan>,
+    },
+    #[error("invalid crate name `{name}`: {reason}")]
+    #[diagnostic(code(aethaum::parse::invalid_crate_name), help("crate names follow Cargo package names, e.g. `my_world` or `my-world`"))]
+    InvalidCrateName {
+        name: SmartString,
+        reason: &'static str,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("{reason}")]
+        span: Option<SourceSpan>,
+    },
+    #[error("`{embed}` and `{file}` cannot be specified at the same time")]
+    #[diagnostic(code(aethaum::parse::conflicting_script), help("keep either the embedded script or `{file}`"))]
+    ConflictingScript {
+        embed: &'static str,
+        file: &'static str,
+        #[source_code]
+        src: Option<Arc<NamedSource<Arc<str>>>>,
+        #[label("conflicts with `{embed}`")]
+        span: Option<SourceSpan>,
+    },
+}
+impl ParseError {
+    pub fn raise_invalid_value(field: &'static str, expected: &'static str) -> Self {
+        ParseError::InvalidValue { field, expected, src: None, span: None }
+    }
+    pub fn raise_invalid_ref(kind: &'static str, reference: SmartString, reason: &'static str) -> Self {
+        ParseError::InvalidRef { kind, reference, reason, src: None, span: None }
+    }
+    pub fn raise_invalid_type(type_str: SmartString, reason: &'static str) -> Self {
+        ParseError::InvalidType { type_str, reason, src: None, span: None }
+    }
+    pub fn raise_invalid_crate_name(name: SmartString, reason: &'static str) -> Self {
+        ParseError::InvalidCrateName { name, reason, src: None, span: None }
+    }
+    pub fn raise_conflicting_script(embed: &'static str, file: &'static str) -> Self {
+        ParseError::ConflictingScript { embed, file, src: None, span: None }
+    }
+    ///标注出错的字节范围，已有范围时保留更内层的位置
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        let (_, current) = self.location_mut();
+        current.get_or_insert(span.into());
+        self
+    }
+    ///附上出错的TOML文件
+    pub fn with_source(mut self, source: &SourceFile) -> Self {
+        let (src, _) = self.location_mut();
+        *src = Some(source.named_source());
+        self
+    }
+    ///出错的文件路径，未附上文件时为 None
+    pub fn path(&self) -> Option<&str> {
+        self.location().0.as_ref().map(|src| src.name())
+    }
+    pub fn span(&self) -> Option<SourceSpan> {
+        *self.location().1
+    }
+    fn location(&self) -> (&ParseSource, &Option<SourceSpan>) {
+        match self {
+            ParseError::Toml { src, span, .. }
+            | ParseError::InvalidValue { src, span, .. }
+            | ParseError::InvalidRef { src, span, .. }
+            | ParseError::InvalidType { src, span, .. }
+            | ParseError::InvalidCrateName { src, span, .. }
+            | ParseError::ConflictingScript { src, span, .. } => (src, span),
+        }
+    }
+    fn location_mut(&mut self) -> (&mut ParseSource, &mut Option<SourceSpan>) {
+        match self {
+            ParseError::Toml { src, span, .. }
+            | ParseError::InvalidValue { src, span, .. }
+            | ParseError::InvalidRef { src, span, .. }
+            | ParseError::InvalidType { src, span, .. }
+            | ParseError::InvalidCrateName { src, span, .. }
+            | ParseError::ConflictingScript { src, span, .. } => (src, span),
+        }
+    }
+}
+impl From<toml::de::Error> for ParseError {
+    fn from(error: toml::de::Error) -> Self {
+        ParseError::Toml {
+            message: error.message().to_string(),
+            src: None,
+            span: error.span().map(Into::into),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml_parser::parsed::System;
+    use crate::toml_parser::raw::RawSystem;
+
+    fn parse_system(content: &str) -> ParseError {
+        let source = SourceFile::new("modules/test/systems/test.toml", content);
+        let raw: RawSystem = toml::from_str(content).unwrap();
+        System::try_from(raw).unwrap_err().with_source(&source)
+    }
+    #[test]
+    fn test_conversion_error_points_at_value() {
+        let content = "[normal]\nname = \"Test\"\npriority = \"high\"\n";
+        let error = parse_system(content);
+        assert!(matches!(error, ParseError::InvalidValue { field: "priority", .. }));
+        assert_eq!(error.path(), Some("modules/test/systems/test.toml"));
+        let span = error.span().unwrap();
+        assert_eq!(&content[span.offset()..span.offset() + span.len()], "\"high\"");
+    }
+    #[test]
+    fn test_ref_error_points_at_reference() {
+        let content = "[normal]\nname = \"Test\"\n\n[[queries]]\nname = \"q\"\ncomponents_include = [\"a::b::C\"]\n";
+        let error = parse_system(content);
+        assert_eq!(error.to_string(), "invalid component reference `a::b::C`: nested module is currently not allowed");
+        let span = error.span().unwrap();
+        assert_eq!(&content[span.offset()..span.offset() + span.len()], "\"a::b::C\"");
+    }
+    #[test]
+    fn test_toml_error_keeps_span() {
+        let content = "[normal]\nname = \n";
+        let source = SourceFile::new("test.toml", content);
+        let error = ParseError::from(toml::from_str::<RawSystem>(content).unwrap_err()).with_source(&source);
+        assert!(matches!(error, ParseError::Toml { .. }));
+        assert!(error.span().is_some());
+        assert_eq!(error.path(), Some("test.toml"));
+    }
+}
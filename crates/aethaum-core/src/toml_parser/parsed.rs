@@ -1,20 +1,135 @@
 use std::collections::HashMap;
-use std::fmt::Display;
+use std::ops::Range;
 use std::path::PathBuf;
-use crate::toml_parser::raw::{RawComponent, RawComponentField, RawComponentFile, RawEntityProto, RawEntityProtoFile, RawEvent, RawEventField, RawEventFile, RawSystem, RawSystemEventHandler, RawSystemFile, RawSystemNormal, RawSystemQuery, RawSystemUpdate, RawTomlCodeFile, RawWorld, RawWorldBuild, RawWorldCargo, RawWorldModules, RawWorldNormal};
+use std::sync::Arc;
+use crate::toml_parser::raw::{RawComponent, RawComponentField, RawComponentFile, RawEntityProto, RawEntityProtoFile, RawEvent, RawEventField, RawEventFile, RawSystem, RawSystemEventHandler, RawSystemFile, RawSystemNormal, RawSystemQuery, RawSystemUpdate, RawTomlCodeFile, RawWorld, RawWorldBuild, RawWorldBuildTarget, RawWorldCargo, RawWorldModules, RawWorldNormal};
 use smart_string::SmartString;
 use std::time::Duration;
-use anyhow::Error;
+use crate::toml_parser::error::{ParseError, SourceLocation};
 use itertools::Itertools;
+use miette::NamedSource;
 use one_or_many::OneOrMany;
-use proc_macro2::Span;
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
 use syn::Ident;
+use toml::Spanned;
 
 #[derive(Debug,Clone,PartialEq)]
 pub enum LuaScript {
-    Embed(SmartString),
+    Embed(EmbedLuaScript),
     File(PathBuf),
 }
+///内嵌于TOML的Lua代码
+#[derive(Debug,Clone,PartialEq)]
+pub struct EmbedLuaScript {
+    pub source: SmartString,
+    ///字符串(含引号)在TOML文件中的字节范围
+    pub span: Option<Range<usize>>,
+}
+impl EmbedLuaScript {
+    ///代码第一行在TOML文件中的行号(从1开始)
+    ///
+    ///多行字符串开头紧跟的换行会被TOML去掉，此时代码从下一行开始
+    pub fn first_line(&self, source_file: &SourceFile) -> Option<usize> {
+        let start = self.span.as_ref()?.start;
+        let value = source_file.content.get(start..)?;
+        let trimmed_newline = ["'''", "\"\"\""].iter()
+            .filter_map(|delimiter| value.strip_prefix(delimiter))
+            .any(|rest| rest.starts_with('\n') || rest.starts_with("\r\n"));
+        Some(source_file.line_of(start) + trimmed_newline as usize)
+    }
+}
+impl From<Spanned<SmartString>> for EmbedLuaScript {
+    fn from(value: Spanned<SmartString>) -> Self {
+        let span = value.span();
+        EmbedLuaScript { source: value.into_inner(), span: Some(span) }
+    }
+}
+impl From<&str> for EmbedLuaScript {
+    fn from(value: &str) -> Self {
+        EmbedLuaScript { source: value.into(), span: None }
+    }
+}
+///定义来自的TOML文件，路径相对于项目根目录
+#[derive(Debug,Clone,PartialEq)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub content: Arc<str>,
+}
+impl SourceFile {
+    pub fn new(path: impl Into<PathBuf>, content: impl Into<Arc<str>>) -> Self {
+        SourceFile { path: path.into(), content: content.into() }
+    }
+    ///字节位置所在的行号(从1开始)
+    pub fn line_of(&self, offset: usize) -> usize {
+        let offset = offset.min(self.content.len());
+        self.content.as_bytes()[..offset].iter().filter(|&&b| b == b'\n').count() + 1
+    }
+    ///miette 渲染报错时使用的源码
+    pub fn named_source(&self) -> Arc<NamedSource<Arc<str>>> {
+        Arc::new(NamedSource::new(self.path.to_string_lossy(), self.content.clone()).with_language("toml"))
+    }
+}
+///TOML中的值及其位置，比较与哈希时只看值本身
+#[derive(Debug,Clone)]
+pub struct Located<T> {
+    pub value: T,
+    ///值在TOML文件中的字节范围
+    pub span: Option<Range<usize>>,
+    ///值所在的TOML文件，由加载器填入
+    pub source: Option<SourceFile>,
+}
+impl<T> Located<T> {
+    pub fn new(value: T) -> Self {
+        Located { value, span: None, source: None }
+    }
+    pub fn from_spanned(value: Spanned<T>) -> Self {
+        let span = value.span();
+        Located { value: value.into_inner(), span: Some(span), source: None }
+    }
+    ///转换带位置的值，出错时标注该值的位置
+    pub fn try_from_spanned<S>(value: Spanned<S>) -> Result<Self, ParseError>
+    where
+        T: TryFrom<S, Error = ParseError>,
+    {
+        let span = value.span();
+        let value = T::try_from(value.into_inner()).map_err(|error| error.with_span(span.clone()))?;
+        Ok(Located { value, span: Some(span), source: None })
+    }
+    pub fn set_source(&mut self, source: &SourceFile) {
+        self.source = Some(source.clone());
+    }
+    ///转换值本身，保留位置
+    pub fn map<U>(&self, f: impl FnOnce(&T) -> U) -> Located<U> {
+        Located { value: f(&self.value), span: self.span.clone(), source: self.source.clone() }
+    }
+    ///报错时标注的位置
+    pub fn location(&self) -> SourceLocation {
+        SourceLocation::new(self.source.as_ref(), self.span.clone())
+    }
+}
+impl<T> std::ops::Deref for Located<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+impl<T> From<T> for Located<T> {
+    fn from(value: T) -> Self {
+        Located::new(value)
+    }
+}
+impl<T: PartialEq> PartialEq for Located<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+impl<T: Eq> Eq for Located<T> {}
+impl<T: std::hash::Hash> std::hash::Hash for Located<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
 impl LuaScript {
     pub fn is_embed(&self) -> bool {
         matches!(self, LuaScript::Embed(_))
@@ -22,23 +137,28 @@ impl LuaScript {
     pub fn is_file(&self) -> bool {
         matches!(self, LuaScript::File(_))
     }
-    pub fn from_embed_or_file(embed: Option<SmartString>, file: Option<PathBuf>) -> Result<Option<Self>, anyhow::Error> {
+    ///embed_key 与 file_key 为两者在TOML中的键名，用于报错
+    pub fn from_embed_or_file(
+        embed: Option<Spanned<SmartString>>,
+        file: Option<Spanned<SmartString>>,
+        (embed_key, file_key): (&'static str, &'static str),
+    ) -> Result<Option<Self>, ParseError> {
         match (embed, file) {
-            (Some(embed), None) => Ok(Some(LuaScript::Embed(embed))),
-            (None, Some(file)) => Ok(Some(LuaScript::File(file))),
-            (Some(_), Some(_)) => anyhow::bail!("Embed and File cannot be specified at the same time"),
+            (Some(embed), None) => Ok(Some(LuaScript::Embed(embed.into()))),
+            (None, Some(file)) => Ok(Some(LuaScript::File(PathBuf::from(file.into_inner().as_str())))),
+            (Some(_), Some(file)) => Err(ParseError::raise_conflicting_script(embed_key, file_key).with_span(file.span())),
             (None, None) => Ok(None),
         }
      }
 }
 pub trait TomlCode: Sized { //标记Trait, 用于约束Parser泛型
     type RawFile: RawTomlCodeFile + for<'de> serde::Deserialize<'de>;
-    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, anyhow::Error>;
+    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, ParseError>;
 
 }
 pub trait Field {
     fn name_as_rust_ident(&self) -> Ident;
-    fn type_as_rust_ident(&self) -> Ident;
+    fn type_as_rust_type(&self) -> TokenStream;
 }
 pub trait Describable {
     fn description(&self) -> Option<&str> {
@@ -59,7 +179,30 @@ pub enum PrimitiveType {
     Int,
     Bool,
     Str,
-    //TODO: 添加更多类型
+    F32,
+    I32,
+    U32,
+    U8,
+    U64,
+    ///实体引用，在Lua中表示为整数
+    Entity,
+    Vec2,
+    Vec3,
+    ///sRGB颜色
+    Color,
+}
+impl PrimitiveType {
+    ///整数类型能否表示该值
+    pub fn fits_integer(&self, value: i64) -> bool {
+        match self {
+            PrimitiveType::Int => true,
+            PrimitiveType::I32 => i32::try_from(value).is_ok(),
+            PrimitiveType::U32 => u32::try_from(value).is_ok(),
+            PrimitiveType::U8 => u8::try_from(value).is_ok(),
+            PrimitiveType::U64 => u64::try_from(value).is_ok(),
+            _ => false,
+        }
+    }
 }
 impl std::fmt::Display for PrimitiveType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -68,19 +211,77 @@ impl std::fmt::Display for PrimitiveType {
             PrimitiveType::Int => write!(f, "int"),
             PrimitiveType::Bool => write!(f, "bool"),
             PrimitiveType::Str => write!(f, "str"),
+            PrimitiveType::F32 => write!(f, "f32"),
+            PrimitiveType::I32 => write!(f, "i32"),
+            PrimitiveType::U32 => write!(f, "u32"),
+            PrimitiveType::U8 => write!(f, "u8"),
+            PrimitiveType::U64 => write!(f, "u64"),
+            PrimitiveType::Entity => write!(f, "Entity"),
+            PrimitiveType::Vec2 => write!(f, "vec2"),
+            PrimitiveType::Vec3 => write!(f, "vec3"),
+            PrimitiveType::Color => write!(f, "color"),
+        }
+    }
+}
+///TOML中的向量默认值：长度为维度的数字数组，如 `[1.0, 2]`
+pub fn vector_components(value: &toml::Value, dimension: usize) -> Option<Vec<f32>> {
+    let components = value.as_array()?.iter()
+        .map(|component| match component {
+            toml::Value::Float(f) => Some(*f as f32),
+            toml::Value::Integer(i) => Some(*i as f32),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (components.len() == dimension).then_some(components)
+}
+///TOML中的颜色默认值：`"#RRGGBB"`、`"#RRGGBBAA"` 或 `[r, g, b]`、`[r, g, b, a]`，分量为0到1的sRGB值
+pub fn color_components(value: &toml::Value) -> Option<[f32; 4]> {
+    match value {
+        toml::Value::String(hex) => {
+            let hex = hex.strip_prefix('#')?;
+            if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+                return None;
+            }
+            let mut rgba = [1.0; 4];
+            for (i, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+                *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()? as f32 / 255.0;
+            }
+            Some(rgba)
+        }
+        toml::Value::Array(array) => {
+            let components = vector_components(value, array.len())?;
+            if !matches!(components.len(), 3 | 4) || components.iter().any(|c| !(0.0..=1.0).contains(c)) {
+                return None;
+            }
+            let mut rgba = [1.0; 4];
+            rgba[..components.len()].copy_from_slice(&components);
+            Some(rgba)
         }
+        _ => None,
     }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub enum AethaumType {
     Primitive(PrimitiveType),
+    ///`list<T>`
+    List(Box<AethaumType>),
+    ///`map<str, T>`，键固定为字符串
+    Map(Box<AethaumType>),
+    ///`option<T>`
+    Option(Box<AethaumType>),
+    ///`array<T, N>`
+    Array(Box<AethaumType>, usize),
     Custom(SmartString)
 }
 impl std::fmt::Display for AethaumType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AethaumType::Primitive(primitive) => write!(f, "{}", primitive),
+            AethaumType::List(element) => write!(f, "list<{}>", element),
+            AethaumType::Map(value) => write!(f, "map<str, {}>", value),
+            AethaumType::Option(inner) => write!(f, "option<{}>", inner),
+            AethaumType::Array(element, len) => write!(f, "array<{}, {}>", element, len),
             AethaumType::Custom(custom) => write!(f, "{}", custom),
         }
     }
@@ -93,25 +294,138 @@ impl AethaumType {
     pub fn is_custom(&self) -> bool {
         matches!(self, AethaumType::Custom(_))
     }
-    pub fn from_type_str(type_str: &str) -> AethaumType {
-        match type_str {
+    ///解析字段的类型表达式，容器类型可以嵌套，如 `map<str, list<vec2>>`
+    pub fn from_type_str(type_str: &str) -> Result<AethaumType, ParseError> {
+        TypeExprParser { rest: type_str }.parse()
+            .map_err(|reason| ParseError::raise_invalid_type(type_str.into(), reason))
+    }
+    fn from_name(name: &str) -> AethaumType {
+        match name {
             "float" => AethaumType::Primitive(PrimitiveType::Float),
             "int" => AethaumType::Primitive(PrimitiveType::Int),
             "bool" => AethaumType::Primitive(PrimitiveType::Bool),
             "str" => AethaumType::Primitive(PrimitiveType::Str),
+            "f32" => AethaumType::Primitive(PrimitiveType::F32),
+            "i32" => AethaumType::Primitive(PrimitiveType::I32),
+            "u32" => AethaumType::Primitive(PrimitiveType::U32),
+            "u8" => AethaumType::Primitive(PrimitiveType::U8),
+            "u64" => AethaumType::Primitive(PrimitiveType::U64),
+            "Entity" => AethaumType::Primitive(PrimitiveType::Entity),
+            "vec2" => AethaumType::Primitive(PrimitiveType::Vec2),
+            "vec3" => AethaumType::Primitive(PrimitiveType::Vec3),
+            "color" => AethaumType::Primitive(PrimitiveType::Color),
 
-            _ => AethaumType::Custom(type_str.into()),
+            _ => AethaumType::Custom(name.into()),
         }
     }
-    pub fn to_rust_type(&self) -> Ident {
+    pub fn to_rust_type(&self) -> TokenStream {
         match self {
             AethaumType::Primitive(primitive) => match primitive {
-                PrimitiveType::Float => Ident::new("f64", Span::call_site()),
-                PrimitiveType::Int => Ident::new("i64", Span::call_site()),
-                PrimitiveType::Bool => Ident::new("bool", Span::call_site()),
-                PrimitiveType::Str => Ident::new("String", Span::call_site()),
+                PrimitiveType::Float => quote! { f64 },
+                PrimitiveType::Int => quote! { i64 },
+                PrimitiveType::Bool => quote! { bool },
+                PrimitiveType::Str => quote! { String },
+                PrimitiveType::F32 => quote! { f32 },
+                PrimitiveType::I32 => quote! { i32 },
+                PrimitiveType::U32 => quote! { u32 },
+                PrimitiveType::U8 => quote! { u8 },
+                PrimitiveType::U64 => quote! { u64 },
+                PrimitiveType::Entity => quote! { Entity },
+                PrimitiveType::Vec2 => quote! { Vec2 },
+                PrimitiveType::Vec3 => quote! { Vec3 },
+                PrimitiveType::Color => quote! { Color },
             },
-            AethaumType::Custom(custom) => Ident::new(custom, Span::call_site()),
+            AethaumType::List(element) => {
+                let element = element.to_rust_type();
+                quote! { Vec<#element> }
+            }
+            AethaumType::Map(value) => {
+                let value = value.to_rust_type();
+                quote! { std::collections::HashMap<String, #value> }
+            }
+            AethaumType::Option(inner) => {
+                let inner = inner.to_rust_type();
+                quote! { Option<#inner> }
+            }
+            AethaumType::Array(element, len) => {
+                let element = element.to_rust_type();
+                let len = proc_macro2::Literal::usize_unsuffixed(*len);
+                quote! { [#element; #len] }
+            }
+            AethaumType::Custom(custom) => Ident::new(custom, Span::call_site()).into_token_stream(),
+        }
+    }
+}
+///类型表达式的递归下降解析，出错时返回原因
+struct TypeExprParser<'a> {
+    rest: &'a str,
+}
+impl<'a> TypeExprParser<'a> {
+    fn parse(mut self) -> Result<AethaumType, &'static str> {
+        let type_spec = self.parse_type()?;
+        if !self.rest.trim().is_empty() {
+            return Err("unexpected characters after the type");
+        }
+        Ok(type_spec)
+    }
+    fn parse_type(&mut self) -> Result<AethaumType, &'static str> {
+        let name = self.word().ok_or("expected a type name")?;
+        // 自定义类型名在生成的代码中用作Rust标识符，数字与关键字不能作为类型名
+        if syn::parse_str::<syn::Ident>(name).is_err() {
+            return Err("type names must be identifiers");
+        }
+        if !matches!(name, "list" | "map" | "option" | "array") {
+            return Ok(AethaumType::from_name(name));
+        }
+        if !self.eat('<') {
+            return Err("container types need type arguments, e.g. `list<int>`");
+        }
+        let type_spec = match name {
+            "list" => AethaumType::List(Box::new(self.parse_type()?)),
+            "option" => AethaumType::Option(Box::new(self.parse_type()?)),
+            "map" => {
+                if self.parse_type()? != AethaumType::Primitive(PrimitiveType::Str) {
+                    return Err("map keys must be `str`");
+                }
+                if !self.eat(',') {
+                    return Err("expected `,` between the key and value types of `map`");
+                }
+                AethaumType::Map(Box::new(self.parse_type()?))
+            }
+            _ => {
+                let element = self.parse_type()?;
+                if !self.eat(',') {
+                    return Err("expected `,` before the length of `array`");
+                }
+                let len = self.word().and_then(|len| len.parse().ok())
+                    .ok_or("array length must be a non-negative integer")?;
+                AethaumType::Array(Box::new(element), len)
+            }
+        };
+        if !self.eat('>') {
+            return Err("expected `>` after the type arguments");
+        }
+        Ok(type_spec)
+    }
+    ///类型名或数字
+    fn word(&mut self) -> Option<&'a str> {
+        self.rest = self.rest.trim_start();
+        let end = self.rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(self.rest.len());
+        if end == 0 {
+            return None;
+        }
+        let (word, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(word)
+    }
+    fn eat(&mut self, token: char) -> bool {
+        self.rest = self.rest.trim_start();
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
         }
     }
 }
@@ -120,8 +434,8 @@ impl AethaumType {
 #[derive(Debug,PartialEq, Clone)]
 pub struct ComponentField {
     pub name: SmartString,
-    pub type_spec : AethaumType,
-    pub default_value: Option<toml::Value>,
+    pub type_spec : Located<AethaumType>,
+    pub default_value: Option<Located<toml::Value>>,
     pub description: Option<SmartString>
 }
 
@@ -131,6 +445,18 @@ pub struct Component {
     pub description: Option<SmartString>,
     pub fields: Option<Vec<ComponentField>>
 }
+impl Component {
+    ///记录定义组件的TOML文件，用于标注字段类型与默认值的位置
+    pub fn with_source(mut self, source: &SourceFile) -> Self {
+        for field in self.fields.iter_mut().flatten() {
+            field.type_spec.set_source(source);
+            if let Some(default_value) = field.default_value.as_mut() {
+                default_value.set_source(source);
+            }
+        }
+        self
+    }
+}
 #[derive(Debug,PartialEq,Clone,Eq,Hash)]
 pub struct ComponentRef {
     pub name: SmartString,
@@ -158,14 +484,14 @@ impl From<(SmartString, SmartString)> for ComponentRef {
     }
 }
 impl TryFrom<SmartString> for ComponentRef {
-    type Error = anyhow::Error;
+    type Error = ParseError;
     fn try_from(s: SmartString) -> Result<Self, Self::Error> {
         let res : Vec<&str> = s.split("::").collect();
         match res.len() {
-            0 => anyhow::bail!("Invalid component ref: {}, component name is required", s),
+            0 => Err(ParseError::raise_invalid_ref("component", s, "component name is required")),
             1 => Ok(Self::new(None::<SmartString>, s)),
             2 => Ok(Self::new(Some(res[0]), res[1])),
-            _ => anyhow::bail!("Invalid component ref: {}, nested module is currently not allowed", s),
+            _ => Err(ParseError::raise_invalid_ref("component", s, "nested module is currently not allowed")),
         }
     }
 }
@@ -176,8 +502,8 @@ impl std::fmt::Display for ComponentRef {
 }
 #[derive(Debug, PartialEq, Clone)] //TODO: check if there is any conflict when construct
 pub struct ComponentConstraint {
-    include: Option<Vec<ComponentRef>>, //必须包含的组件
-    exclude: Option<Vec<ComponentRef>>, //必须不包含的组件
+    include: Option<Vec<Located<ComponentRef>>>, //必须包含的组件
+    exclude: Option<Vec<Located<ComponentRef>>>, //必须不包含的组件
 }
 impl ComponentConstraint {
     pub fn new_empty() -> Self {
@@ -187,31 +513,34 @@ impl ComponentConstraint {
         }
     }
     pub fn with_include(mut self, include: Vec<ComponentRef>) -> Self {
-        self.include = Some(include);
+        self.include = Some(include.into_iter().map(Located::new).collect());
         self
     }
     pub fn with_exclude(mut self, exclude: Vec<ComponentRef>) -> Self {
-        self.exclude = Some(exclude);
+        self.exclude = Some(exclude.into_iter().map(Located::new).collect());
         self
     }
-    pub fn chained_iter(&self) -> impl Iterator<Item = &ComponentRef> { //TODO: test it
+    pub fn chained_iter(&self) -> impl Iterator<Item = &Located<ComponentRef>> { //TODO: test it
         self.include.iter().flatten().chain(self.exclude.iter().flatten())
     }
-    pub fn get_include(&self) -> Option<&Vec<ComponentRef>> {
+    fn chained_iter_mut(&mut self) -> impl Iterator<Item = &mut Located<ComponentRef>> {
+        self.include.iter_mut().flatten().chain(self.exclude.iter_mut().flatten())
+    }
+    pub fn get_include(&self) -> Option<&Vec<Located<ComponentRef>>> {
         self.include.as_ref()
     }
-    pub fn get_exclude(&self) -> Option<&Vec<ComponentRef>> {
+    pub fn get_exclude(&self) -> Option<&Vec<Located<ComponentRef>>> {
         self.exclude.as_ref()
     }
 }
-impl TryFrom<(Option<Vec<SmartString>>, Option<Vec<SmartString>>)> for ComponentConstraint {
-    type Error = anyhow::Error;
+impl TryFrom<(Option<Vec<Spanned<SmartString>>>, Option<Vec<Spanned<SmartString>>>)> for ComponentConstraint {
+    type Error = ParseError;
     fn try_from(
-        (include, exclude): (Option<Vec<SmartString>>, Option<Vec<SmartString>>),
+        (include, exclude): (Option<Vec<Spanned<SmartString>>>, Option<Vec<Spanned<SmartString>>>),
     ) -> Result<Self, Self::Error> {
         Ok(Self {
-            include: include.map(|v| v.into_iter().map(|s| s.try_into()).try_collect()).transpose()?,
-            exclude: exclude.map(|v| v.into_iter().map(|s| s.try_into()).try_collect()).transpose()?,
+            include: include.map(|v| v.into_iter().map(Located::try_from_spanned).try_collect()).transpose()?,
+            exclude: exclude.map(|v| v.into_iter().map(Located::try_from_spanned).try_collect()).transpose()?,
         })
     }
 }
@@ -220,7 +549,7 @@ impl TryFrom<(Option<Vec<SmartString>>, Option<Vec<SmartString>>)> for Component
 #[derive(Debug,PartialEq,Clone)]
 pub struct EventField {
     pub name: SmartString,
-    pub type_spec : AethaumType,
+    pub type_spec : Located<AethaumType>,
     pub description: Option<SmartString>
 }
 
@@ -230,6 +559,13 @@ pub struct Event {
     pub description: Option<SmartString>,
     pub fields: Option<Vec<EventField>>
 }
+impl Event {
+    ///记录定义事件的TOML文件，用于标注字段类型的位置
+    pub fn with_source(mut self, source: &SourceFile) -> Self {
+        self.fields.iter_mut().flatten().for_each(|field| field.type_spec.set_source(source));
+        self
+    }
+}
 #[derive(Debug,PartialEq,Clone, Eq, Hash)]
 pub struct EventRef {
     pub name: SmartString,
@@ -257,14 +593,14 @@ impl From<(SmartString, SmartString)> for EventRef {
     }
 }
 impl TryFrom<SmartString> for EventRef {
-    type Error = anyhow::Error;
+    type Error = ParseError;
     fn try_from(s: SmartString) -> Result<Self, Self::Error> {
         let res : Vec<&str> = s.split("::").collect();
         match res.len() {
-            0 => anyhow::bail!("Invalid event ref: {}, event name is required", s),
+            0 => Err(ParseError::raise_invalid_ref("event", s, "event name is required")),
             1 => Ok(Self::new(None::<SmartString>, s)),
             2 => Ok(Self::new(Some(res[0]), res[1])),
-            _ => anyhow::bail!("Invalid event ref: {}, nested module is currently not allowed", s),
+            _ => Err(ParseError::raise_invalid_ref("event", s, "nested module is currently not allowed")),
         }
     }
 }
@@ -278,7 +614,14 @@ impl std::fmt::Display for EventRef {
 pub struct EntityProto {
     pub name: SmartString,
     pub description: Option<SmartString>,
-    pub components: Vec<ComponentRef>
+    pub components: Vec<Located<ComponentRef>>
+}
+impl EntityProto {
+    ///记录定义原型的TOML文件，用于标注组件引用的位置
+    pub fn with_source(mut self, source: &SourceFile) -> Self {
+        self.components.iter_mut().for_each(|component_ref| component_ref.set_source(source));
+        self
+    }
 }
 #[derive(Debug,PartialEq,Clone, Eq, Hash)]
 pub struct EntityProtoRef {
@@ -307,14 +650,14 @@ impl From<(SmartString, SmartString)> for EntityProtoRef {
     }
 }
 impl TryFrom<SmartString> for EntityProtoRef {
-    type Error = anyhow::Error;
+    type Error = ParseError;
     fn try_from(s: SmartString) -> Result<Self, Self::Error> {
         let res : Vec<&str> = s.split("::").collect();
         match res.len() {
-            0 => anyhow::bail!("Invalid entity proto ref: {}, entity proto name is required", s),
+            0 => Err(ParseError::raise_invalid_ref("entity proto", s, "entity proto name is required")),
             1 => Ok(Self::new(None::<SmartString>, s)),
             2 => Ok(Self::new(Some(res[0]), res[1])),
-            _ => anyhow::bail!("Invalid entity proto ref: {}, nested module is currently not allowed", s)
+            _ => Err(ParseError::raise_invalid_ref("entity proto", s, "nested module is currently not allowed"))
         }
     }
 }
@@ -333,7 +676,7 @@ pub struct SystemQuery {
 }
 #[derive(Debug,PartialEq,Clone)]
 pub struct SystemEventHandler {
-    pub watch_for: EventRef,
+    pub watch_for: Located<EventRef>,
     pub priority: u32,
     pub logic: Option<LuaScript>
 }
@@ -348,13 +691,41 @@ pub struct System {
     pub normal: SystemNormal,
     pub queries: Vec<SystemQuery>,
     pub update: Option<SystemUpdate>,
-    pub event_handlers: Vec<SystemEventHandler>
+    pub event_handlers: Vec<SystemEventHandler>,
+    ///定义系统的TOML文件，由加载器填入
+    pub source: Option<SourceFile>
 }
 #[derive(Debug,PartialEq,Clone, Eq, Hash)]
 pub struct SystemRef {
     pub name: SmartString,
     pub module_name: Option<SmartString>,
 }
+impl System {
+    ///记录定义系统的TOML文件，系统中的引用同样记录该文件
+    pub fn with_source(mut self, source: SourceFile) -> Self {
+        for query in self.queries.iter_mut() {
+            query.component_constraint.chained_iter_mut().for_each(|component_ref| component_ref.set_source(&source));
+        }
+        for event_handler in self.event_handlers.iter_mut() {
+            event_handler.watch_for.set_source(&source);
+        }
+        self.source = Some(source);
+        self
+    }
+    ///内嵌脚本第一行在TOML文件中的行号，脚本来自文件或不知道来源时为 None
+    pub fn script_line(&self, script: &LuaScript) -> Option<usize> {
+        match script {
+            LuaScript::Embed(embed) => embed.first_line(self.source.as_ref()?),
+            LuaScript::File(_) => None,
+        }
+    }
+    ///系统优先级，未指定时为0，越高越先执行
+    pub fn priority(&self) -> u32 {
+        self.normal.priority.as_ref()
+            .and_then(|priority| priority.get_ref().as_integer())
+            .map_or(0, |i| i as u32)
+    }
+}
 impl SystemRef {
     pub fn new(module_name: Option<impl Into<SmartString>>,name: impl Into<SmartString>) -> Self {
         Self { name: name.into(), module_name: module_name.map(|s| s.into()) }
@@ -377,14 +748,14 @@ impl From<(SmartString, SmartString)> for SystemRef {
     }
 }
 impl TryFrom<SmartString> for SystemRef {
-    type Error = anyhow::Error;
+    type Error = ParseError;
     fn try_from(s: SmartString) -> Result<Self, Self::Error> {
         let res : Vec<&str> = s.split("::").collect();
         match res.len() {
-            0 => anyhow::bail!("Invalid system ref: {}, system name is required", s),
+            0 => Err(ParseError::raise_invalid_ref("system", s, "system name is required")),
             1 => Ok(Self::new(None::<SmartString>, s)),
             2 => Ok(Self::new(Some(res[0]), res[1])),
-            _ => anyhow::bail!("Invalid system ref: {}, nested module is currently not allowed", s)
+            _ => Err(ParseError::raise_invalid_ref("system", s, "nested module is currently not allowed"))
         }
     }
 }
@@ -396,88 +767,99 @@ impl std::fmt::Display for SystemRef {
 //TomlCode Mark
 impl TomlCode for Component {
     type RawFile = RawComponentFile;
-    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, Error> {
+    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, ParseError> {
+        let components: Vec<_> = raw.into_iter().map(|x| x.try_into()).try_collect()?;
         Ok(OneOrMany::from_iter(
-            raw.into_iter().map(|x| x.into())
+            components
         ))
     }
 }
 impl TomlCode for Event {
     type RawFile = RawEventFile;
-    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, Error> {
+    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, ParseError> {
+        let events: Vec<_> = raw.into_iter().map(|x| x.try_into()).try_collect()?;
         Ok(OneOrMany::from_iter(
-            raw.into_iter().map(|x| x.into())
+            events
         ))
     }
 }
 impl TomlCode for EntityProto {
     type RawFile = RawEntityProtoFile;
-    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, Error> {
+    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, ParseError> {
         let protos: Vec<_> = raw.into_iter().map(|x| x.try_into()).try_collect()?;
         //TODO: remove the try_collect
         Ok(OneOrMany::from_iter(
-            protos.into_iter()
+            protos
         ))
     }
 }
 impl TomlCode for System {
     type RawFile = RawSystemFile;
-    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, Error> {
+    fn from_raw_file(raw: <Self::RawFile as RawTomlCodeFile>::RawPieces) -> Result<OneOrMany<Self>, ParseError> {
         Ok(OneOrMany::One(Box::new(raw.into_pieces().try_into()?)))
     }
 }
 
 
 //Raw Transformation
-impl From<RawComponentField> for ComponentField {
-    fn from(value: RawComponentField) -> Self {
-        ComponentField {
+///解析字段类型并记录类型字符串的位置，出错时标注类型字符串
+fn parse_type_spec(type_spec: &Spanned<SmartString>) -> Result<Located<AethaumType>, ParseError> {
+    let parsed = AethaumType::from_type_str(type_spec.get_ref()).map_err(|error| error.with_span(type_spec.span()))?;
+    Ok(Located { value: parsed, span: Some(type_spec.span()), source: None })
+}
+impl TryFrom<RawComponentField> for ComponentField {
+    type Error = ParseError;
+    fn try_from(value: RawComponentField) -> Result<Self, Self::Error> {
+        Ok(ComponentField {
             name: value.name,
-            type_spec: AethaumType::from_type_str(&value.type_spec),
-            default_value: value.default,
+            type_spec: parse_type_spec(&value.type_spec)?,
+            default_value: value.default.map(Located::from_spanned),
             description: value.description,
-        }
+        })
     }
 }
-impl From<RawComponent> for Component {
-    fn from(value: RawComponent) -> Self {
-        Component {
+impl TryFrom<RawComponent> for Component {
+    type Error = ParseError;
+    fn try_from(value: RawComponent) -> Result<Self, Self::Error> {
+        Ok(Component {
             name: value.name,
-            fields: value.fields.map(|fields| fields.into_iter().map(|x| x.into()).collect()),
+            fields: value.fields.map(|fields| fields.into_iter().map(ComponentField::try_from).try_collect()).transpose()?,
             description: value.description,
-        }
+        })
     }
 }
-impl From<RawEventField> for EventField {
-    fn from(value: RawEventField) -> Self {
-        EventField {
+impl TryFrom<RawEventField> for EventField {
+    type Error = ParseError;
+    fn try_from(value: RawEventField) -> Result<Self, Self::Error> {
+        Ok(EventField {
             name: value.name,
-            type_spec: AethaumType::from_type_str(&value.type_spec),
+            type_spec: parse_type_spec(&value.type_spec)?,
             description: value.description,
-        }
+        })
     }
 }
-impl From<RawEvent> for Event {
-    fn from(value: RawEvent) -> Self {
-        Event {
+impl TryFrom<RawEvent> for Event {
+    type Error = ParseError;
+    fn try_from(value: RawEvent) -> Result<Self, Self::Error> {
+        Ok(Event {
             name: value.name,
-            fields: value.fields.map(|fields| fields.into_iter().map(|x| x.into()).collect()),
+            fields: value.fields.map(|fields| fields.into_iter().map(EventField::try_from).try_collect()).transpose()?,
             description: value.description,
-        }
+        })
     }
 }
 impl TryFrom<RawEntityProto> for EntityProto {
-    type Error = anyhow::Error;
+    type Error = ParseError;
     fn try_from(value: RawEntityProto) -> Result<Self, Self::Error> {
         Ok(EntityProto {
             name: value.name,
             description: value.description,
-            components: value.components.into_iter().map(|x| x.try_into()).try_collect()?,
+            components: value.components.into_iter().map(Located::try_from_spanned).try_collect()?,
         })
     }
 }
 impl TryFrom<RawSystemQuery> for SystemQuery {
-    type Error = anyhow::Error;
+    type Error = ParseError;
     fn try_from(value: RawSystemQuery) -> Result<Self, Self::Error> {
         Ok(SystemQuery {
             name: value.name,
@@ -489,63 +871,53 @@ impl TryFrom<RawSystemQuery> for SystemQuery {
     }
 }
 impl TryFrom<RawSystemEventHandler> for SystemEventHandler {
-    type Error = anyhow::Error; //TODO: better error type further
+    type Error = ParseError;
     fn try_from(value: RawSystemEventHandler) -> Result<Self, Self::Error> {
         Ok(SystemEventHandler {
-            watch_for: value.watch_for.try_into()?,
-            priority: match value.priority {
-                Some(priority) => {
-                   match priority {
-                       toml::Value::Integer(i) => {
-                           if i < 0 {
-                               return Err(anyhow::anyhow!("Priority must be positive"));
-                           }
-                           i as u32
-                       },
-                       _ => return Err(anyhow::anyhow!("Priority must be an integer")),
-                   }
-                },
-                None => 0
-            },
-            logic: LuaScript::from_embed_or_file(value.logic, value.logic_file.map(|x| PathBuf::from(x.as_str())))? // For Further Version, LuaScript type might not be SmartString
+            watch_for: Located::try_from_spanned(value.watch_for)?,
+            priority: value.priority.as_ref().map(parse_priority).transpose()?.unwrap_or(0),
+            logic: LuaScript::from_embed_or_file(value.logic, value.logic_file, ("logic", "logic_file"))?
         })
     }
 }
 impl TryFrom<RawSystemUpdate> for SystemUpdate {
-    type Error = anyhow::Error; //TODO: better error type further
+    type Error = ParseError;
     fn try_from(value: RawSystemUpdate) -> Result<Self, Self::Error> {
+        let invalid_interval = || ParseError::raise_invalid_value("interval", "a positive number of seconds")
+            .with_span(value.interval.span());
         Ok(SystemUpdate {
-            interval: match value.interval {
-                toml::Value::Integer(i) => {
-                    if i <= 0 {
-                        return Err(anyhow::anyhow!("Interval must be positive"));
-                    }
-                    Duration::from_secs(i as u64)
-                }
-                toml::Value::Float(f) => {
-                    if f <= 0.0 {
-                        return Err(anyhow::anyhow!("Interval must be positive"));
-                    }
-                    Duration::from_secs_f64(f)
-                }
-                _ => return Err(anyhow::anyhow!("Interval must be a number")),
+            interval: match value.interval.get_ref() {
+                toml::Value::Integer(i) if *i > 0 => Duration::from_secs(*i as u64),
+                toml::Value::Float(f) if *f > 0.0 => Duration::from_secs_f64(*f),
+                _ => return Err(invalid_interval()),
             },
-            condition: LuaScript::from_embed_or_file(value.condition, value.condition_file.map(|x| PathBuf::from(x.as_str())))?,
-            logic: LuaScript::from_embed_or_file(value.logic, value.logic_file.map(|x| PathBuf::from(x.as_str())))?,
+            condition: LuaScript::from_embed_or_file(value.condition, value.condition_file, ("condition", "condition_file"))?,
+            logic: LuaScript::from_embed_or_file(value.logic, value.logic_file, ("logic", "logic_file"))?,
         })
     }
 }
+///系统与事件处理器的优先级：不大于 u32::MAX 的非负整数
+fn parse_priority(priority: &Spanned<toml::Value>) -> Result<u32, ParseError> {
+    match priority.get_ref() {
+        toml::Value::Integer(i) if *i < 0 => Err(ParseError::raise_invalid_value("priority", "a non-negative integer")),
+        toml::Value::Integer(i) => u32::try_from(*i)
+            .map_err(|_| ParseError::raise_invalid_value("priority", "no larger than 4294967295")),
+        _ => Err(ParseError::raise_invalid_value("priority", "a non-negative integer")),
+    }.map_err(|error| error.with_span(priority.span()))
+}
 impl TryFrom<RawSystem> for System {
-    type Error = anyhow::Error; //TODO: better error type further
+    type Error = ParseError;
     fn try_from(value: RawSystem) -> Result<Self, Self::Error> {
+        value.normal.priority.as_ref().map(parse_priority).transpose()?;
         Ok(System {
-            normal: value.normal.into(),
+            normal: value.normal,
             queries: value.queries.into_iter().map(|q| q.try_into()).try_collect()?,
             update: value.update.map(TryInto::try_into).transpose()?,
             event_handlers: value.event_handlers
                 .into_iter()
                 .map(|h| h.try_into())
                 .try_collect()?,
+            source: None,
         })
     }
 }
@@ -635,39 +1007,112 @@ impl From<RawWorldModules> for WorldModules {
 }
 #[derive(Debug,PartialEq,Clone)]
 pub struct WorldBuild {
-    pub output_dir: SmartString,
-}
-impl From<RawWorldBuild> for WorldBuild {
-    fn from(value: RawWorldBuild) -> Self {
+    ///生成项目的目录，相对路径相对于项目根目录
+    pub output_dir: PathBuf,
+    ///生成的crate名，为空时使用世界名
+    pub crate_name: Option<SmartString>,
+    pub target: BuildTarget,
+}
+impl Default for WorldBuild {
+    fn default() -> Self {
         WorldBuild {
-            output_dir: value.output_dir,
+            output_dir: PathBuf::from("generated"),
+            crate_name: None,
+            target: BuildTarget::Bin,
         }
     }
 }
-#[derive(Debug,PartialEq,Clone)]
+///按 Cargo 包名的规则检查crate名，生成的 Cargo.toml 与库名都由它得出
+fn check_crate_name(name: &str) -> Result<(), &'static str> {
+    let Some(first) = name.chars().next() else {
+        return Err("crate names cannot be empty");
+    };
+    if first.is_ascii_digit() {
+        return Err("crate names cannot start with a digit");
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("crate names may only contain ASCII letters, digits, `-` and `_`");
+    }
+    if matches!(name, "test" | "core" | "std" | "alloc" | "proc_macro" | "proc-macro") {
+        return Err("conflicts with a Rust built-in library");
+    }
+    if syn::parse_str::<syn::Ident>(&name.replace('-', "_")).is_err() {
+        return Err("crate names cannot be Rust keywords");
+    }
+    Ok(())
+}
+impl TryFrom<RawWorldBuild> for WorldBuild {
+    type Error = ParseError;
+    fn try_from(value: RawWorldBuild) -> Result<Self, Self::Error> {
+        let default = WorldBuild::default();
+        let crate_name = value.crate_name
+            .map(|crate_name| match check_crate_name(crate_name.get_ref()) {
+                Ok(()) => Ok(crate_name.into_inner()),
+                Err(reason) => Err(ParseError::raise_invalid_crate_name(crate_name.get_ref().clone(), reason).with_span(crate_name.span())),
+            })
+            .transpose()?;
+        Ok(WorldBuild {
+            output_dir: value.output_dir.map(|dir| PathBuf::from(dir.as_str())).unwrap_or(default.output_dir),
+            crate_name,
+            target: value.target.map(Into::into).unwrap_or(default.target),
+        })
+    }
+}
+///生成的crate是可执行程序还是库
+#[derive(Debug,PartialEq,Clone,Copy)]
+pub enum BuildTarget {
+    Bin,
+    Lib,
+}
+impl From<RawWorldBuildTarget> for BuildTarget {
+    fn from(value: RawWorldBuildTarget) -> Self {
+        match value {
+            RawWorldBuildTarget::Bin => BuildTarget::Bin,
+            RawWorldBuildTarget::Lib => BuildTarget::Lib,
+        }
+    }
+}
+#[derive(Debug,PartialEq,Clone,Default)]
 pub struct WorldCargo {
-
+    ///额外的依赖，与默认依赖同名时覆盖默认依赖
+    pub dependencies: toml::Table,
+    pub features: toml::Table,
+    pub profile: toml::Table,
+    ///额外的 [package] 字段
+    pub package: toml::Table,
 }
 impl From<RawWorldCargo> for WorldCargo {
-    fn from(_value: RawWorldCargo) -> Self {
-        WorldCargo {}
+    fn from(value: RawWorldCargo) -> Self {
+        WorldCargo {
+            dependencies: value.dependencies,
+            features: value.features,
+            profile: value.profile,
+            package: value.package,
+        }
     }
 }
 #[derive(Debug,PartialEq,Clone)]
 pub struct World {
     pub normal: WorldNormal,
     pub modules: WorldModules,
-    pub build: Option<WorldBuild>,
-    pub cargo: Option<WorldCargo>,
+    pub build: WorldBuild,
+    pub cargo: WorldCargo,
+}
+impl World {
+    ///生成的crate名
+    pub fn crate_name(&self) -> &str {
+        self.build.crate_name.as_deref().unwrap_or(self.normal.name.as_str())
+    }
 }
-impl From<RawWorld> for World {
-    fn from(value: RawWorld) -> Self {
-        World {
+impl TryFrom<RawWorld> for World {
+    type Error = ParseError;
+    fn try_from(value: RawWorld) -> Result<Self, Self::Error> {
+        Ok(World {
             normal: value.normal.into(),
             modules: value.modules.into(),
-            build: value.build.map(Into::into),
-            cargo: value.cargo.map(Into::into),
-        }
+            build: value.build.map(WorldBuild::try_from).transpose()?.unwrap_or_default(),
+            cargo: value.cargo.map(Into::into).unwrap_or_default(),
+        })
     }
 }
 //Field Trait Implementation
@@ -676,7 +1121,7 @@ impl Field for ComponentField {
         Ident::new(&self.name, Span::call_site())
     }
 
-    fn type_as_rust_ident(&self) -> Ident {
+    fn type_as_rust_type(&self) -> TokenStream {
         self.type_spec.to_rust_type()
     }
 }
@@ -685,7 +1130,7 @@ impl Field for &ComponentField {
         Ident::new(&self.name, Span::call_site())
     }
 
-    fn type_as_rust_ident(&self) -> Ident {
+    fn type_as_rust_type(&self) -> TokenStream {
         self.type_spec.to_rust_type()
     }
 }
@@ -694,7 +1139,7 @@ impl Field for EventField {
         Ident::new(&self.name, Span::call_site())
     }
 
-    fn type_as_rust_ident(&self) -> Ident {
+    fn type_as_rust_type(&self) -> TokenStream {
         self.type_spec.to_rust_type()
     }
 }
@@ -703,7 +1148,7 @@ impl Field for &EventField {
         Ident::new(&self.name, Span::call_site())
     }
 
-    fn type_as_rust_ident(&self) -> Ident {
+    fn type_as_rust_type(&self) -> TokenStream {
         self.type_spec.to_rust_type()
     }
 }
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 ///原始toml解析，类型，模块路径在后续处理
 use serde::{Deserialize, Serialize};
 use smart_string::SmartString;
+use toml::Spanned;
 
 pub trait RawTomlCodeFile: Sized {
     type RawPieces: for<'de> Deserialize<'de>; //TODO: try using Iterator
@@ -19,8 +20,8 @@ pub struct RawNormal {
 pub struct RawComponentField {
     pub name: SmartString,
     #[serde(rename = "type")]
-    pub type_spec: SmartString,
-    pub default: Option<toml::Value>,
+    pub type_spec: Spanned<SmartString>,
+    pub default: Option<Spanned<toml::Value>>,
     pub description: Option<SmartString>,
 }
 #[derive(Debug,Serialize,Deserialize)]
@@ -48,7 +49,7 @@ impl RawTomlCodeFile for RawComponentFile {
 pub struct RawEventField {
     pub name: SmartString,
     #[serde(rename = "type")]
-    pub type_spec: SmartString,
+    pub type_spec: Spanned<SmartString>,
     pub description: Option<SmartString>,
 }
 #[derive(Debug,Serialize,Deserialize)]
@@ -74,7 +75,7 @@ impl RawTomlCodeFile for RawEventFile {
 pub struct RawEntityProto {
     pub name: SmartString,
     pub description: Option<SmartString>,
-    pub components: Vec<SmartString>,
+    pub components: Vec<Spanned<SmartString>>,
 }
 #[derive(Debug,Serialize,Deserialize)]
 pub struct RawEntityProtoFile {
@@ -94,36 +95,38 @@ impl RawTomlCodeFile for RawEntityProtoFile {
 pub struct RawSystemQuery {
     pub name: SmartString,
     pub description: Option<SmartString>,
-    pub components_include: Option<Vec<SmartString>>,
-    pub components_exclude: Option<Vec<SmartString>>,
+    pub components_include: Option<Vec<Spanned<SmartString>>>,
+    pub components_exclude: Option<Vec<Spanned<SmartString>>>,
 }
 #[derive(Debug,Serialize,Deserialize)]
 pub struct RawSystemEventHandler {
-    pub watch_for: SmartString,
-    pub priority: Option<toml::Value>,
-    pub logic: Option<SmartString>,
-    pub logic_file: Option<SmartString>
+    pub watch_for: Spanned<SmartString>,
+    pub priority: Option<Spanned<toml::Value>>,
+    pub logic: Option<Spanned<SmartString>>,
+    pub logic_file: Option<Spanned<SmartString>>
 }
 #[derive(Debug,Serialize,Deserialize)]
 pub struct RawSystemUpdate {
-    pub interval: toml::Value,
-    pub condition: Option<SmartString>,
-    pub condition_file: Option<SmartString>,
-    pub logic: Option<SmartString>,
-    pub logic_file: Option<SmartString>
+    pub interval: Spanned<toml::Value>,
+    pub condition: Option<Spanned<SmartString>>,
+    pub condition_file: Option<Spanned<SmartString>>,
+    pub logic: Option<Spanned<SmartString>>,
+    pub logic_file: Option<Spanned<SmartString>>
 }
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct RawSystemNormal {
     pub name: SmartString,
     pub description: Option<SmartString>,
     pub category: Option<SmartString>,
-    pub priority: Option<toml::Value>,
+    pub priority: Option<Spanned<toml::Value>>,
 }
 #[derive(Debug,Serialize,Deserialize)]
 pub struct RawSystem {
     pub normal: RawSystemNormal,
+    #[serde(default)]
     pub queries: Vec<RawSystemQuery>,
     pub update: Option<RawSystemUpdate>,
+    #[serde(default)]
     pub event_handlers: Vec<RawSystemEventHandler>,
 }
 pub type RawSystemFile = RawSystem;
@@ -146,11 +149,28 @@ pub struct RawWorldModules {
 }
 #[derive(Debug,Serialize,Deserialize)]
 pub struct RawWorldBuild {
-    pub output_dir: SmartString,
+    pub output_dir: Option<SmartString>,
+    pub crate_name: Option<Spanned<SmartString>>,
+    pub target: Option<RawWorldBuildTarget>,
 }
+#[derive(Debug,Serialize,Deserialize,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RawWorldBuildTarget {
+    Bin,
+    Lib,
+}
+///直接合并进生成的 Cargo.toml 的配置，其它键多半是拼写错误，直接报错
 #[derive(Debug,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RawWorldCargo {
-
+    #[serde(default)]
+    pub dependencies: toml::Table,
+    #[serde(default)]
+    pub features: toml::Table,
+    #[serde(default)]
+    pub profile: toml::Table,
+    #[serde(default)]
+    pub package: toml::Table,
 }
 #[derive(Debug,Serialize,Deserialize)]
 pub struct RawWorld {
@@ -161,7 +181,7 @@ pub struct RawWorld {
     pub build: Option<RawWorldBuild>,
     pub cargo: Option<RawWorldCargo>,
 }
-type RawWorldFile = RawWorld;
+pub type RawWorldFile = RawWorld;
 
 #[cfg(test)]
 mod test {
@@ -339,8 +359,8 @@ mod test {
         let raw_system : RawSystemFile = toml::from_str(toml_str).unwrap();
         assert_eq!(raw_system.event_handlers.len(), 2);
         assert_eq!(raw_system.queries.len(),2);
-        assert_eq!(raw_system.event_handlers[0].watch_for, "EntityDamaged");
-        assert_eq!(raw_system.event_handlers[1].watch_for, "EntityHealed");
+        assert_eq!(raw_system.event_handlers[0].watch_for.get_ref().as_str(), "EntityDamaged");
+        assert_eq!(raw_system.event_handlers[1].watch_for.get_ref().as_str(), "EntityHealed");
         assert_eq!(raw_system.queries[0].name, "living_entities");
         assert_eq!(raw_system.queries[1].name, "damaged_entities");
     }
@@ -358,15 +378,46 @@ mod test {
 
         [build]
         output_dir = "generated"
+        crate_name = "my_ai_world"
+        target = "lib"
 
         [cargo]
         # 标准 Cargo 配置
+        [cargo.dependencies]
+        rand = "0.9"
+
+        [cargo.profile.release]
+        lto = true
         "#;
         let raw_world : RawWorldFile = toml::from_str(toml_str).unwrap();
         assert_eq!(raw_world.normal.name, "MyAIWorld");
         assert_eq!(raw_world.normal.version, "0.1.0");
         assert_eq!(raw_world.normal.author, "Your Name");
-        assert_eq!(raw_world.build.unwrap().output_dir, "generated");
+        let build = raw_world.build.unwrap();
+        assert_eq!(build.output_dir.unwrap(), "generated");
+        assert_eq!(build.crate_name.unwrap().into_inner(), "my_ai_world");
+        assert_eq!(build.target, Some(RawWorldBuildTarget::Lib));
+        let cargo = raw_world.cargo.unwrap();
+        assert_eq!(cargo.dependencies["rand"].as_str(), Some("0.9"));
+        assert_eq!(cargo.profile["release"]["lto"].as_bool(), Some(true));
+        assert!(cargo.features.is_empty());
         println!("{:?}", raw_world.modules)
     }
+    #[test]
+    fn test_unknown_cargo_keys_are_rejected() {
+        let toml_str = r#"
+        [world]
+        name = "MyAIWorld"
+        version = "0.1.0"
+        author = "Your Name"
+
+        [modules]
+
+        [cargo.dependancies]
+        rand = "0.9"
+        "#;
+        let err = toml::from_str::<RawWorldFile>(toml_str).unwrap_err();
+        assert!(err.message().contains("unknown field `dependancies`"), "{}", err);
+        assert!(err.span().is_some());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use std::path::Path;
+use std::time::Duration;
+use serde::Deserialize;
+use crate::error::RuntimeError;
+
+///项目中配置文件的位置，相对于项目根目录
+pub const CONFIG_FILE: &str = "config/config.toml";
+
+///config/config.toml，未知的表(如 [logging])会被忽略
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct RuntimeConfig {
+    #[serde(default)]
+    pub engine: EngineConfig,
+}
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EngineConfig {
+    ///每秒的tick数，每次 step 虚拟时钟前进 1/tick_rate 秒
+    #[serde(default = "EngineConfig::default_tick_rate")]
+    pub tick_rate: f64,
+}
+impl EngineConfig {
+    fn default_tick_rate() -> f64 {
+        60.0
+    }
+    pub fn tick(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.tick_rate)
+    }
+}
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self { tick_rate: Self::default_tick_rate() }
+    }
+}
+impl RuntimeConfig {
+    ///读取项目根目录下的 config/config.toml，文件不存在时使用默认配置
+    pub fn load(project_root: impl AsRef<Path>) -> Result<Self, RuntimeError> {
+        let path = project_root.as_ref().join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|source| RuntimeError::Io { path: path.clone(), source })?;
+        let config: Self = toml::from_str(&content)
+            .map_err(|source| RuntimeError::Config { path, source })?;
+        config.validate()?;
+        Ok(config)
+    }
+    pub fn validate(&self) -> Result<(), RuntimeError> {
+        let tick_rate = self.engine.tick_rate;
+        if !tick_rate.is_finite() || tick_rate <= 0.0 {
+            return Err(RuntimeError::InvalidTickRate(tick_rate));
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_load_config() {
+        let root = std::env::temp_dir().join("aethaum_test_runtime_config");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("config")).unwrap();
+        assert_eq!(RuntimeConfig::load(&root).unwrap(), RuntimeConfig::default());
+
+        std::fs::write(root.join(CONFIG_FILE), "[engine]\ntick_rate = 20\n\n[logging]\nlevel = \"info\"\n").unwrap();
+        let config = RuntimeConfig::load(&root).unwrap();
+        assert_eq!(config.engine.tick_rate, 20.0);
+        assert_eq!(config.engine.tick(), Duration::from_millis(50));
+
+        std::fs::write(root.join(CONFIG_FILE), "[engine]\ntick_rate = 0\n").unwrap();
+        assert!(matches!(RuntimeConfig::load(&root), Err(RuntimeError::InvalidTickRate(_))));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
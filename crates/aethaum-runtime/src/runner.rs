@@ -0,0 +1,161 @@
+use std::path::Path;
+use std::time::Duration;
+use bevy_app::{App, Plugins};
+use bevy_ecs::schedule::{ExecutorKind, Schedules};
+use bevy_ecs::world::World;
+use bevy_time::{Real, Time, TimePlugin, TimeUpdateStrategy, Virtual};
+use crate::config::RuntimeConfig;
+use crate::error::RuntimeError;
+
+///无窗口的确定性运行器
+///
+///时间由虚拟时钟驱动：每次 tick 固定前进 1/tick_rate 秒，与真实时间无关，
+///因此可以远快于实时地推进世界，且相同的输入每次都得到相同的结果
+pub struct HeadlessRunner {
+    app: App,
+    tick: Duration,
+    ticks: u64,
+    prepared: bool,
+}
+impl HeadlessRunner {
+    pub fn new(config: &RuntimeConfig) -> Self {
+        let tick = config.engine.tick();
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(tick));
+        // 默认的 max_delta 会截断较低 tick_rate 下的单步时长
+        app.world_mut().resource_mut::<Time<Virtual>>().set_max_delta(tick);
+        Self { app, tick, ticks: 0, prepared: false }
+    }
+    ///读取项目根目录下的 config/config.toml 创建运行器
+    pub fn from_project(project_root: impl AsRef<Path>) -> Result<Self, RuntimeError> {
+        Ok(Self::new(&RuntimeConfig::load(project_root)?))
+    }
+    ///添加插件，通常为生成代码中各模块的插件
+    pub fn with_plugins<M>(mut self, plugins: impl Plugins<M>) -> Self {
+        self.app.add_plugins(plugins);
+        self
+    }
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+    pub fn world(&self) -> &World {
+        self.app.world()
+    }
+    pub fn world_mut(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+    ///每个tick的时长
+    pub fn tick_duration(&self) -> Duration {
+        self.tick
+    }
+    ///已经运行的tick数
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+    ///虚拟时钟经过的时间
+    pub fn elapsed(&self) -> Duration {
+        self.app.world().resource::<Time<Virtual>>().elapsed()
+    }
+    ///运行 n 个tick
+    pub fn step(&mut self, n: u64) {
+        self.prepare();
+        for _ in 0..n {
+            self.app.update();
+            self.ticks += 1;
+        }
+    }
+    ///运行覆盖 duration 所需的tick数(向上取整)，返回运行的tick数
+    pub fn run_for(&mut self, duration: Duration) -> u64 {
+        let ticks = duration.as_nanos().div_ceil(self.tick.as_nanos()) as u64;
+        self.step(ticks);
+        ticks
+    }
+    ///逐tick运行直到 predicate 返回 true，返回运行的tick数。运行前已满足时不运行
+    ///
+    ///predicate 永不满足时不会返回，需要上限时可在 predicate 中检查 [`Time`] 或计数
+    pub fn run_until(&mut self, mut predicate: impl FnMut(&World) -> bool) -> u64 {
+        let start = self.ticks;
+        while !predicate(self.app.world()) {
+            self.step(1);
+        }
+        self.ticks - start
+    }
+    ///第一次运行前完成插件的构建，并固定调度顺序与时钟起点
+    fn prepare(&mut self) {
+        if self.prepared {
+            return;
+        }
+        self.prepared = true;
+        self.app.finish();
+        self.app.cleanup();
+        // 单线程执行器按拓扑顺序逐个运行系统，避免并行带来的不确定性
+        for (_, schedule) in self.app.world_mut().resource_mut::<Schedules>().iter_mut() {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        }
+        // 真实时钟的第一次更新只记录起点，提前记录后第一个tick也能前进完整的时长
+        let mut real_time = self.app.world_mut().resource_mut::<Time<Real>>();
+        let startup = real_time.startup();
+        real_time.update_with_instant(startup);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_app::{Plugin, Update};
+    use bevy_ecs::prelude::*;
+    use crate::config::EngineConfig;
+
+    #[derive(Resource, Default)]
+    struct Trace {
+        elapsed: Duration,
+        deltas: Vec<Duration>,
+    }
+    struct TracePlugin;
+    impl Plugin for TracePlugin {
+        fn build(&self, app: &mut App) {
+            app.init_resource::<Trace>()
+                .add_systems(Update, |time: Res<Time>, mut trace: ResMut<Trace>| {
+                    trace.elapsed += time.delta();
+                    trace.deltas.push(time.delta());
+                });
+        }
+    }
+    fn runner(tick_rate: f64) -> HeadlessRunner {
+        HeadlessRunner::new(&RuntimeConfig { engine: EngineConfig { tick_rate } })
+            .with_plugins(TracePlugin)
+    }
+    #[test]
+    fn test_step_advances_virtual_clock() {
+        let mut runner = runner(10.0);
+        runner.step(3);
+        assert_eq!(runner.ticks(), 3);
+        assert_eq!(runner.elapsed(), Duration::from_millis(300));
+        assert_eq!(runner.world().resource::<Trace>().deltas, vec![Duration::from_millis(100); 3]);
+
+        assert_eq!(runner.run_for(Duration::from_millis(250)), 3);
+        assert_eq!(runner.elapsed(), Duration::from_millis(600));
+
+        let ticks = runner.run_until(|world| world.resource::<Trace>().elapsed >= Duration::from_secs(1));
+        assert_eq!(ticks, 4);
+        assert_eq!(runner.run_until(|_| true), 0);
+    }
+    #[test]
+    fn test_low_tick_rate_is_not_clamped() {
+        let mut runner = runner(1.0);
+        runner.step(2);
+        assert_eq!(runner.world().resource::<Trace>().elapsed, Duration::from_secs(2));
+    }
+    #[test]
+    fn test_runs_are_deterministic() {
+        let trace = || {
+            let mut runner = runner(60.0);
+            runner.run_for(Duration::from_secs(2));
+            runner.world().resource::<Trace>().deltas.clone()
+        };
+        assert_eq!(trace(), trace());
+    }
+}
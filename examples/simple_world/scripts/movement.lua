@@ -0,0 +1,3 @@
+-- 按速度更新位置
+entity.position.x = entity.position.x + entity.velocity.dx
+entity.position.y = entity.position.y + entity.velocity.dy
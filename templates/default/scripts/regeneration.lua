@@ -0,0 +1,2 @@
+-- 每次更新恢复1点生命值，不超过最大值
+entity.health.value = math.min(entity.health.value + 1, entity.health.max_value)
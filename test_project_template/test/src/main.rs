@@ -1,7 +1,35 @@
-use bevy::prelude::*;
+use std::time::Duration;
+use bevy_app::prelude::*;
+use bevy_app::ScheduleRunnerPlugin;
 mod modules;
 mod lua_bindings;
 mod aethaum_predefined;
+///config/config.toml 中 [engine] 的 tick_rate，文件或字段不存在时为60
+fn tick_rate() -> f64 {
+    let path = aethaum_predefined::project_root().join("config").join("config.toml");
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return 60.0;
+    };
+    let config: toml::Table = toml::from_str(&content)
+        .unwrap_or_else(|err| panic!("invalid {}: {}", path.display(), err));
+    let tick_rate = match config.get("engine").and_then(|engine| engine.get("tick_rate"))
+    {
+        Some(toml::Value::Float(tick_rate)) => *tick_rate,
+        Some(toml::Value::Integer(tick_rate)) => *tick_rate as f64,
+        Some(_) => panic!("tick_rate in {} must be a number", path.display()),
+        None => 60.0,
+    };
+    if !(tick_rate.is_finite() && tick_rate > 0.0) {
+        panic!("tick_rate in {} must be positive, got {}", path.display(), tick_rate);
+    }
+    tick_rate
+}
 fn main() {
-    App::new().add_plugins(DefaultPlugins).add_plugins(lua_bindings::LuaPlugin).run();
+    App::new()
+        .add_plugins(
+            ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / tick_rate())),
+        )
+        .add_plugins(lua_bindings::LuaPlugin)
+        .add_plugins(modules::AethaumModulesPlugin)
+        .run();
 }